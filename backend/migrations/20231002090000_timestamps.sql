@@ -0,0 +1,6 @@
+ALTER TABLE blogs
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+ALTER TABLE tags
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
@@ -0,0 +1,10 @@
+CREATE TABLE idempotency_keys
+(
+    scope         TEXT        NOT NULL,
+    key           TEXT        NOT NULL,
+    fingerprint   TEXT        NOT NULL,
+    status_code   INTEGER,
+    response_body TEXT,
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (scope, key)
+);
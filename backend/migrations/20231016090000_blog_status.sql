@@ -0,0 +1,4 @@
+CREATE TYPE blog_status AS ENUM ('draft', 'published');
+
+ALTER TABLE blogs
+    ADD COLUMN status blog_status NOT NULL DEFAULT 'published';
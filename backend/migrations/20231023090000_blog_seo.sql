@@ -0,0 +1,5 @@
+ALTER TABLE blogs
+    ADD COLUMN meta_description text,
+    ADD COLUMN canonical_url text,
+    ADD COLUMN noindex boolean NOT NULL DEFAULT false,
+    ADD COLUMN social_image text;
@@ -0,0 +1,9 @@
+ALTER TABLE blogs ADD COLUMN slug TEXT UNIQUE;
+
+CREATE TABLE import_sources
+(
+    source      TEXT        NOT NULL PRIMARY KEY,
+    blog_id     INTEGER     NOT NULL REFERENCES blogs (id) ON DELETE CASCADE,
+    checksum    TEXT        NOT NULL,
+    imported_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
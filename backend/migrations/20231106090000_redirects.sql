@@ -0,0 +1,6 @@
+CREATE TABLE redirects
+(
+    path       TEXT        NOT NULL PRIMARY KEY,
+    blog_id    INTEGER     NOT NULL REFERENCES blogs (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
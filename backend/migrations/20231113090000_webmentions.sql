@@ -0,0 +1,15 @@
+CREATE TYPE webmention_status AS ENUM ('pending', 'verified', 'rejected');
+
+CREATE TABLE webmentions
+(
+    id         SERIAL PRIMARY KEY,
+    blog_id    INTEGER           NOT NULL REFERENCES blogs (id) ON DELETE CASCADE,
+    source     TEXT              NOT NULL,
+    target     TEXT              NOT NULL,
+    status     webmention_status NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMPTZ       NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ       NOT NULL DEFAULT now(),
+    UNIQUE (source, target)
+);
+
+CREATE INDEX webmentions_blog_id_idx ON webmentions (blog_id, status);
@@ -0,0 +1,7 @@
+CREATE TABLE followers
+(
+    actor        TEXT        NOT NULL PRIMARY KEY,
+    inbox        TEXT        NOT NULL,
+    shared_inbox TEXT,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
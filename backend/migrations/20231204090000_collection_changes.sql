@@ -0,0 +1,21 @@
+CREATE TABLE collection_changes
+(
+    name       TEXT        NOT NULL PRIMARY KEY,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+INSERT INTO collection_changes (name) VALUES ('blogs'), ('tags');
+
+CREATE FUNCTION touch_collection() RETURNS trigger AS $$
+BEGIN
+    UPDATE collection_changes SET changed_at = now() WHERE name = TG_ARGV[0];
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER blogs_changed AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON blogs
+    FOR EACH STATEMENT EXECUTE FUNCTION touch_collection('blogs');
+CREATE TRIGGER blog_tags_changed AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON blog_tags
+    FOR EACH STATEMENT EXECUTE FUNCTION touch_collection('blogs');
+CREATE TRIGGER tags_changed AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON tags
+    FOR EACH STATEMENT EXECUTE FUNCTION touch_collection('tags');
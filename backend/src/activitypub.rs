@@ -0,0 +1,334 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Url;
+use serde_json::{json, Value};
+
+use crate::config::{ActivityPubConfig, SiteConfig};
+use crate::outbound::check_url;
+use crate::render;
+use crate::repositories::blog::{BlogEntity, BlogStatus};
+
+pub mod delivery;
+pub mod signature;
+
+pub const WEBFINGER_PATH: &str = "/.well-known/webfinger";
+pub const ACTOR_PATH: &str = "/activitypub/actor";
+pub const INBOX_PATH: &str = "/activitypub/inbox";
+pub const OUTBOX_PATH: &str = "/activitypub/outbox";
+pub const FOLLOWERS_PATH: &str = "/activitypub/followers";
+pub const CONTENT_TYPE: &str = "application/activity+json";
+pub const JRD_CONTENT_TYPE: &str = "application/jrd+json";
+pub const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+const CONTEXT: &str = "https://www.w3.org/ns/activitystreams";
+const SECURITY_CONTEXT: &str = "https://w3id.org/security/v1";
+
+pub fn actor_id(site: &SiteConfig) -> String {
+    site.url(ACTOR_PATH)
+}
+
+pub fn key_id(site: &SiteConfig) -> String {
+    format!("{}#main-key", actor_id(site))
+}
+
+fn timestamp(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// acct:username@host の host。base_url のスキームとパスを除いたもの
+fn host(site: &SiteConfig) -> &str {
+    let url = site.base_url.split_once("://").map(|(_, rest)| rest).unwrap_or(&site.base_url);
+    url.split('/').next().unwrap_or(url)
+}
+
+// acct:blog@example.com か、アクターの URL で問い合わせられる
+pub fn webfinger(resource: &str, site: &SiteConfig, config: &ActivityPubConfig) -> Option<Value> {
+    let account = format!("{}@{}", config.username, host(site));
+    let is_account = resource
+        .strip_prefix("acct:")
+        .map(|acct| acct.eq_ignore_ascii_case(&account))
+        .unwrap_or(false);
+    if !is_account && resource != actor_id(site) {
+        return None;
+    }
+    Some(json!({
+        "subject": format!("acct:{}", account),
+        "aliases": [actor_id(site)],
+        "links": [
+            { "rel": "self", "type": CONTENT_TYPE, "href": actor_id(site) },
+            { "rel": "http://webfinger.net/rel/profile-page", "type": "text/html", "href": site.url("/") }
+        ]
+    }))
+}
+
+pub fn actor(site: &SiteConfig, config: &ActivityPubConfig, public_key_pem: &str) -> Value {
+    json!({
+        "@context": [CONTEXT, SECURITY_CONTEXT],
+        "id": actor_id(site),
+        "type": "Person",
+        "preferredUsername": config.username,
+        "name": site.title,
+        "summary": render::escape_html(&site.description),
+        "url": site.url("/"),
+        "inbox": site.url(INBOX_PATH),
+        "outbox": site.url(OUTBOX_PATH),
+        "followers": site.url(FOLLOWERS_PATH),
+        "manuallyApprovesFollowers": false,
+        "endpoints": { "sharedInbox": site.url(INBOX_PATH) },
+        "publicKey": {
+            "id": key_id(site),
+            "owner": actor_id(site),
+            "publicKeyPem": public_key_pem
+        }
+    })
+}
+
+pub fn article(blog: &BlogEntity, site: &SiteConfig) -> Value {
+    let tags: Vec<Value> = blog
+        .tags
+        .iter()
+        .map(|tag| {
+            json!({
+                "type": "Hashtag",
+                "name": format!("#{}", tag.name),
+                "href": site.url(&format!("/tags/{}/", tag.id))
+            })
+        })
+        .collect();
+    json!({
+        "id": site.blog_url(blog.id),
+        "type": "Article",
+        "attributedTo": actor_id(site),
+        "name": blog.title,
+        "content": render::markdown_to_html(&blog.body),
+        "summary": blog.seo.description,
+        "url": site.blog_url(blog.id),
+        "published": timestamp(blog.created_at),
+        "updated": timestamp(blog.updated_at),
+        "to": [PUBLIC],
+        "cc": [site.url(FOLLOWERS_PATH)],
+        "tag": tags
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityType {
+    Create,
+    Update,
+    Delete,
+}
+
+impl ActivityType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ActivityType::Create => "Create",
+            ActivityType::Update => "Update",
+            ActivityType::Delete => "Delete",
+        }
+    }
+}
+
+// 記事の Create / Update / Delete。id は同じ変更なら同じになるように日時から作る
+pub fn activity(kind: ActivityType, blog: &BlogEntity, site: &SiteConfig) -> Value {
+    let (object, at) = match kind {
+        ActivityType::Create => (article(blog, site), blog.created_at),
+        ActivityType::Update => (article(blog, site), blog.updated_at),
+        ActivityType::Delete => (
+            json!({ "id": site.blog_url(blog.id), "type": "Tombstone" }),
+            Utc::now(),
+        ),
+    };
+    json!({
+        "@context": CONTEXT,
+        "id": format!("{}#{}-{}", site.blog_url(blog.id), kind.name().to_lowercase(), at.timestamp_millis()),
+        "type": kind.name(),
+        "actor": actor_id(site),
+        "published": timestamp(at),
+        "to": [PUBLIC],
+        "cc": [site.url(FOLLOWERS_PATH)],
+        "object": object
+    })
+}
+
+pub fn accept(follow: &Value, site: &SiteConfig) -> Value {
+    json!({
+        "@context": CONTEXT,
+        "id": format!("{}#accept-{}", actor_id(site), Utc::now().timestamp_millis()),
+        "type": "Accept",
+        "actor": actor_id(site),
+        "object": follow
+    })
+}
+
+// 公開中の記事の Create を新しい順に並べる
+pub fn outbox(blogs: &[BlogEntity], site: &SiteConfig) -> Value {
+    let items: Vec<Value> = blogs
+        .iter()
+        .filter(|blog| blog.status == BlogStatus::Published)
+        .map(|blog| activity(ActivityType::Create, blog, site))
+        .collect();
+    json!({
+        "@context": CONTEXT,
+        "id": site.url(OUTBOX_PATH),
+        "type": "OrderedCollection",
+        "totalItems": items.len(),
+        "orderedItems": items
+    })
+}
+
+// フォロワーの一覧は公開せず、数だけを返す
+pub fn followers(count: usize, site: &SiteConfig) -> Value {
+    json!({
+        "@context": CONTEXT,
+        "id": site.url(FOLLOWERS_PATH),
+        "type": "OrderedCollection",
+        "totalItems": count
+    })
+}
+
+fn object_id(value: &Value) -> Option<&str> {
+    value.as_str().or_else(|| value["id"].as_str())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InboxActivity {
+    Follow { actor: String },
+    Undo { actor: String },
+    // Like や Announce などは受け取るだけで何もしない
+    Other { actor: String },
+}
+
+impl InboxActivity {
+    pub fn actor(&self) -> &str {
+        match self {
+            InboxActivity::Follow { actor } | InboxActivity::Undo { actor } | InboxActivity::Other { actor } => actor,
+        }
+    }
+}
+
+pub fn parse_inbox(activity: &Value, site: &SiteConfig) -> Result<InboxActivity, String> {
+    let actor = object_id(&activity["actor"])
+        .ok_or_else(|| "actor is required".to_string())?
+        .to_string();
+    match activity["type"].as_str() {
+        Some("Follow") => match object_id(&activity["object"]) {
+            Some(object) if object == actor_id(site) => Ok(InboxActivity::Follow { actor }),
+            _ => Err(format!("only {} can be followed", actor_id(site))),
+        },
+        // フォロー以外の Undo (Like の取り消しなど) は無視する
+        Some("Undo") if activity["object"]["type"] == "Follow" => {
+            match object_id(&activity["object"]["actor"]) {
+                Some(follower) if follower == actor => Ok(InboxActivity::Undo { actor }),
+                _ => Err("can not undo a follow of another actor".to_string()),
+            }
+        }
+        Some(_) => Ok(InboxActivity::Other { actor }),
+        None => Err("type is required".to_string()),
+    }
+}
+
+// 署名の検証と配送に使う、リモートのアクターの情報
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteActor {
+    pub id: String,
+    pub inbox: String,
+    pub shared_inbox: Option<String>,
+    pub public_key_pem: String,
+}
+
+impl RemoteActor {
+    // inbox はアクターが書いた URL なので、フォロワーにする前に内部のネットワークを指していないか確かめる
+    pub fn check_inboxes(&self, allow_local: bool) -> Result<(), String> {
+        for inbox in std::iter::once(&self.inbox).chain(&self.shared_inbox) {
+            let url = Url::parse(inbox).map_err(|e| format!("invalid inbox {}: {}", inbox, e))?;
+            check_url(&url, allow_local)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_actor(value: &Value) -> Option<RemoteActor> {
+    Some(RemoteActor {
+        id: value["id"].as_str()?.to_string(),
+        inbox: value["inbox"].as_str()?.to_string(),
+        shared_inbox: value["endpoints"]["sharedInbox"].as_str().map(String::from),
+        public_key_pem: value["publicKey"]["publicKeyPem"].as_str()?.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::tag::Tag;
+
+    #[test]
+    fn build_actor_documents() {
+        let site = SiteConfig::default();
+        let config = ActivityPubConfig::default();
+        let webfinger = webfinger("acct:blog@localhost:3001", &site, &config).unwrap();
+        assert_eq!("http://localhost:3001/activitypub/actor", webfinger["links"][0]["href"]);
+        assert_eq!(Some(webfinger.clone()), super::webfinger("http://localhost:3001/activitypub/actor", &site, &config));
+        assert_eq!(None, super::webfinger("acct:other@localhost:3001", &site, &config));
+
+        let actor = actor(&site, &config, "PEM");
+        assert_eq!("blog", actor["preferredUsername"]);
+        assert_eq!("http://localhost:3001/activitypub/actor#main-key", actor["publicKey"]["id"]);
+        assert_eq!(Some(RemoteActor {
+            id: "http://localhost:3001/activitypub/actor".to_string(),
+            inbox: "http://localhost:3001/activitypub/inbox".to_string(),
+            shared_inbox: Some("http://localhost:3001/activitypub/inbox".to_string()),
+            public_key_pem: "PEM".to_string(),
+        }), parse_actor(&actor));
+
+        let mut blog = BlogEntity::new(3, "Hello".to_string(), "# heading".to_string(), vec![Tag::new(1, "rust".to_string())]);
+        let create = activity(ActivityType::Create, &blog, &site);
+        assert_eq!("Create", create["type"]);
+        assert_eq!("Article", create["object"]["type"]);
+        assert_eq!("<h1>heading</h1>\n", create["object"]["content"]);
+        assert_eq!("#rust", create["object"]["tag"][0]["name"]);
+        let delete = activity(ActivityType::Delete, &blog, &site);
+        assert_eq!(json!({ "id": "http://localhost:3001/blogs/3", "type": "Tombstone" }), delete["object"]);
+
+        blog.status = BlogStatus::Draft;
+        assert_eq!(0, outbox(&[blog], &site)["totalItems"]);
+    }
+
+    #[test]
+    fn reject_private_inboxes() {
+        let mut remote = RemoteActor {
+            id: "https://example.com/users/alice".to_string(),
+            inbox: "https://example.com/users/alice/inbox".to_string(),
+            shared_inbox: Some("https://example.com/inbox".to_string()),
+            public_key_pem: "PEM".to_string(),
+        };
+        assert_eq!(Ok(()), remote.check_inboxes(false));
+        remote.shared_inbox = Some("http://169.254.169.254/latest/meta-data".to_string());
+        assert!(remote.check_inboxes(false).is_err());
+        remote.shared_inbox = None;
+        remote.inbox = "http://127.0.0.1:3000/inbox".to_string();
+        assert!(remote.check_inboxes(false).is_err());
+        assert_eq!(Ok(()), remote.check_inboxes(true));
+        remote.inbox = "not a url".to_string();
+        assert!(remote.check_inboxes(true).is_err());
+    }
+
+    #[test]
+    fn parse_follow_and_undo() {
+        let site = SiteConfig::default();
+        let follow = json!({
+            "id": "https://example.com/follows/1",
+            "type": "Follow",
+            "actor": "https://example.com/users/alice",
+            "object": "http://localhost:3001/activitypub/actor"
+        });
+        let actor = "https://example.com/users/alice".to_string();
+        assert_eq!(Ok(InboxActivity::Follow { actor: actor.clone() }), parse_inbox(&follow, &site));
+        let undo = json!({ "type": "Undo", "actor": { "id": actor }, "object": follow });
+        assert_eq!(Ok(InboxActivity::Undo { actor: actor.clone() }), parse_inbox(&undo, &site));
+        let like = json!({ "type": "Like", "actor": actor, "object": "http://localhost:3001/blogs/1" });
+        assert_eq!(Ok(InboxActivity::Other { actor }), parse_inbox(&like, &site));
+
+        let other = json!({ "type": "Follow", "actor": "https://example.com/users/alice", "object": "https://example.com/users/bob" });
+        assert!(parse_inbox(&other, &site).is_err());
+        let undo = json!({ "type": "Undo", "actor": "https://example.com/users/mallory", "object": follow });
+        assert!(parse_inbox(&undo, &site).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::anyhow;
+use axum::http::HeaderMap;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use rsa::{
+    pkcs1::DecodeRsaPublicKey,
+    pkcs1v15::{Signature as RsaSignature, SigningKey, VerifyingKey},
+    pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePublicKey, LineEnding},
+    signature::{SignatureEncoding, Signer, Verifier},
+    RsaPrivateKey, RsaPublicKey,
+};
+use sha2::{Digest, Sha256};
+
+// 送るときに署名するヘッダ。Mastodon が検証に求めるものと同じ
+const SIGNED_HEADERS: [&str; 4] = ["(request-target)", "host", "date", "digest"];
+// Date がこれ以上ずれている署名は再送されたものとみなす
+const MAX_CLOCK_SKEW_HOURS: i64 = 12;
+
+// HTTP Signatures (draft-cavage-http-signatures) に使うアクターの鍵
+#[derive(Debug, Clone)]
+pub struct ActorKey {
+    private_key: RsaPrivateKey,
+    pub public_key_pem: String,
+}
+
+impl ActorKey {
+    pub fn from_pem(pem: &str) -> anyhow::Result<Self> {
+        let private_key = RsaPrivateKey::from_pkcs8_pem(pem)?;
+        let public_key_pem = private_key.to_public_key().to_public_key_pem(LineEnding::LF)?;
+        Ok(ActorKey { private_key, public_key_pem })
+    }
+
+    // Signature ヘッダの値。headers には host, date, digest が入っていること
+    pub fn sign(&self, key_id: &str, method: &str, path: &str, headers: &HeaderMap) -> anyhow::Result<String> {
+        let names: Vec<String> = SIGNED_HEADERS.iter().map(|name| name.to_string()).collect();
+        let signing_string = signing_string(method, path, &names, headers).map_err(|e| anyhow!(e))?;
+        let signature = SigningKey::<Sha256>::new(self.private_key.clone()).sign(signing_string.as_bytes());
+        Ok(format!(
+            r#"keyId="{}",algorithm="rsa-sha256",headers="{}",signature="{}""#,
+            key_id,
+            names.join(" "),
+            STANDARD.encode(signature.to_bytes())
+        ))
+    }
+}
+
+// Digest ヘッダの値
+pub fn digest(body: &[u8]) -> String {
+    format!("SHA-256={}", STANDARD.encode(Sha256::digest(body)))
+}
+
+fn signing_string(method: &str, path: &str, names: &[String], headers: &HeaderMap) -> Result<String, String> {
+    names
+        .iter()
+        .map(|name| match name.as_str() {
+            "(request-target)" => Ok(format!("(request-target): {} {}", method.to_lowercase(), path)),
+            name => {
+                let values: Vec<&str> = headers
+                    .get_all(name)
+                    .iter()
+                    .filter_map(|value| value.to_str().ok())
+                    .collect();
+                match values.is_empty() {
+                    true => Err(format!("signed header {} is missing", name)),
+                    false => Ok(format!("{}: {}", name, values.join(", "))),
+                }
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|lines| lines.join("\n"))
+}
+
+// 受け取った Signature ヘッダ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub key_id: String,
+    pub headers: Vec<String>,
+    signature: Vec<u8>,
+}
+
+impl Signature {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut key_id = None;
+        let mut headers = vec!["date".to_string()];
+        let mut signature = None;
+        for param in value.split(',') {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| format!("invalid signature parameter: {}", param))?;
+            let value = value.trim().trim_matches('"');
+            match key.trim() {
+                "keyId" => key_id = Some(value.to_string()),
+                "algorithm" if value != "rsa-sha256" && value != "hs2019" => {
+                    return Err(format!("unsupported algorithm: {}", value))
+                }
+                "headers" => headers = value.split_whitespace().map(|name| name.to_lowercase()).collect(),
+                "signature" => {
+                    signature = Some(STANDARD.decode(value).map_err(|e| format!("invalid signature: {}", e))?)
+                }
+                _ => {}
+            }
+        }
+        Ok(Signature {
+            key_id: key_id.ok_or_else(|| "keyId is required".to_string())?,
+            headers,
+            signature: signature.ok_or_else(|| "signature is required".to_string())?,
+        })
+    }
+
+    pub fn covers(&self, name: &str) -> bool {
+        self.headers.iter().any(|header| header == name)
+    }
+
+    // (request-target) と date が署名されていて、date が新しく、公開鍵で検証できること
+    pub fn verify(&self, method: &str, path: &str, headers: &HeaderMap, public_key_pem: &str) -> Result<(), String> {
+        if !self.covers("(request-target)") || !self.covers("date") {
+            return Err("(request-target) and date must be signed".to_string());
+        }
+        let date = headers
+            .get("date")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+            .ok_or_else(|| "invalid date header".to_string())?;
+        if (Utc::now() - date.with_timezone(&Utc)).num_seconds().abs() > Duration::hours(MAX_CLOCK_SKEW_HOURS).num_seconds() {
+            return Err("date is too far from now".to_string());
+        }
+
+        let public_key = RsaPublicKey::from_public_key_pem(public_key_pem)
+            .or_else(|_| RsaPublicKey::from_pkcs1_pem(public_key_pem))
+            .map_err(|e| format!("invalid public key: {}", e))?;
+        let signature = RsaSignature::try_from(self.signature.as_slice()).map_err(|e| e.to_string())?;
+        let signing_string = signing_string(method, path, &self.headers, headers)?;
+        VerifyingKey::<Sha256>::new(public_key)
+            .verify(signing_string.as_bytes(), &signature)
+            .map_err(|_| "signature does not match".to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::activitypub::delivery::test_utils::TEST_PRIVATE_KEY;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn sign_and_verify_requests() {
+        let key = ActorKey::from_pem(TEST_PRIVATE_KEY).unwrap();
+        let body = br#"{"type":"Follow"}"#;
+        let mut headers = HeaderMap::new();
+        headers.insert("host", HeaderValue::from_static("example.com"));
+        headers.insert("date", HeaderValue::from_str(&crate::handlers::cache::http_date(Utc::now())).unwrap());
+        headers.insert("digest", HeaderValue::from_str(&digest(body)).unwrap());
+        let value = key.sign("https://example.com/actor#main-key", "POST", "/inbox", &headers).unwrap();
+
+        let signature = Signature::parse(&value).unwrap();
+        assert_eq!("https://example.com/actor#main-key", signature.key_id);
+        assert!(signature.covers("digest"));
+        assert_eq!(Ok(()), signature.verify("POST", "/inbox", &headers, &key.public_key_pem));
+        assert!(signature.verify("POST", "/other", &headers, &key.public_key_pem).is_err());
+
+        headers.insert("digest", HeaderValue::from_str(&digest(b"tampered")).unwrap());
+        assert!(signature.verify("POST", "/inbox", &headers, &key.public_key_pem).is_err());
+        headers.insert("date", HeaderValue::from_static("Sun, 06 Nov 1994 08:49:37 GMT"));
+        assert_eq!(
+            Err("date is too far from now".to_string()),
+            signature.verify("POST", "/inbox", &headers, &key.public_key_pem)
+        );
+        assert!(Signature::parse(r#"keyId="a",algorithm="hmac-sha256",signature="AA==""#).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use axum::{
+    extract::Extension,
+    http::{header::LINK, HeaderName, HeaderValue},
+    routing::delete,
+    Router,
+};
+use tower_http::set_header::SetResponseHeaderLayer;
+
+use crate::handlers::tag::delete_tag;
+use crate::repositories::{
+    blog::BlogRepository,
+    idempotency::IdempotencyRepository,
+    tag::TagRepository,
+    webmention::WebmentionRepository,
+};
+
+// バージョンごとのルーター。リポジトリは create_app で Extension として共有するので、
+// v2 を作る場合も v2.rs を追加して /api/v2 に nest すればよい
+pub mod v1;
+
+pub const V1_PREFIX: &str = "/api/v1";
+
+// 旧パスから呼ばれたことをハンドラに伝えるためのマーカー
+#[derive(Debug, Clone, Copy)]
+pub struct LegacyApi;
+
+// バージョン無しの旧パス。v1 と同じハンドラに deprecation ヘッダを付けて返す
+pub fn legacy<
+    Blog: BlogRepository,
+    Tag: TagRepository,
+    Idempotency: IdempotencyRepository,
+    Webmention: WebmentionRepository,
+>(
+    sunset: &str,
+) -> Router {
+    let router = v1::router::<Blog, Tag, Idempotency, Webmention>()
+        .route("/tag/:id", delete(delete_tag::<Tag>));
+    deprecated(router, sunset)
+}
+
+// v1::static_router の旧パス
+pub fn legacy_static<Blog: BlogRepository>(sunset: &str) -> Router {
+    deprecated(v1::static_router::<Blog>(), sunset)
+}
+
+fn deprecated(router: Router, sunset: &str) -> Router {
+    let router = router
+        .layer(Extension(LegacyApi))
+        .layer(SetResponseHeaderLayer::overriding(
+            HeaderName::from_static("deprecation"),
+            HeaderValue::from_static("true"),
+        ))
+        .layer(SetResponseHeaderLayer::overriding(
+            LINK,
+            HeaderValue::from_static("</api/v1>; rel=\"successor-version\""),
+        ));
+
+    match HeaderValue::from_str(sunset) {
+        Ok(sunset) => router.layer(SetResponseHeaderLayer::overriding(
+            HeaderName::from_static("sunset"),
+            sunset,
+        )),
+        Err(_) => router,
+    }
+}
@@ -0,0 +1,71 @@
+use axum::{
+    routing::{delete, get, post, MethodRouter},
+    Router,
+};
+
+use crate::handlers::{
+    export::export_archive,
+    blog::{
+        all_blog, bulk_blog, create_blog, delete_blog, find_blog, find_blog_meta, replace_blog,
+        update_blog,
+    },
+    tag::{all_tag, create_tag, delete_tag},
+    webmention::blog_mentions,
+};
+use crate::repositories::{
+    blog::BlogRepository,
+    idempotency::IdempotencyRepository,
+    tag::TagRepository,
+    webmention::WebmentionRepository,
+};
+
+// v1 のエンドポイント。ルーターも OpenAPI ドキュメントのテストもこの一覧から作るので、
+// ここに追加したエンドポイントがドキュメントに無ければテストが失敗する
+pub fn routes<
+    Blog: BlogRepository,
+    Tag: TagRepository,
+    Idempotency: IdempotencyRepository,
+    Webmention: WebmentionRepository,
+>() -> Vec<(&'static str, MethodRouter)> {
+    vec![
+        ("/blogs", post(create_blog::<Blog, Idempotency>).get(all_blog::<Blog>)),
+        (
+            "/blogs/:id",
+            get(find_blog::<Blog>)
+                .delete(delete_blog::<Blog>)
+                .patch(update_blog::<Blog>)
+                .put(replace_blog::<Blog>),
+        ),
+        ("/blogs/:id/meta", get(find_blog_meta::<Blog>)),
+        ("/blogs/:id/mentions", get(blog_mentions::<Blog, Webmention>)),
+        ("/tags", post(create_tag::<Tag, Idempotency>).get(all_tag::<Tag>)),
+        ("/tags/:id", delete(delete_tag::<Tag>)),
+        ("/export", get(export_archive::<Blog, Tag>)),
+    ]
+}
+
+// axum 0.4 のルーター (matchit 0.4) は /blogs/bulk と /blogs/:id のように、固定のセグメントと
+// パラメーターが重なるパスを一つのルーターに登録できない。重なる固定のパスはこちらに登録し、
+// create_app で router より先に振り分ける
+pub fn static_routes<Blog: BlogRepository>() -> Vec<(&'static str, MethodRouter)> {
+    vec![("/blogs/bulk", post(bulk_blog::<Blog>))]
+}
+
+pub fn router<
+    Blog: BlogRepository,
+    Tag: TagRepository,
+    Idempotency: IdempotencyRepository,
+    Webmention: WebmentionRepository,
+>() -> Router {
+    into_router(routes::<Blog, Tag, Idempotency, Webmention>())
+}
+
+pub fn static_router<Blog: BlogRepository>() -> Router {
+    into_router(static_routes::<Blog>())
+}
+
+fn into_router(routes: Vec<(&'static str, MethodRouter)>) -> Router {
+    routes
+        .into_iter()
+        .fold(Router::new(), |router, (path, method_router)| router.route(path, method_router))
+}
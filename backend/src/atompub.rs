@@ -0,0 +1,324 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use crate::config::SiteConfig;
+use crate::feed::{blog_urn, updated, xml_text};
+use crate::import::html::html_to_markdown;
+use crate::render::escape_html;
+use crate::repositories::{
+    blog::{BlogEntity, BlogSeo, BlogStatus, CreateBlog},
+    tag::Tag,
+};
+
+pub const SERVICE_PATH: &str = "/atompub";
+pub const COLLECTION_PATH: &str = "/atompub/entries";
+pub const CATEGORIES_PATH: &str = "/atompub/categories";
+pub const SERVICE_CONTENT_TYPE: &str = "application/atomsvc+xml; charset=utf-8";
+pub const ENTRY_CONTENT_TYPE: &str = "application/atom+xml;type=entry;charset=utf-8";
+pub const CATEGORIES_CONTENT_TYPE: &str = "application/atomcat+xml; charset=utf-8";
+const ATOM_NS: &str = "http://www.w3.org/2005/Atom";
+const APP_NS: &str = "http://www.w3.org/2007/app";
+
+pub fn member_path(id: i32) -> String {
+    format!("{}/{}", COLLECTION_PATH, id)
+}
+
+fn timestamp(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn service(site: &SiteConfig) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<service xmlns="{app}" xmlns:atom="{atom}">
+  <workspace>
+    <atom:title>{title}</atom:title>
+    <collection href="{collection}">
+      <atom:title>{title}</atom:title>
+      <accept>application/atom+xml;type=entry</accept>
+      <categories href="{categories}" />
+    </collection>
+  </workspace>
+</service>
+"#,
+        app = APP_NS,
+        atom = ATOM_NS,
+        title = xml_text(&site.title),
+        collection = xml_text(&site.url(COLLECTION_PATH)),
+        categories = xml_text(&site.url(CATEGORIES_PATH)),
+    )
+}
+
+// タグを固定しない (fixed="no") ので、一覧に無いカテゴリを送るとタグが作られる
+pub fn categories(tags: &[Tag]) -> String {
+    let categories: String = tags
+        .iter()
+        .map(|tag| format!("\n  <atom:category term=\"{}\" />", xml_text(&tag.name)))
+        .collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<app:categories xmlns:app=\"{}\" xmlns:atom=\"{}\" fixed=\"no\">{}\n</app:categories>\n",
+        APP_NS, ATOM_NS, categories
+    )
+}
+
+// 編集しても内容が変わらないように、本文は HTML にせず Markdown のまま text で返す
+fn entry(blog: &BlogEntity, site: &SiteConfig, namespaces: &str) -> String {
+    let draft = match blog.status {
+        BlogStatus::Draft => "\n  <app:control>\n    <app:draft>yes</app:draft>\n  </app:control>",
+        BlogStatus::Published => "",
+    };
+    let categories: String = blog
+        .tags
+        .iter()
+        .map(|tag| format!("\n  <category term=\"{}\" />", xml_text(&tag.name)))
+        .collect();
+    let summary = match &blog.seo.description {
+        Some(description) => format!("\n  <summary type=\"text\">{}</summary>", xml_text(description)),
+        None => String::new(),
+    };
+    format!(
+        r#"<entry{namespaces}>
+  <id>{id}</id>
+  <title type="text">{title}</title>
+  <author>
+    <name>{author}</name>
+  </author>
+  <link rel="edit" href="{edit}" />
+  <link rel="alternate" type="text/html" href="{alternate}" />
+  <published>{published}</published>
+  <updated>{updated}</updated>
+  <app:edited>{updated}</app:edited>{draft}{categories}{summary}
+  <content type="text">{content}</content>
+</entry>"#,
+        namespaces = namespaces,
+        id = blog_urn(blog.id),
+        title = xml_text(&blog.title),
+        author = xml_text(&site.author),
+        edit = xml_text(&site.url(&member_path(blog.id))),
+        alternate = xml_text(&site.blog_url(blog.id)),
+        published = timestamp(blog.created_at),
+        updated = timestamp(blog.updated_at),
+        draft = draft,
+        categories = categories,
+        summary = summary,
+        content = xml_text(&blog.body),
+    )
+}
+
+pub fn entry_document(blog: &BlogEntity, site: &SiteConfig) -> String {
+    let namespaces = format!(" xmlns=\"{}\" xmlns:app=\"{}\"", ATOM_NS, APP_NS);
+    format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}\n", entry(blog, site, &namespaces))
+}
+
+// 下書きも含めた編集用のコレクション。blogs は更新の新しい順に並んでいること。
+// last_modified は記事の一覧が最後に変わった時刻
+pub fn collection(blogs: &[BlogEntity], site: &SiteConfig, last_modified: Option<DateTime<Utc>>) -> String {
+    let entries: String = blogs
+        .iter()
+        .map(|blog| format!("\n{}", entry(blog, site, "")))
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="{atom}" xmlns:app="{app}">
+<id>{id}</id>
+<title type="text">{title}</title>
+<updated>{updated}</updated>
+<link rel="self" href="{href}" />{entries}
+</feed>
+"#,
+        atom = ATOM_NS,
+        app = APP_NS,
+        id = xml_text(&site.url(COLLECTION_PATH)),
+        title = xml_text(&site.title),
+        updated = timestamp(updated(last_modified)),
+        href = xml_text(&site.url(COLLECTION_PATH)),
+        entries = entries,
+    )
+}
+
+// POST / PUT で送られた Atom エントリ
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AtomEntry {
+    pub title: String,
+    pub body: String,
+    pub categories: Vec<String>,
+    pub draft: bool,
+    pub published: Option<DateTime<Utc>>,
+    pub summary: Option<String>,
+}
+
+impl AtomEntry {
+    // seo は summary 以外を引き継ぐ元
+    pub fn into_create_blog(self, tags: Vec<i32>, slug: Option<String>, seo: BlogSeo) -> CreateBlog {
+        CreateBlog {
+            title: self.title,
+            body: self.body,
+            tags,
+            status: match self.draft {
+                true => BlogStatus::Draft,
+                false => BlogStatus::Published,
+            },
+            seo: BlogSeo {
+                description: self.summary,
+                ..seo
+            },
+            slug,
+            published_at: self.published,
+        }
+    }
+}
+
+fn attribute(e: &BytesStart, name: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|attribute| attribute.key == name)
+        .and_then(|attribute| attribute.unescaped_value().ok().map(|value| String::from_utf8_lossy(&value).to_string()))
+}
+
+pub fn parse_entry(xml: &str) -> Result<AtomEntry, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    // 要素のローカル名のスタック。名前空間の接頭辞は見ない
+    let mut stack: Vec<Vec<u8>> = Vec::new();
+    let mut entry = AtomEntry::default();
+    let mut found = false;
+    let mut text = String::new();
+    let mut content_type = String::new();
+    // type="xhtml" の content の中身は HTML として組み立て直す
+    let mut xhtml = String::new();
+    let in_xhtml = |stack: &[Vec<u8>], content_type: &str| {
+        stack.len() >= 2 && stack[1] == b"content" && content_type == "xhtml"
+    };
+    loop {
+        let event = reader.read_event(&mut buf).map_err(|e| e.to_string())?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let empty = matches!(event, Event::Empty(_));
+                if in_xhtml(&stack, &content_type) {
+                    let close = if empty { " /" } else { "" };
+                    xhtml.push_str(&format!("<{}{}>", String::from_utf8_lossy(e), close));
+                } else {
+                    match (stack.len(), e.local_name()) {
+                        (0, b"entry") => found = true,
+                        (0, _) => return Err("root element must be an Atom entry".to_string()),
+                        (1, b"content") => content_type = attribute(e, b"type").unwrap_or_default(),
+                        (1, b"category") => {
+                            if let Some(term) = attribute(e, b"term").filter(|term| !term.trim().is_empty()) {
+                                entry.categories.push(term.trim().to_string());
+                            }
+                        }
+                        _ => {}
+                    }
+                    text.clear();
+                }
+                if !empty {
+                    stack.push(e.local_name().to_vec());
+                }
+            }
+            Event::Text(ref e) | Event::CData(ref e) => {
+                let value = e.unescape_and_decode(&reader).map_err(|e| e.to_string())?;
+                match in_xhtml(&stack, &content_type) {
+                    true => xhtml.push_str(&escape_html(&value)),
+                    false => text.push_str(&value),
+                }
+            }
+            Event::End(ref e) => {
+                stack.pop();
+                if in_xhtml(&stack, &content_type) {
+                    xhtml.push_str(&format!("</{}>", String::from_utf8_lossy(e.name())));
+                    continue;
+                }
+                let path: Vec<&[u8]> = stack.iter().map(Vec::as_slice).collect();
+                match (path.as_slice(), e.local_name()) {
+                    ([b"entry"], b"title") => entry.title = text.trim().to_string(),
+                    ([b"entry"], b"summary") => entry.summary = Some(text.trim().to_string()).filter(|s| !s.is_empty()),
+                    ([b"entry"], b"published") => {
+                        let published = DateTime::parse_from_rfc3339(text.trim())
+                            .map_err(|_| format!("invalid published: {}", text.trim()))?;
+                        entry.published = Some(published.with_timezone(&Utc));
+                    }
+                    ([b"entry"], b"content") => {
+                        entry.body = match content_type.as_str() {
+                            "html" => html_to_markdown(&text),
+                            "xhtml" => html_to_markdown(&xhtml),
+                            _ => text.trim().to_string(),
+                        }
+                    }
+                    ([b"entry", b"control"], b"draft") => entry.draft = text.trim() == "yes",
+                    _ => {}
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    match found {
+        true => Ok(entry),
+        false => Err("Atom entry is required".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::feed::atom::test_utils::assert_valid_atom;
+
+    #[test]
+    fn parse_atom_entries() {
+        let entry = parse_entry(
+            r#"<?xml version="1.0"?>
+<entry xmlns="http://www.w3.org/2005/Atom" xmlns:app="http://www.w3.org/2007/app">
+  <title>Hello &amp; AtomPub</title>
+  <published>2023-11-20T09:00:00+09:00</published>
+  <category term="rust" /><category term=" axum "></category>
+  <app:control><app:draft>yes</app:draft></app:control>
+  <summary>short</summary>
+  <content type="html">&lt;p&gt;a &lt;b&gt;b&lt;/b&gt;&lt;/p&gt;</content>
+</entry>"#,
+        )
+        .unwrap();
+        assert_eq!("Hello & AtomPub", entry.title);
+        assert_eq!("a **b**", entry.body);
+        assert_eq!(vec!["rust", "axum"], entry.categories);
+        assert!(entry.draft);
+        assert_eq!(Some("short".to_string()), entry.summary);
+        assert_eq!("2023-11-20T00:00:00+00:00", entry.published.unwrap().to_rfc3339());
+
+        let entry = parse_entry(
+            r#"<atom:entry xmlns:atom="http://www.w3.org/2005/Atom"><atom:title>x</atom:title>
+<atom:content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml"><p>1 &lt; 2<br/><em>ok</em></p></div></atom:content></atom:entry>"#,
+        )
+        .unwrap();
+        assert_eq!("1 < 2  \n_ok_", entry.body);
+        assert!(!entry.draft);
+
+        assert!(parse_entry("<feed xmlns=\"http://www.w3.org/2005/Atom\" />").is_err());
+        assert!(parse_entry("not xml").is_err());
+    }
+
+    #[test]
+    fn render_service_collection_and_entry() {
+        let site = SiteConfig::default();
+        let mut blog = BlogEntity::new(1, "Hello".to_string(), "**body** <b>".to_string(), vec![Tag::new(1, "rust".to_string())]);
+        blog.status = BlogStatus::Draft;
+
+        let document = entry_document(&blog, &site);
+        assert!(document.contains(r#"<link rel="edit" href="http://localhost:3001/atompub/entries/1" />"#));
+        assert!(document.contains("<app:draft>yes</app:draft>"));
+        assert!(document.contains(r#"<content type="text">**body** &lt;b&gt;</content>"#));
+        // 返したエントリをそのまま PUT しても内容が変わらない
+        let parsed = parse_entry(&document).unwrap();
+        assert_eq!(("Hello", "**body** <b>", true), (parsed.title.as_str(), parsed.body.as_str(), parsed.draft));
+        assert_eq!(vec!["rust"], parsed.categories);
+
+        let feed = assert_valid_atom(&collection(&[blog], &site, None));
+        assert_eq!(1, feed.all("entry").len());
+        assert!(service(&site).contains(r#"<collection href="http://localhost:3001/atompub/entries">"#));
+        assert!(categories(&[Tag::new(1, "rust".to_string())]).contains(r#"<atom:category term="rust" />"#));
+    }
+}
@@ -0,0 +1,146 @@
+use argon2::{
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
+    Argon2,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::AuthConfig;
+use crate::repositories::user::{UserEntity, UserRepository};
+
+pub const LOGIN_PATH: &str = "/auth/login";
+pub const REFRESH_PATH: &str = "/auth/refresh";
+pub const REVOKE_PATH: &str = "/auth/revoke";
+// これより短いパスワードでユーザーを作らせない
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("fail hash password: {}", e))?;
+    Ok(hash.to_string())
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+        .unwrap_or(false)
+}
+
+// URL に入れても壊れない 256 bit の乱数。リフレッシュトークンなどに使う
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// 漏れても使えないよう、リフレッシュトークンは SHA-256 だけを保存する
+pub fn token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+// アクセストークンの中身。sub はユーザーの id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub name: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+pub fn issue_access_token(user: &UserEntity, config: &AuthConfig) -> anyhow::Result<String> {
+    let now = Utc::now();
+    let claims = Claims {
+        sub: user.id.to_string(),
+        name: user.username.clone(),
+        iat: now.timestamp(),
+        exp: (now + config.access_token_ttl).timestamp(),
+    };
+    let key = EncodingKey::from_secret(config.jwt_secret.as_bytes());
+    Ok(encode(&Header::new(Algorithm::HS256), &claims, &key)?)
+}
+
+// 署名と有効期限を確かめる。alg は HS256 以外を受け付けない
+pub fn decode_access_token(token: &str, config: &AuthConfig) -> Result<Claims, String> {
+    let key = DecodingKey::from_secret(config.jwt_secret.as_bytes());
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.leeway = 0;
+    decode::<Claims>(token, &key, &validation)
+        .map(|data| data.claims)
+        .map_err(|e| e.to_string())
+}
+
+// POST /auth/login と /auth/refresh のレスポンス (RFC 6749 5.1)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub refresh_token: String,
+}
+
+// アクセストークンと、保存したリフレッシュトークンの組を発行する
+pub async fn issue_tokens<U: UserRepository>(
+    repository: &U,
+    user: &UserEntity,
+    config: &AuthConfig,
+) -> anyhow::Result<TokenResponse> {
+    let refresh_token = random_token();
+    repository
+        .save_refresh_token(user.id, &token_hash(&refresh_token), Utc::now() + config.refresh_token_ttl)
+        .await?;
+    Ok(TokenResponse {
+        access_token: issue_access_token(user, config)?,
+        token_type: "Bearer".to_string(),
+        expires_in: config.access_token_ttl.num_seconds(),
+        refresh_token,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn hash_and_verify_password() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong horse", &hash));
+        assert!(!verify_password("correct horse", "not a hash"));
+        assert_ne!(hash, hash_password("correct horse").unwrap());
+    }
+
+    #[test]
+    fn issue_and_decode_access_token() {
+        let config = AuthConfig::default();
+        let user = UserEntity {
+            id: 1,
+            username: "alice".to_string(),
+            password_hash: String::new(),
+        };
+        let token = issue_access_token(&user, &config).unwrap();
+        let claims = decode_access_token(&token, &config).unwrap();
+        assert_eq!("1", claims.sub);
+        assert_eq!("alice", claims.name);
+        assert_eq!(config.access_token_ttl.num_seconds(), claims.exp - claims.iat);
+
+        let other = AuthConfig { jwt_secret: "other".to_string(), ..AuthConfig::default() };
+        assert!(decode_access_token(&token, &other).is_err());
+        let expired = AuthConfig { access_token_ttl: Duration::seconds(-1), ..AuthConfig::default() };
+        let token = issue_access_token(&user, &expired).unwrap();
+        assert!(decode_access_token(&token, &config).is_err());
+
+        assert_ne!(random_token(), random_token());
+        assert_eq!(64, token_hash("token").len());
+    }
+}
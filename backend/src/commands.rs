@@ -0,0 +1,160 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use std::path::Path;
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    sync::mpsc,
+};
+
+use crate::auth;
+use crate::config::SiteConfig;
+use crate::export;
+use crate::import::{self, ImportAction, ImportReport};
+use crate::repositories::{
+    blog::BlogRepositoryForDb,
+    import::ImportRepositoryForDb,
+    redirect::RedirectRepositoryForDb,
+    tag::TagRepositoryForDb,
+    user::{UserRepository, UserRepositoryForDb},
+};
+use crate::static_site::{self, Templates};
+
+const COMMANDS: &str = "create-user, export, export-static, import-markdown, import-wordpress";
+
+// `cargo run -- <command> [args...]` で実行するサブコマンド
+pub async fn run(command: &str, args: &[String], pool: PgPool) -> anyhow::Result<()> {
+    match command {
+        "create-user" => create_user(args.first().cloned(), pool).await,
+        "export" => export_to_file(args.first().cloned(), pool).await,
+        "export-static" => export_static(args, pool).await,
+        "import-markdown" => import_markdown(args, pool).await,
+        "import-wordpress" => import_wordpress(args, pool).await,
+        _ => Err(anyhow::anyhow!("unknown command [{}]. available: {}", command, COMMANDS)),
+    }
+}
+
+// create-user <username>。パスワードはコマンドラインの履歴に残らないよう標準入力から読む
+async fn create_user(username: Option<String>, pool: PgPool) -> anyhow::Result<()> {
+    let username = username.ok_or_else(|| anyhow::anyhow!("usage: create-user <username> < password"))?;
+    let mut password = String::new();
+    BufReader::new(tokio::io::stdin()).read_line(&mut password).await?;
+    let password = password.trim_end_matches(&['\r', '\n'][..]);
+    if password.chars().count() < auth::MIN_PASSWORD_LENGTH {
+        anyhow::bail!("password must be at least {} characters", auth::MIN_PASSWORD_LENGTH);
+    }
+    let user = UserRepositoryForDb::new(pool)
+        .create(&username, &auth::hash_password(password)?)
+        .await?;
+    tracing::info!("created user {} (id {})", user.username, user.id);
+    Ok(())
+}
+
+async fn export_to_file(path: Option<String>, pool: PgPool) -> anyhow::Result<()> {
+    let path = path.unwrap_or_else(|| export::file_name(Utc::now()));
+    let mut file = File::create(&path).await?;
+    let blog_repository = BlogRepositoryForDb::new(pool.clone());
+    let tag_repository = TagRepositoryForDb::new(pool);
+
+    let (sender, mut receiver) = mpsc::channel::<Vec<u8>>(8);
+    let write = async {
+        while let Some(chunk) = receiver.recv().await {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await
+    };
+    let (result, written) = tokio::join!(
+        export::write_archive(&blog_repository, &tag_repository, sender),
+        write
+    );
+    result?;
+    written?;
+    tracing::info!("exported to {}", path);
+    Ok(())
+}
+
+// export-static <dir> [--templates <dir>]
+async fn export_static(args: &[String], pool: PgPool) -> anyhow::Result<()> {
+    let usage = || anyhow::anyhow!("usage: export-static <dir> [--templates <dir>]");
+    let mut out = None;
+    let mut templates = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--templates" => templates = Some(args.next().ok_or_else(usage)?),
+            _ => out = Some(arg),
+        }
+    }
+    let out = Path::new(out.ok_or_else(usage)?);
+    let templates = Templates::load(templates.map(Path::new))?;
+    let report = static_site::build(
+        &BlogRepositoryForDb::new(pool.clone()),
+        &TagRepositoryForDb::new(pool),
+        &SiteConfig::from_env(),
+        &templates,
+        out,
+    )
+    .await?;
+    tracing::info!(
+        "exported static site to {}: written {}, unchanged {}, removed {}",
+        out.display(),
+        report.written,
+        report.unchanged,
+        report.removed,
+    );
+    Ok(())
+}
+
+// 取り込み元のパスと --apply。--apply を付けるまでは何が起きるかを表示するだけ
+fn import_args<'a>(args: &'a [String], usage: &str) -> anyhow::Result<(&'a str, bool)> {
+    let apply = args.iter().any(|arg| arg == "--apply");
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .ok_or_else(|| anyhow::anyhow!("usage: {} [--apply]", usage))?;
+    Ok((path, apply))
+}
+
+// import-markdown <dir> [--apply]
+async fn import_markdown(args: &[String], pool: PgPool) -> anyhow::Result<()> {
+    let (dir, apply) = import_args(args, "import-markdown <dir>")?;
+    let report = import::markdown::import_dir(
+        Path::new(dir),
+        &BlogRepositoryForDb::new(pool.clone()),
+        &TagRepositoryForDb::new(pool.clone()),
+        &ImportRepositoryForDb::new(pool.clone()),
+        &RedirectRepositoryForDb::new(pool),
+        !apply,
+    )
+    .await?;
+    print_report(&report, apply)
+}
+
+// import-wordpress <export.xml> [--apply]
+async fn import_wordpress(args: &[String], pool: PgPool) -> anyhow::Result<()> {
+    let (file, apply) = import_args(args, "import-wordpress <export.xml>")?;
+    let xml = tokio::fs::read_to_string(file).await?;
+    let report = import::wordpress::import_wxr(
+        &xml,
+        &BlogRepositoryForDb::new(pool.clone()),
+        &TagRepositoryForDb::new(pool.clone()),
+        &ImportRepositoryForDb::new(pool.clone()),
+        &RedirectRepositoryForDb::new(pool),
+        !apply,
+    )
+    .await?;
+    print_report(&report, apply)
+}
+
+fn print_report(report: &ImportReport, apply: bool) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(report)?);
+    tracing::info!(
+        "{}: create {}, update {}, skip {}, fail {}",
+        if apply { "imported" } else { "dry-run" },
+        report.count(ImportAction::Create),
+        report.count(ImportAction::Update),
+        report.count(ImportAction::Skip),
+        report.count(ImportAction::Fail),
+    );
+    Ok(())
+}
@@ -0,0 +1,364 @@
+use chrono::Duration;
+use std::{env, path::PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub api: ApiConfig,
+    pub cache: CacheConfig,
+    pub idempotency: IdempotencyConfig,
+    pub graphql: GraphqlConfig,
+    pub site: SiteConfig,
+    pub activitypub: ActivityPubConfig,
+    pub auth: AuthConfig,
+    pub outbound: OutboundConfig,
+    pub docs: DocsConfig,
+}
+
+impl AppConfig {
+    pub fn from_env() -> Self {
+        AppConfig {
+            api: ApiConfig::from_env(),
+            cache: CacheConfig::from_env(),
+            idempotency: IdempotencyConfig::from_env(),
+            graphql: GraphqlConfig::from_env(),
+            site: SiteConfig::from_env(),
+            activitypub: ActivityPubConfig::from_env(),
+            auth: AuthConfig::from_env(),
+            outbound: OutboundConfig::from_env(),
+            docs: DocsConfig::from_env(),
+        }
+    }
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            api: ApiConfig::default(),
+            cache: CacheConfig::default(),
+            idempotency: IdempotencyConfig::default(),
+            graphql: GraphqlConfig::default(),
+            site: SiteConfig::default(),
+            activitypub: ActivityPubConfig::default(),
+            auth: AuthConfig::default(),
+            outbound: OutboundConfig::default(),
+            docs: DocsConfig::default(),
+        }
+    }
+}
+
+// バージョン無しの旧パスの廃止予定日 (Sunset ヘッダ, HTTP-date)
+#[derive(Debug, Clone)]
+pub struct ApiConfig {
+    pub legacy_sunset: String,
+}
+
+impl ApiConfig {
+    const DEFAULT_LEGACY_SUNSET: &'static str = "Sun, 31 Mar 2024 23:59:59 GMT";
+
+    pub fn from_env() -> Self {
+        ApiConfig {
+            legacy_sunset: env_or("API_LEGACY_SUNSET", Self::DEFAULT_LEGACY_SUNSET),
+        }
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig {
+            legacy_sunset: Self::DEFAULT_LEGACY_SUNSET.to_string(),
+        }
+    }
+}
+
+// ルートごとの Cache-Control ヘッダ
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub blogs: String,
+    pub blog: String,
+    pub tags: String,
+    pub feed: String,
+    pub sitemap: String,
+}
+
+impl CacheConfig {
+    const DEFAULT: &'static str = "no-cache";
+
+    pub fn from_env() -> Self {
+        CacheConfig {
+            blogs: env_or("CACHE_CONTROL_BLOGS", Self::DEFAULT),
+            blog: env_or("CACHE_CONTROL_BLOG", Self::DEFAULT),
+            tags: env_or("CACHE_CONTROL_TAGS", Self::DEFAULT),
+            feed: env_or("CACHE_CONTROL_FEED", Self::DEFAULT),
+            sitemap: env_or("CACHE_CONTROL_SITEMAP", Self::DEFAULT),
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            blogs: Self::DEFAULT.to_string(),
+            blog: Self::DEFAULT.to_string(),
+            tags: Self::DEFAULT.to_string(),
+            feed: Self::DEFAULT.to_string(),
+            sitemap: Self::DEFAULT.to_string(),
+        }
+    }
+}
+
+// Idempotency-Key を保持しておく期間と、処理中のキーをロックしておく期間。
+// 処理中にプロセスが落ちても、lock_timeout を過ぎれば同じキーでリトライできる
+#[derive(Debug, Clone)]
+pub struct IdempotencyConfig {
+    pub window: Duration,
+    pub lock_timeout: Duration,
+}
+
+impl IdempotencyConfig {
+    const DEFAULT_WINDOW_SECS: i64 = 24 * 60 * 60;
+    const DEFAULT_LOCK_TIMEOUT_SECS: i64 = 60;
+
+    pub fn from_env() -> Self {
+        IdempotencyConfig {
+            window: Duration::seconds(
+                env_parse("IDEMPOTENCY_WINDOW_SECS").unwrap_or(Self::DEFAULT_WINDOW_SECS),
+            ),
+            lock_timeout: Duration::seconds(
+                env_parse("IDEMPOTENCY_LOCK_TIMEOUT_SECS").unwrap_or(Self::DEFAULT_LOCK_TIMEOUT_SECS),
+            ),
+        }
+    }
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        IdempotencyConfig {
+            window: Duration::seconds(Self::DEFAULT_WINDOW_SECS),
+            lock_timeout: Duration::seconds(Self::DEFAULT_LOCK_TIMEOUT_SECS),
+        }
+    }
+}
+
+// /docs の Swagger UI が読むファイル (swagger-ui-bundle.js, swagger-ui.css) を置くディレクトリ。
+// make vendor-swagger-ui で取得する。無ければ /docs は JavaScript を使わないリファレンスを返す
+#[derive(Debug, Clone)]
+pub struct DocsConfig {
+    pub assets_dir: PathBuf,
+}
+
+impl DocsConfig {
+    const DEFAULT_ASSETS_DIR: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/static/swagger-ui");
+
+    pub fn from_env() -> Self {
+        DocsConfig {
+            assets_dir: env_or("DOCS_ASSETS_DIR", Self::DEFAULT_ASSETS_DIR).into(),
+        }
+    }
+}
+
+impl Default for DocsConfig {
+    fn default() -> Self {
+        DocsConfig {
+            assets_dir: Self::DEFAULT_ASSETS_DIR.into(),
+        }
+    }
+}
+
+// GraphQL クエリの深さと複雑さの上限。GraphiQL はデフォルトでは開発ビルドだけで有効
+#[derive(Debug, Clone)]
+pub struct GraphqlConfig {
+    pub max_depth: usize,
+    pub max_complexity: usize,
+    pub graphiql: bool,
+}
+
+impl GraphqlConfig {
+    const DEFAULT_MAX_DEPTH: usize = 8;
+    const DEFAULT_MAX_COMPLEXITY: usize = 250;
+
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        GraphqlConfig {
+            max_depth: env_parse("GRAPHQL_MAX_DEPTH").unwrap_or(default.max_depth),
+            max_complexity: env_parse("GRAPHQL_MAX_COMPLEXITY").unwrap_or(default.max_complexity),
+            graphiql: env_parse("GRAPHIQL").unwrap_or(default.graphiql),
+        }
+    }
+}
+
+impl Default for GraphqlConfig {
+    fn default() -> Self {
+        GraphqlConfig {
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+            max_complexity: Self::DEFAULT_MAX_COMPLEXITY,
+            graphiql: cfg!(debug_assertions),
+        }
+    }
+}
+
+// フィードなどで外部に公開する URL とサイトの情報
+#[derive(Debug, Clone)]
+pub struct SiteConfig {
+    pub base_url: String,
+    pub title: String,
+    pub description: String,
+    pub author: String,
+    pub feed_limit: i64,
+    pub robots_disallow: Vec<String>,
+}
+
+impl SiteConfig {
+    const DEFAULT_BASE_URL: &'static str = "http://localhost:3001";
+    const DEFAULT_TITLE: &'static str = "next_blog";
+    const DEFAULT_FEED_LIMIT: i64 = 20;
+    const DEFAULT_ROBOTS_DISALLOW: &'static str = "/api/,/graphql";
+
+    pub fn from_env() -> Self {
+        SiteConfig {
+            base_url: env_or("SITE_BASE_URL", Self::DEFAULT_BASE_URL)
+                .trim_end_matches('/')
+                .to_string(),
+            title: env_or("SITE_TITLE", Self::DEFAULT_TITLE),
+            description: env_or("SITE_DESCRIPTION", ""),
+            author: env_or("SITE_AUTHOR", Self::DEFAULT_TITLE),
+            feed_limit: env_parse("FEED_LIMIT").unwrap_or(Self::DEFAULT_FEED_LIMIT),
+            robots_disallow: split_list(&env_or("ROBOTS_DISALLOW", Self::DEFAULT_ROBOTS_DISALLOW)),
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    pub fn blog_url(&self, id: i32) -> String {
+        self.url(&format!("/blogs/{}", id))
+    }
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        SiteConfig {
+            base_url: Self::DEFAULT_BASE_URL.to_string(),
+            title: Self::DEFAULT_TITLE.to_string(),
+            description: String::new(),
+            author: Self::DEFAULT_TITLE.to_string(),
+            feed_limit: Self::DEFAULT_FEED_LIMIT,
+            robots_disallow: split_list(Self::DEFAULT_ROBOTS_DISALLOW),
+        }
+    }
+}
+
+// ActivityPub のアカウント (@username@host) と HTTP Signatures に使う秘密鍵 (PKCS#8 PEM)。
+// 鍵が無ければ連合しない。配送に失敗したら retry_delay, その 2 倍, 4 倍... と待って max_attempts 回まで送る
+#[derive(Debug, Clone)]
+pub struct ActivityPubConfig {
+    pub username: String,
+    pub private_key: Option<String>,
+    pub retry_delay: Duration,
+    pub max_attempts: u32,
+}
+
+impl ActivityPubConfig {
+    const DEFAULT_USERNAME: &'static str = "blog";
+    const DEFAULT_RETRY_SECS: i64 = 60;
+    const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+    pub fn from_env() -> Self {
+        ActivityPubConfig {
+            username: env_or("ACTIVITYPUB_USERNAME", Self::DEFAULT_USERNAME),
+            private_key: env::var("ACTIVITYPUB_PRIVATE_KEY_PATH")
+                .ok()
+                .and_then(|path| std::fs::read_to_string(path).ok()),
+            retry_delay: Duration::seconds(
+                env_parse("ACTIVITYPUB_RETRY_SECS").unwrap_or(Self::DEFAULT_RETRY_SECS),
+            ),
+            max_attempts: env_parse("ACTIVITYPUB_MAX_ATTEMPTS").unwrap_or(Self::DEFAULT_MAX_ATTEMPTS),
+        }
+    }
+}
+
+impl Default for ActivityPubConfig {
+    fn default() -> Self {
+        ActivityPubConfig {
+            username: Self::DEFAULT_USERNAME.to_string(),
+            private_key: None,
+            retry_delay: Duration::seconds(Self::DEFAULT_RETRY_SECS),
+            max_attempts: Self::DEFAULT_MAX_ATTEMPTS,
+        }
+    }
+}
+
+// アクセストークン (JWT, HS256) の署名鍵と、アクセストークン・リフレッシュトークンの有効期間。
+// JWT_SECRET が無ければ起動ごとに作るので、再起動するとログインし直しになる
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    pub jwt_secret: String,
+    pub access_token_ttl: Duration,
+    pub refresh_token_ttl: Duration,
+}
+
+impl AuthConfig {
+    const DEFAULT_JWT_SECRET: &'static str = "next_blog-development-secret";
+    const DEFAULT_ACCESS_TOKEN_SECS: i64 = 15 * 60;
+    const DEFAULT_REFRESH_TOKEN_SECS: i64 = 30 * 24 * 60 * 60;
+
+    pub fn from_env() -> Self {
+        let jwt_secret = env::var("JWT_SECRET").ok().filter(|secret| !secret.is_empty());
+        AuthConfig {
+            jwt_secret: jwt_secret.unwrap_or_else(|| {
+                tracing::warn!("JWT_SECRET is not set. tokens are invalidated on restart");
+                crate::auth::random_token()
+            }),
+            access_token_ttl: Duration::seconds(
+                env_parse("AUTH_ACCESS_TOKEN_SECS").unwrap_or(Self::DEFAULT_ACCESS_TOKEN_SECS),
+            ),
+            refresh_token_ttl: Duration::seconds(
+                env_parse("AUTH_REFRESH_TOKEN_SECS").unwrap_or(Self::DEFAULT_REFRESH_TOKEN_SECS),
+            ),
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            jwt_secret: Self::DEFAULT_JWT_SECRET.to_string(),
+            access_token_ttl: Duration::seconds(Self::DEFAULT_ACCESS_TOKEN_SECS),
+            refresh_token_ttl: Duration::seconds(Self::DEFAULT_REFRESH_TOKEN_SECS),
+        }
+    }
+}
+
+// カンマ区切りの一覧。空なら何も無い
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+// 外から渡された URL (Webmention の source, ActivityPub のアクターなど) を取得するときの制限。
+// 内部のネットワークへのリクエスト (SSRF) を防ぐため、既定では公開アドレスの https / http にだけ繋ぐ。
+// allow_local は手元のサーバーを相手にする開発とテストのためのもの
+#[derive(Debug, Clone, Default)]
+pub struct OutboundConfig {
+    pub allow_local: bool,
+}
+
+impl OutboundConfig {
+    pub fn from_env() -> Self {
+        OutboundConfig {
+            allow_local: env_parse("OUTBOUND_ALLOW_LOCAL").unwrap_or(false),
+        }
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(key: &str) -> Option<T> {
+    env::var(key).ok().and_then(|value| value.parse().ok())
+}
+
+fn env_or(key: &str, default: &str) -> String {
+    env::var(key).unwrap_or(default.to_string())
+}
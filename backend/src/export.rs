@@ -0,0 +1,140 @@
+use chrono::{DateTime, Utc};
+use flate2::{write::GzEncoder, Compression};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tar::{Builder, Header};
+use tokio::sync::mpsc::Sender;
+
+use crate::render::{self, yaml_string};
+use crate::repositories::{
+    blog::{BlogField, BlogFields, BlogRepository},
+    tag::{Tag, TagRepository},
+    RepositoryError,
+};
+
+pub const CONTENT_TYPE: &str = "application/gzip";
+const FORMAT: &str = "next_blog-export";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+struct ManifestEntry {
+    id: i32,
+    slug: String,
+    path: String,
+    sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Manifest {
+    format: &'static str,
+    version: u32,
+    exported_at: DateTime<Utc>,
+    tags: String,
+    blogs: Vec<ManifestEntry>,
+}
+
+pub fn file_name(now: DateTime<Utc>) -> String {
+    format!("next_blog-{}.tar.gz", now.format("%Y%m%d%H%M%S"))
+}
+
+// tar に追加するたびに圧縮済みのバイト列を取り出して送るので、アーカイブ全体はメモリに載せない
+struct ArchiveWriter {
+    builder: Builder<GzEncoder<Vec<u8>>>,
+    sender: Sender<Vec<u8>>,
+}
+
+impl ArchiveWriter {
+    fn new(sender: Sender<Vec<u8>>) -> Self {
+        ArchiveWriter {
+            builder: Builder::new(GzEncoder::new(Vec::new(), Compression::default())),
+            sender,
+        }
+    }
+
+    async fn append(&mut self, path: &str, data: &[u8], mtime: DateTime<Utc>) -> anyhow::Result<()> {
+        let mut header = Header::new_gnu();
+        header.set_path(path)?;
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime.timestamp().max(0) as u64);
+        header.set_cksum();
+        self.builder.append(&header, data)?;
+        self.flush().await
+    }
+
+    async fn flush(&mut self) -> anyhow::Result<()> {
+        let chunk = std::mem::take(self.builder.get_mut().get_mut());
+        if !chunk.is_empty() {
+            self.sender.send(chunk).await?;
+        }
+        Ok(())
+    }
+
+    async fn finish(self) -> anyhow::Result<()> {
+        let chunk = self.builder.into_inner()?.finish()?;
+        if !chunk.is_empty() {
+            self.sender.send(chunk).await?;
+        }
+        Ok(())
+    }
+}
+
+fn tags_yaml(tags: &[Tag]) -> String {
+    tags.iter()
+        .map(|tag| format!("- id: {}\n  name: {}\n", tag.id, yaml_string(&tag.name)))
+        .collect()
+}
+
+// 1記事ずつ読み出して posts/{id}-{slug}.md, tags.yaml, manifest.json の順に書き出す
+pub async fn write_archive<B: BlogRepository, T: TagRepository>(
+    blog_repository: &B,
+    tag_repository: &T,
+    sender: Sender<Vec<u8>>,
+) -> anyhow::Result<()> {
+    let exported_at = Utc::now();
+    let mut writer = ArchiveWriter::new(sender);
+
+    let ids: Vec<i32> = blog_repository
+        .all_with(&BlogFields::new(vec![BlogField::Id]))
+        .await?
+        .into_iter()
+        .map(|blog| blog.id)
+        .rev()
+        .collect();
+    let mut entries = Vec::with_capacity(ids.len());
+    for id in ids {
+        let blog = match blog_repository.find(id).await {
+            Ok(blog) => blog,
+            // 書き出している間に削除された記事は飛ばす
+            Err(e) if matches!(e.downcast_ref::<RepositoryError>(), Some(RepositoryError::NotFound(_))) => continue,
+            Err(e) => return Err(e),
+        };
+        let slug = render::slug(&blog);
+        let path = format!("posts/{}-{}.md", blog.id, slug);
+        let markdown = render::markdown_with(
+            &blog,
+            &[("id", blog.id.to_string()), ("slug", yaml_string(&slug))],
+        );
+        writer.append(&path, markdown.as_bytes(), blog.updated_at).await?;
+        entries.push(ManifestEntry {
+            id: blog.id,
+            slug,
+            path,
+            sha256: format!("{:x}", Sha256::digest(markdown.as_bytes())),
+        });
+    }
+
+    let tags = tag_repository.all().await?;
+    writer.append("tags.yaml", tags_yaml(&tags).as_bytes(), exported_at).await?;
+
+    let manifest = Manifest {
+        format: FORMAT,
+        version: VERSION,
+        exported_at,
+        tags: "tags.yaml".to_string(),
+        blogs: entries,
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest)?;
+    writer.append("manifest.json", &manifest, exported_at).await?;
+    writer.finish().await
+}
@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use std::time::UNIX_EPOCH;
+use uuid::Uuid;
+
+use crate::render;
+use crate::repositories::blog::BlogEntity;
+
+pub mod atom;
+pub mod json;
+pub mod rss;
+
+// フィードの description に使う抜粋の文字数
+const EXCERPT_LENGTH: usize = 200;
+
+// XML 1.0 で使えない制御文字を取り除いてからエスケープする
+pub fn xml_text(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    render::escape_html(&value)
+}
+
+fn excerpt(blog: &BlogEntity) -> String {
+    render::excerpt(&blog.body, EXCERPT_LENGTH)
+}
+
+// サイトの URL が変わっても変わらないように、固定の名前空間から UUID v5 を作る
+fn urn(name: &str) -> String {
+    let namespace = Uuid::new_v5(&Uuid::NAMESPACE_URL, b"https://github.com/riiiiin/next_blog");
+    format!("urn:uuid:{}", Uuid::new_v5(&namespace, name.as_bytes()))
+}
+
+pub fn blog_urn(id: i32) -> String {
+    urn(&format!("blog:{}", id))
+}
+
+pub fn last_modified(blogs: &[BlogEntity]) -> Option<DateTime<Utc>> {
+    blogs.iter().map(|blog| blog.updated_at).max()
+}
+
+// フィード自体の更新日時。記事が無いときも毎回同じ内容 (ETag) になるよう、固定の日時にする
+pub fn updated(last_modified: Option<DateTime<Utc>>) -> DateTime<Utc> {
+    last_modified.unwrap_or_else(|| DateTime::from(UNIX_EPOCH))
+}
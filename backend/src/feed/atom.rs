@@ -0,0 +1,274 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use super::{blog_urn, excerpt, updated, urn, xml_text};
+use crate::config::SiteConfig;
+use crate::render;
+use crate::repositories::{blog::BlogEntity, tag::Tag};
+
+pub const CONTENT_TYPE: &str = "application/atom+xml; charset=utf-8";
+
+fn timestamp(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn entry(blog: &BlogEntity, site: &SiteConfig) -> String {
+    let categories: String = blog
+        .tags
+        .iter()
+        .map(|tag| format!("\n    <category term=\"{}\" />", xml_text(&tag.name)))
+        .collect();
+    format!(
+        r#"
+  <entry>
+    <id>{id}</id>
+    <title type="text">{title}</title>
+    <link rel="alternate" type="text/html" href="{link}" />
+    <published>{published}</published>
+    <updated>{updated}</updated>{categories}
+    <summary type="text">{summary}</summary>
+    <content type="html">{content}</content>
+  </entry>"#,
+        id = blog_urn(blog.id),
+        title = xml_text(&blog.title),
+        link = xml_text(&site.blog_url(blog.id)),
+        published = timestamp(blog.created_at),
+        updated = timestamp(blog.updated_at),
+        categories = categories,
+        summary = xml_text(&excerpt(blog)),
+        content = xml_text(&render::markdown_to_html(&blog.body)),
+    )
+}
+
+// tag を指定するとそのタグのフィード、無ければサイト全体のフィードを作る。
+// blogs は公開済みの新しい順に並んでいること。last_modified はフィードの updated にする
+pub fn atom(blogs: &[BlogEntity], site: &SiteConfig, tag: Option<&Tag>, last_modified: Option<DateTime<Utc>>) -> String {
+    let (id, title, self_path, alternate_path) = match tag {
+        Some(tag) => (
+            urn(&format!("tag:{}", tag.id)),
+            format!("{} - {}", site.title, tag.name),
+            format!("/tags/{}/feed.atom", tag.id),
+            format!("/tags/{}", tag.id),
+        ),
+        None => (urn("feed"), site.title.clone(), "/feed.atom".to_string(), "/".to_string()),
+    };
+    let entries: String = blogs.iter().map(|blog| entry(blog, site)).collect();
+    let updated = updated(last_modified);
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xml:lang="ja">
+  <id>{id}</id>
+  <title type="text">{title}</title>
+  <subtitle type="text">{subtitle}</subtitle>
+  <updated>{updated}</updated>
+  <author>
+    <name>{author}</name>
+  </author>
+  <link rel="self" type="application/atom+xml" href="{self_link}" />
+  <link rel="alternate" type="text/html" href="{alternate_link}" />
+  <generator>next_blog</generator>{entries}
+</feed>
+"#,
+        id = id,
+        title = xml_text(&title),
+        subtitle = xml_text(&site.description),
+        updated = timestamp(updated),
+        author = xml_text(&site.author),
+        self_link = xml_text(&site.url(&self_path)),
+        alternate_link = xml_text(&site.url(&alternate_path)),
+        entries = entries,
+    )
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use chrono::DateTime;
+    use quick_xml::{
+        events::{BytesStart, Event},
+        Reader,
+    };
+    use std::collections::{HashMap, HashSet};
+
+    #[derive(Debug, Default)]
+    pub struct Element {
+        pub name: String,
+        pub attributes: HashMap<String, String>,
+        pub text: String,
+        pub children: Vec<Element>,
+    }
+
+    impl Element {
+        pub fn all(&self, name: &str) -> Vec<&Element> {
+            self.children.iter().filter(|child| child.name == name).collect()
+        }
+
+        pub fn one(&self, name: &str) -> &Element {
+            let found = self.all(name);
+            assert_eq!(1, found.len(), "<{}> must contain exactly one <{}>", self.name, name);
+            found[0]
+        }
+
+        fn attribute(&self, name: &str) -> Option<&str> {
+            self.attributes.get(name).map(String::as_str)
+        }
+    }
+
+    fn element(start: &BytesStart, reader: &Reader<&[u8]>) -> Element {
+        let attributes = start
+            .attributes()
+            .map(|attribute| {
+                let attribute = attribute.unwrap();
+                (
+                    String::from_utf8(attribute.key.to_vec()).unwrap(),
+                    attribute.unescape_and_decode_value(reader).unwrap(),
+                )
+            })
+            .collect();
+        Element {
+            name: String::from_utf8(start.name().to_vec()).unwrap(),
+            attributes,
+            ..Element::default()
+        }
+    }
+
+    pub fn parse(xml: &str) -> Element {
+        let mut reader = Reader::from_str(xml);
+        reader.trim_text(true);
+        let mut buf = Vec::new();
+        let mut stack: Vec<Element> = vec![];
+        loop {
+            match reader.read_event(&mut buf).expect("invalid xml") {
+                Event::Start(start) => stack.push(element(&start, &reader)),
+                Event::Empty(start) => {
+                    let child = element(&start, &reader);
+                    stack.last_mut().unwrap().children.push(child);
+                }
+                Event::Text(text) => {
+                    let text = text.unescape_and_decode(&reader).unwrap();
+                    stack.last_mut().unwrap().text.push_str(&text);
+                }
+                Event::End(_) => {
+                    let closed = stack.pop().unwrap();
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(closed),
+                        None => return closed,
+                    }
+                }
+                Event::Eof => panic!("unexpected end of document"),
+                _ => {}
+            }
+            buf.clear();
+        }
+    }
+
+    fn assert_date(element: &Element) {
+        assert!(
+            DateTime::parse_from_rfc3339(&element.text).is_ok(),
+            "<{}> must be RFC 3339: {}",
+            element.name,
+            element.text
+        );
+    }
+
+    fn assert_text_construct(element: &Element) {
+        let kind = element.attribute("type").unwrap_or("text");
+        assert!(["text", "html", "xhtml"].contains(&kind), "invalid type of <{}>", element.name);
+    }
+
+    // RFC 4287 の必須要素と個数の制約を確認する
+    pub fn assert_valid_atom(xml: &str) -> Element {
+        let feed = parse(xml);
+        assert_eq!("feed", feed.name);
+        assert_eq!(Some("http://www.w3.org/2005/Atom"), feed.attribute("xmlns"));
+
+        assert!(feed.one("id").text.contains(':'), "feed id must be an IRI");
+        assert_text_construct(feed.one("title"));
+        assert_date(feed.one("updated"));
+        let self_links = feed
+            .all("link")
+            .into_iter()
+            .filter(|link| link.attribute("rel") == Some("self"))
+            .count();
+        assert_eq!(1, self_links);
+        let feed_has_author = !feed.all("author").is_empty();
+        for author in feed.all("author") {
+            assert!(!author.one("name").text.is_empty());
+        }
+
+        let mut ids = HashSet::new();
+        for entry in feed.all("entry") {
+            let id = &entry.one("id").text;
+            assert!(id.starts_with("urn:uuid:"), "entry id must be a URN: {}", id);
+            assert!(ids.insert(id.clone()), "duplicated entry id: {}", id);
+            assert_text_construct(entry.one("title"));
+            assert_date(entry.one("updated"));
+            assert!(entry.all("published").len() <= 1);
+            entry.all("published").into_iter().for_each(assert_date);
+            assert!(feed_has_author || !entry.all("author").is_empty(), "entry must have an author");
+            for link in entry.all("link") {
+                assert!(link.attribute("href").is_some());
+            }
+            let contents = entry.all("content");
+            assert!(contents.len() <= 1);
+            match contents.first() {
+                Some(content) => assert_text_construct(content),
+                None => assert!(
+                    entry
+                        .all("link")
+                        .iter()
+                        .any(|link| link.attribute("rel").unwrap_or("alternate") == "alternate"),
+                    "entry without content must have an alternate link"
+                ),
+            }
+            for category in entry.all("category") {
+                assert!(category.attribute("term").is_some());
+            }
+        }
+        feed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::test_utils::assert_valid_atom;
+    use super::*;
+
+    fn blogs() -> Vec<BlogEntity> {
+        vec![
+            BlogEntity::new(
+                2,
+                "Rust & <Atom>".to_string(),
+                "**bold** <i>raw</i>".to_string(),
+                vec![Tag::new(1, "rust".to_string())],
+            ),
+            BlogEntity::new(1, "first".to_string(), "body".to_string(), vec![]),
+        ]
+    }
+
+    #[test]
+    fn valid_atom_feed() {
+        let site = SiteConfig::default();
+        let feed = assert_valid_atom(&atom(&blogs(), &site, None, None));
+        let entries = feed.all("entry");
+        assert_eq!(2, entries.len());
+        assert_eq!("Rust & <Atom>", entries[0].one("title").text);
+        assert!(entries[0].one("content").text.contains("<p><strong>bold</strong> &lt;i&gt;raw&lt;/i&gt;</p>"));
+        assert_eq!("next_blog", feed.one("author").one("name").text);
+
+        let tag = Tag::new(1, "rust".to_string());
+        let feed = assert_valid_atom(&atom(&blogs()[..1], &site, Some(&tag), None));
+        assert_eq!("next_blog - rust", feed.one("title").text);
+        let empty = assert_valid_atom(&atom(&[], &site, Some(&tag), None));
+        assert_eq!("1970-01-01T00:00:00Z", empty.one("updated").text);
+    }
+
+    #[test]
+    fn stable_entry_ids() {
+        let mut site = SiteConfig::default();
+        let before = assert_valid_atom(&atom(&blogs(), &site, None, None));
+        site.base_url = "https://blog.example.com".to_string();
+        let after = assert_valid_atom(&atom(&blogs(), &site, None, None));
+        assert_eq!(before.one("id").text, after.one("id").text);
+        assert_eq!(before.all("entry")[0].one("id").text, after.all("entry")[0].one("id").text);
+        assert_ne!(before.all("entry")[0].one("id").text, before.all("entry")[1].one("id").text);
+    }
+}
@@ -0,0 +1,116 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::{blog_urn, excerpt};
+use crate::config::SiteConfig;
+use crate::render;
+use crate::repositories::blog::BlogEntity;
+
+pub const CONTENT_TYPE: &str = "application/feed+json; charset=utf-8";
+const VERSION: &str = "https://jsonfeed.org/version/1.1";
+pub const FEED_PATH: &str = "/feed.json";
+
+#[derive(Debug, Serialize)]
+pub struct Author {
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonFeed {
+    version: &'static str,
+    title: String,
+    home_page_url: String,
+    feed_url: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_url: Option<String>,
+    language: &'static str,
+    authors: Vec<Author>,
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Item {
+    id: String,
+    url: String,
+    title: String,
+    content_html: String,
+    summary: String,
+    date_published: DateTime<Utc>,
+    date_modified: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+impl Item {
+    fn new(blog: &BlogEntity, site: &SiteConfig) -> Self {
+        Item {
+            id: blog_urn(blog.id),
+            url: site.blog_url(blog.id),
+            title: blog.title.clone(),
+            content_html: render::markdown_to_html(&blog.body),
+            summary: excerpt(blog),
+            date_published: blog.created_at,
+            date_modified: blog.updated_at,
+            tags: blog.tags.iter().map(|tag| tag.name.clone()).collect(),
+        }
+    }
+}
+
+fn page_url(site: &SiteConfig, page: u32) -> String {
+    match page {
+        1 => site.url(FEED_PATH),
+        page => site.url(&format!("{}?page={}", FEED_PATH, page)),
+    }
+}
+
+// blogs は公開済みの新しい順に並んだ page ページ目。has_next なら next_url を付ける
+pub fn json_feed(blogs: &[BlogEntity], site: &SiteConfig, page: u32, has_next: bool) -> JsonFeed {
+    JsonFeed {
+        version: VERSION,
+        title: site.title.clone(),
+        home_page_url: site.url("/"),
+        feed_url: page_url(site, page),
+        description: site.description.clone(),
+        next_url: has_next.then(|| page_url(site, page + 1)),
+        language: "ja",
+        authors: vec![Author { name: site.author.clone() }],
+        items: blogs.iter().map(|blog| Item::new(blog, site)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::tag::Tag;
+
+    #[test]
+    fn json_feed_items() {
+        let blog = BlogEntity::new(
+            1,
+            "title".to_string(),
+            "*body*".to_string(),
+            vec![Tag::new(1, "rust".to_string())],
+        );
+        let site = SiteConfig::default();
+        let feed = serde_json::to_value(json_feed(&[blog.clone()], &site, 1, true)).unwrap();
+
+        assert_eq!(VERSION, feed["version"]);
+        assert_eq!("http://localhost:3001/feed.json", feed["feed_url"]);
+        assert_eq!("http://localhost:3001/feed.json?page=2", feed["next_url"]);
+        assert!(feed.get("description").is_none());
+        let item = &feed["items"][0];
+        assert_eq!(blog_urn(1), item["id"]);
+        assert_eq!("http://localhost:3001/blogs/1", item["url"]);
+        assert_eq!("<p><em>body</em></p>\n", item["content_html"]);
+        assert_eq!("body", item["summary"]);
+        assert_eq!(serde_json::json!(["rust"]), item["tags"]);
+        assert_eq!(serde_json::to_value(blog.created_at).unwrap(), item["date_published"]);
+
+        let feed = serde_json::to_value(json_feed(&[], &site, 2, false)).unwrap();
+        assert_eq!("http://localhost:3001/feed.json?page=2", feed["feed_url"]);
+        assert!(feed.get("next_url").is_none());
+        assert_eq!(serde_json::json!([]), feed["items"]);
+    }
+}
@@ -0,0 +1,92 @@
+use chrono::{DateTime, Utc};
+
+use super::{excerpt, updated, xml_text};
+use crate::config::SiteConfig;
+use crate::render;
+use crate::repositories::blog::BlogEntity;
+
+pub const CONTENT_TYPE: &str = "application/rss+xml; charset=utf-8";
+
+fn item(blog: &BlogEntity, site: &SiteConfig) -> String {
+    let link = xml_text(&site.blog_url(blog.id));
+    let categories: String = blog
+        .tags
+        .iter()
+        .map(|tag| format!("\n      <category>{}</category>", xml_text(&tag.name)))
+        .collect();
+    format!(
+        r#"
+    <item>
+      <title>{title}</title>
+      <link>{link}</link>
+      <guid isPermaLink="true">{link}</guid>
+      <description>{description}</description>
+      <pubDate>{pub_date}</pubDate>{categories}
+      <content:encoded>{content}</content:encoded>
+    </item>"#,
+        title = xml_text(&blog.title),
+        link = link,
+        description = xml_text(&excerpt(blog)),
+        pub_date = blog.created_at.to_rfc2822(),
+        categories = categories,
+        content = xml_text(&render::markdown_to_html(&blog.body)),
+    )
+}
+
+// blogs は公開済みの新しい順に並んでいること
+pub fn rss(blogs: &[BlogEntity], site: &SiteConfig, last_modified: Option<DateTime<Utc>>) -> String {
+    let items: String = blogs.iter().map(|blog| item(blog, site)).collect();
+    let last_build_date = updated(last_modified);
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/" xmlns:atom="http://www.w3.org/2005/Atom">
+  <channel>
+    <title>{title}</title>
+    <link>{link}</link>
+    <description>{description}</description>
+    <language>ja</language>
+    <lastBuildDate>{last_build_date}</lastBuildDate>
+    <atom:link href="{self_link}" rel="self" type="application/rss+xml" />{items}
+  </channel>
+</rss>
+"#,
+        title = xml_text(&site.title),
+        link = xml_text(&site.url("/")),
+        description = xml_text(&site.description),
+        last_build_date = last_build_date.to_rfc2822(),
+        self_link = xml_text(&site.url("/feed.rss")),
+        items = items,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::tag::Tag;
+
+    #[test]
+    fn escape_special_characters() {
+        let mut blog = BlogEntity::new(
+            1,
+            "Tom & Jerry <3 \"quotes\"".to_string(),
+            "body with <b>html</b> & **markdown**".to_string(),
+            vec![Tag::new(1, "C&C++".to_string())],
+        );
+        blog.title.push('\u{0008}');
+        let rss = rss(&[blog], &SiteConfig::default(), None);
+
+        assert!(rss.contains("<title>Tom &amp; Jerry &lt;3 &quot;quotes&quot;</title>"));
+        assert!(rss.contains("<category>C&amp;C++</category>"));
+        assert!(rss.contains("<link>http://localhost:3001/blogs/1</link>"));
+        assert!(rss.contains("&lt;strong&gt;markdown&lt;/strong&gt;"));
+        assert!(!rss.contains("<b>"));
+        assert!(!rss.contains('\u{0008}'));
+    }
+
+    #[test]
+    fn stable_empty_feed() {
+        let empty = rss(&[], &SiteConfig::default(), None);
+        assert!(empty.contains("1970 00:00:00 +0000</lastBuildDate>"));
+        assert_eq!(empty, rss(&[], &SiteConfig::default(), None));
+    }
+}
@@ -0,0 +1,382 @@
+use async_graphql::{
+    async_trait,
+    dataloader::{DataLoader, Loader},
+    Context, EmptySubscription, Enum, Error, InputObject, Object, Result, Schema, SimpleObject,
+};
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, future::Future, marker::PhantomData, pin::Pin, sync::Arc};
+use validator::Validate;
+
+use crate::config::GraphqlConfig;
+use crate::handlers::auth::AuthUser;
+use crate::repositories::{
+    blog::{
+        self, BlogEntity, BlogField, BlogFields, BlogRepository, BlogSeo, CreateBlog, TagCount, UpdateBlog,
+    },
+    tag::{self, TagRepository},
+};
+use crate::webmention::WebmentionQueue;
+
+pub type BlogSchema<B, T> = Schema<QueryRoot<B, T>, MutationRoot<B, T>, EmptySubscription>;
+
+pub fn schema<B: BlogRepository, T: TagRepository>(
+    blog_repository: B,
+    tag_repository: T,
+    config: &GraphqlConfig,
+) -> BlogSchema<B, T> {
+    let blog_repository = Arc::new(blog_repository);
+    let loader = TagLoader::new(blog_repository.clone());
+    Schema::build(
+        QueryRoot::<B, T>(PhantomData),
+        MutationRoot::<B, T>(PhantomData),
+        EmptySubscription,
+    )
+    .data(blog_repository)
+    .data(Arc::new(tag_repository))
+    .data(DataLoader::new(loader, tokio::spawn))
+    .limit_depth(config.max_depth)
+    .limit_complexity(config.max_complexity)
+    .finish()
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "blog::BlogStatus")]
+pub enum BlogStatus {
+    Draft,
+    Published,
+}
+
+#[derive(SimpleObject, Clone)]
+pub struct Tag {
+    id: i32,
+    name: String,
+}
+
+impl From<tag::Tag> for Tag {
+    fn from(tag: tag::Tag) -> Self {
+        Tag { id: tag.id, name: tag.name }
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+pub struct TagUsage {
+    id: i32,
+    name: String,
+    count: i64,
+}
+
+impl From<TagCount> for TagUsage {
+    fn from(count: TagCount) -> Self {
+        TagUsage { id: count.id, name: count.name, count: count.count }
+    }
+}
+
+// ブログごとのタグをまとめて1回のクエリで読むためのローダー。
+// リポジトリの型をスキーマの型から消すためにクロージャで持つ
+type TagsFuture = Pin<Box<dyn Future<Output = anyhow::Result<HashMap<i32, Vec<tag::Tag>>>> + Send>>;
+type LoadTags = dyn Fn(Vec<i32>) -> TagsFuture + Send + Sync;
+
+pub struct TagLoader {
+    load: Arc<LoadTags>,
+}
+
+impl TagLoader {
+    fn new<B: BlogRepository>(repository: Arc<B>) -> Self {
+        TagLoader {
+            load: Arc::new(move |ids: Vec<i32>| {
+                let repository = repository.clone();
+                Box::pin(async move { repository.tags_for(&ids).await })
+            }),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<i32> for TagLoader {
+    type Value = Vec<tag::Tag>;
+    type Error = Arc<anyhow::Error>;
+
+    async fn load(&self, keys: &[i32]) -> std::result::Result<HashMap<i32, Self::Value>, Self::Error> {
+        (self.load)(keys.to_vec()).await.map_err(Arc::new)
+    }
+}
+
+pub struct Blog(BlogEntity);
+
+#[Object]
+impl Blog {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn title(&self) -> &str {
+        &self.0.title
+    }
+
+    async fn body(&self) -> &str {
+        &self.0.body
+    }
+
+    async fn status(&self) -> BlogStatus {
+        self.0.status.into()
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn updated_at(&self) -> DateTime<Utc> {
+        self.0.updated_at
+    }
+
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        let loader = ctx.data::<DataLoader<TagLoader>>()?;
+        let tags = loader.load_one(self.0.id).await?.unwrap_or_default();
+        Ok(tags.into_iter().map(Tag::from).collect())
+    }
+}
+
+// body が選択されていなければ SQL でも読まない
+fn blog_fields(ctx: &Context<'_>) -> BlogFields {
+    let mut fields = BlogFields::without(BlogField::Body);
+    if ctx.look_ahead().field("body").exists() {
+        fields.include(BlogField::Body);
+    }
+    fields
+}
+
+pub struct QueryRoot<B, T>(PhantomData<(B, T)>);
+
+#[Object]
+impl<B: BlogRepository, T: TagRepository> QueryRoot<B, T> {
+    async fn blog(&self, ctx: &Context<'_>, id: i32) -> Result<Option<Blog>> {
+        let repository = ctx.data::<Arc<B>>()?;
+        Ok(repository.find_with(id, &blog_fields(ctx)).await.ok().map(Blog))
+    }
+
+    #[graphql(complexity = "first as usize * child_complexity")]
+    async fn blogs(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 20, validator(minimum = 1, maximum = 100))] first: i32,
+        #[graphql(default = 0, validator(minimum = 0))] offset: i32,
+        status: Option<BlogStatus>,
+    ) -> Result<Vec<Blog>> {
+        let repository = ctx.data::<Arc<B>>()?;
+        let status = status.map(blog::BlogStatus::from);
+        let blogs = repository
+            .page_with(&blog_fields(ctx), status, first as i64, offset as i64)
+            .await?;
+        Ok(blogs.into_iter().map(Blog).collect())
+    }
+
+    #[graphql(complexity = "limit as usize * child_complexity")]
+    async fn related_blogs(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        #[graphql(default = 5, validator(minimum = 1, maximum = 20))] limit: i32,
+    ) -> Result<Vec<Blog>> {
+        let repository = ctx.data::<Arc<B>>()?;
+        let blogs = repository.related(id, limit as i64).await?;
+        Ok(blogs.into_iter().map(Blog).collect())
+    }
+
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        let repository = ctx.data::<Arc<T>>()?;
+        let tags = repository.all().await?;
+        Ok(tags.into_iter().map(Tag::from).collect())
+    }
+
+    async fn tag_counts(&self, ctx: &Context<'_>) -> Result<Vec<TagUsage>> {
+        let repository = ctx.data::<Arc<B>>()?;
+        let counts = repository.tag_counts().await?;
+        Ok(counts.into_iter().map(TagUsage::from).collect())
+    }
+}
+
+#[derive(InputObject)]
+pub struct CreateBlogInput {
+    title: String,
+    body: String,
+    #[graphql(default)]
+    tags: Vec<i32>,
+    #[graphql(default_with = "BlogStatus::Published")]
+    status: BlogStatus,
+}
+
+#[derive(InputObject)]
+pub struct UpdateBlogInput {
+    title: Option<String>,
+    body: Option<String>,
+    tags: Option<Vec<i32>>,
+    status: Option<BlogStatus>,
+}
+
+fn validated<V: Validate>(payload: V) -> Result<V> {
+    payload
+        .validate()
+        .map_err(|e| Error::new(format!("Validation error: [{}]", e).replace('\n', ", ")))?;
+    Ok(payload)
+}
+
+pub struct MutationRoot<B, T>(PhantomData<(B, T)>);
+
+// WebmentionQueue はリクエストごとに graphql_handler から渡される。単体テストのスキーマには無い
+fn notify_webmentions(ctx: &Context<'_>, blog_id: i32) {
+    if let Some(webmentions) = ctx.data_opt::<WebmentionQueue>() {
+        webmentions.send(blog_id);
+    }
+}
+
+// アクセストークンが有効なら graphql_handler が AuthUser を渡す
+fn authorize(ctx: &Context<'_>) -> Result<()> {
+    ctx.data_opt::<AuthUser>()
+        .map(|_| ())
+        .ok_or_else(|| Error::new("Unauthorized: access token is required"))
+}
+
+#[Object]
+impl<B: BlogRepository, T: TagRepository> MutationRoot<B, T> {
+    async fn create_blog(&self, ctx: &Context<'_>, input: CreateBlogInput) -> Result<Blog> {
+        authorize(ctx)?;
+        let repository = ctx.data::<Arc<B>>()?;
+        let payload = validated(CreateBlog {
+            title: input.title,
+            body: input.body,
+            tags: input.tags,
+            status: input.status.into(),
+            seo: BlogSeo::default(),
+            slug: None,
+            published_at: None,
+        })?;
+        let blog = repository.create(payload).await?;
+        notify_webmentions(ctx, blog.id);
+        Ok(Blog(blog))
+    }
+
+    async fn update_blog(&self, ctx: &Context<'_>, id: i32, input: UpdateBlogInput) -> Result<Blog> {
+        authorize(ctx)?;
+        let repository = ctx.data::<Arc<B>>()?;
+        let payload = validated(UpdateBlog {
+            title: input.title.map(Some),
+            body: input.body.map(Some),
+            tags: input.tags.map(Some),
+            status: input.status.map(|status| Some(status.into())),
+            seo: None,
+            slug: None,
+        })?;
+        let blog = repository.update(id, payload).await?;
+        notify_webmentions(ctx, blog.id);
+        Ok(Blog(blog))
+    }
+
+    async fn delete_blog(&self, ctx: &Context<'_>, id: i32) -> Result<bool> {
+        authorize(ctx)?;
+        let repository = ctx.data::<Arc<B>>()?;
+        repository.delete(id).await?;
+        Ok(true)
+    }
+
+    async fn create_tag(&self, ctx: &Context<'_>, name: String) -> Result<Tag> {
+        authorize(ctx)?;
+        if name.is_empty() || name.chars().count() > 100 {
+            return Err(Error::new("Validation error: [name: length must be 1 to 100]"));
+        }
+        let repository = ctx.data::<Arc<T>>()?;
+        Ok(repository.create(name).await?.into())
+    }
+
+    async fn delete_tag(&self, ctx: &Context<'_>, id: i32) -> Result<bool> {
+        authorize(ctx)?;
+        let repository = ctx.data::<Arc<T>>()?;
+        repository.delete(id).await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        blog::test_utils::BlogRepositoryForMemory,
+        tag::test_utils::TagRepositoryForMemory,
+    };
+
+    async fn fixture(config: &GraphqlConfig) -> BlogSchema<BlogRepositoryForMemory, TagRepositoryForMemory> {
+        let tags = vec![tag::Tag::new(1, "rust".to_string()), tag::Tag::new(2, "axum".to_string())];
+        let blog_repository = BlogRepositoryForMemory::new(tags);
+        for (title, tags) in [("first", vec![1]), ("second", vec![1, 2]), ("third", vec![2])] {
+            blog_repository
+                .create(CreateBlog {
+                    title: title.to_string(),
+                    body: format!("{} body", title),
+                    tags,
+                    status: blog::BlogStatus::Published,
+                    seo: BlogSeo::default(),
+                    slug: None,
+                    published_at: None,
+                })
+                .await
+                .unwrap();
+        }
+        schema(blog_repository, TagRepositoryForMemory::new(), config)
+    }
+
+    #[tokio::test]
+    async fn query_blog_with_tags_related_and_counts() {
+        let schema = fixture(&GraphqlConfig::default()).await;
+        let res = schema
+            .execute(
+                r#"{
+                    blog(id: 2) { title tags { name } }
+                    relatedBlogs(id: 1) { id }
+                    tagCounts { name count }
+                }"#,
+            )
+            .await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        assert_eq!("second", data["blog"]["title"]);
+        assert_eq!(serde_json::json!([{"name": "rust"}, {"name": "axum"}]), data["blog"]["tags"]);
+        assert_eq!(serde_json::json!([{"id": 2}]), data["relatedBlogs"]);
+        assert_eq!(serde_json::json!([
+            {"name": "rust", "count": 2},
+            {"name": "axum", "count": 2}
+        ]), data["tagCounts"]);
+    }
+
+    #[tokio::test]
+    async fn reject_too_deep_and_too_complex_query() {
+        let config = GraphqlConfig { max_depth: 2, ..GraphqlConfig::default() };
+        let schema = fixture(&config).await;
+        let res = schema.execute("{ blog(id: 1) { tags { name } } }").await;
+        assert_eq!(1, res.errors.len());
+        assert!(res.errors[0].message.contains("nested too deep"));
+
+        let config = GraphqlConfig { max_complexity: 50, ..GraphqlConfig::default() };
+        let schema = fixture(&config).await;
+        let res = schema.execute("{ blogs(first: 100) { id title } }").await;
+        assert_eq!(1, res.errors.len());
+        assert!(res.errors[0].message.contains("too complex"));
+    }
+
+    #[tokio::test]
+    async fn create_blog_with_validation() {
+        let schema = fixture(&GraphqlConfig::default()).await;
+        let user = AuthUser { id: 1, username: "alice".to_string() };
+        let mutation = |query: &str| async_graphql::Request::new(query).data(user.clone());
+        let res = schema
+            .execute(mutation(r#"mutation { createBlog(input: { title: "", body: "body" }) { id } }"#))
+            .await;
+        assert!(!res.errors.is_empty());
+
+        let create = r#"mutation { createBlog(input: { title: "new", body: "body", tags: [2] }) { id tags { id } } }"#;
+        let res = schema.execute(create).await;
+        assert_eq!("Unauthorized: access token is required", res.errors[0].message);
+        let res = schema.execute(mutation(create)).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        assert_eq!(serde_json::json!([{"id": 2}]), data["createBlog"]["tags"]);
+    }
+}
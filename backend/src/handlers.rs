@@ -7,8 +7,24 @@ use axum::{
 use serde::de::DeserializeOwned;
 use validator::Validate;
 
+pub mod activitypub;
+pub mod atompub;
+pub mod auth;
 pub mod blog;
+pub mod cache;
+pub mod docs;
+pub mod export;
+pub mod feed;
+pub mod graphql;
+pub mod idempotency;
+pub mod micropub;
+pub mod negotiation;
+pub mod oembed;
+pub mod redirect;
+pub mod sitemap;
 pub mod tag;
+pub mod webmention;
+pub mod xmlrpc;
 
 
 
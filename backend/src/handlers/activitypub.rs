@@ -0,0 +1,147 @@
+use axum::{
+    body::Bytes,
+    extract::{Extension, Query},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::activitypub::{
+    self,
+    delivery::{fetch_actor, Federation},
+    signature::{digest, Signature},
+    InboxActivity,
+};
+use crate::config::AppConfig;
+use crate::repositories::{
+    blog::BlogRepository,
+    follower::{Follower, FollowerRepository},
+};
+use crate::outbound::OutboundClient;
+
+#[derive(Debug, Deserialize)]
+pub struct WebfingerQuery {
+    resource: String,
+}
+
+fn json_response(content_type: &'static str, value: Value) -> impl IntoResponse {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    (StatusCode::OK, headers, value.to_string())
+}
+
+// 鍵が無ければ連合していないので、どの文書も 404 にする
+fn enabled(federation: &Federation) -> Result<(), StatusCode> {
+    federation.key().map(|_| ()).ok_or(StatusCode::NOT_FOUND)
+}
+
+pub async fn webfinger(
+    Query(query): Query<WebfingerQuery>,
+    Extension(federation): Extension<Federation>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    enabled(&federation)?;
+    let document = activitypub::webfinger(&query.resource, &config.site, &config.activitypub)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(json_response(activitypub::JRD_CONTENT_TYPE, document))
+}
+
+pub async fn actor(
+    Extension(federation): Extension<Federation>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let key = federation.key().ok_or(StatusCode::NOT_FOUND)?;
+    let document = activitypub::actor(&config.site, &config.activitypub, &key.public_key_pem);
+    Ok(json_response(activitypub::CONTENT_TYPE, document))
+}
+
+pub async fn outbox<B: BlogRepository>(
+    Extension(repository): Extension<Arc<B>>,
+    Extension(federation): Extension<Federation>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    enabled(&federation)?;
+    let mut blogs = repository.all().await.or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    blogs.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+    Ok(json_response(activitypub::CONTENT_TYPE, activitypub::outbox(&blogs, &config.site)))
+}
+
+pub async fn followers<F: FollowerRepository>(
+    Extension(repository): Extension<Arc<F>>,
+    Extension(federation): Extension<Federation>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    enabled(&federation)?;
+    let followers = repository.all().await.or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(json_response(
+        activitypub::CONTENT_TYPE,
+        activitypub::followers(followers.len(), &config.site),
+    ))
+}
+
+// 署名を送り主のアクターの公開鍵で確かめてから、Follow ならフォロワーに加えて Accept を返送する
+pub async fn inbox<F: FollowerRepository>(
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<F>>,
+    Extension(federation): Extension<Federation>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    enabled(&federation).map_err(|status| (status, String::new()))?;
+    let unauthorized = |message: String| (StatusCode::UNAUTHORIZED, message);
+    let signature = headers
+        .get("signature")
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| unauthorized("signature is required".to_string()))
+        .and_then(|value| Signature::parse(value).map_err(unauthorized))?;
+    // 本文のすり替えを防ぐため、Digest も署名されていて本文と一致すること
+    let digest_matches = headers
+        .get("digest")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value == digest(&body))
+        .unwrap_or(false);
+    if !signature.covers("digest") || !digest_matches {
+        return Err(unauthorized("digest must be signed and match the body".to_string()));
+    }
+    let remote = fetch_actor(&OutboundClient::new(&config.outbound), &signature.key_id)
+        .await
+        .map_err(|e| unauthorized(e.to_string()))?;
+    signature
+        .verify("POST", activitypub::INBOX_PATH, &headers, &remote.public_key_pem)
+        .map_err(unauthorized)?;
+
+    let activity: Value = serde_json::from_slice(&body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let parsed = activitypub::parse_inbox(&activity, &config.site)
+        .map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+    // 署名した鍵の持ち主と違うアクターの名前で送られたものは受け付けない
+    if parsed.actor() != remote.id {
+        return Err(unauthorized("actor does not own the signing key".to_string()));
+    }
+    match parsed {
+        InboxActivity::Follow { .. } => {
+            remote
+                .check_inboxes(config.outbound.allow_local)
+                .map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+            repository
+                .save(Follower {
+                    actor: remote.id.clone(),
+                    inbox: remote.inbox.clone(),
+                    shared_inbox: remote.shared_inbox.clone(),
+                })
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            federation.deliver(&remote.inbox, activitypub::accept(&activity, &config.site));
+        }
+        InboxActivity::Undo { .. } => {
+            repository
+                .delete(&remote.id)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        }
+        InboxActivity::Other { .. } => {}
+    }
+    Ok(StatusCode::ACCEPTED)
+}
@@ -0,0 +1,203 @@
+use axum::{
+    extract::{Extension, Path},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::atompub::{self, AtomEntry};
+use crate::config::{AppConfig, SiteConfig};
+use crate::feed::atom;
+use crate::import::percent_decode;
+use crate::render;
+use crate::repositories::{
+    blog::{BlogEntity, BlogRepository, UpdateBlog},
+    tag::TagRepository,
+};
+use crate::webmention::WebmentionQueue;
+
+use super::auth::AuthUser;
+use super::cache::{conditional, is_precondition_met, strong_etag};
+
+// 編集用のエントリはキャッシュさせず、毎回 ETag で確かめてもらう
+const NO_CACHE: &str = "no-cache";
+
+pub async fn service_document(
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> impl IntoResponse {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(atompub::SERVICE_CONTENT_TYPE));
+    (StatusCode::OK, headers, atompub::service(&config.site))
+}
+
+pub async fn categories_document<T: TagRepository>(
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let tags = repository.all().await.or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(atompub::CATEGORIES_CONTENT_TYPE));
+    Ok((StatusCode::OK, headers, atompub::categories(&tags)))
+}
+
+// 下書きも含めて更新日時の新しい順に返すので、読むだけでもログインが要る
+pub async fn list_entries<B: BlogRepository>(
+    _user: AuthUser,
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<B>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut blogs = repository.all().await.or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    blogs.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(b.id.cmp(&a.id)));
+    // 削除しても進むよう、記事の一覧が最後に変わった時刻を使う
+    let last_modified = repository.last_modified().await.or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    conditional(
+        &headers,
+        atompub::collection(&blogs, &config.site, last_modified).into_bytes(),
+        atom::CONTENT_TYPE,
+        last_modified,
+        NO_CACHE,
+    )
+}
+
+pub async fn find_entry<B: BlogRepository>(
+    _user: AuthUser,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<B>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let blog = repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
+    conditional(
+        &headers,
+        atompub::entry_document(&blog, &config.site).into_bytes(),
+        atompub::ENTRY_CONTENT_TYPE,
+        Some(blog.updated_at),
+        NO_CACHE,
+    )
+}
+
+pub async fn create_entry<B: BlogRepository, T: TagRepository>(
+    _user: AuthUser,
+    headers: HeaderMap,
+    Extension(blog_repository): Extension<Arc<B>>,
+    Extension(tag_repository): Extension<Arc<T>>,
+    Extension(webmentions): Extension<WebmentionQueue>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    body: String,
+) -> Result<Response, (StatusCode, String)> {
+    let entry = parse(&headers, &body)?;
+    let categories = entry.categories.clone();
+    // Slug ヘッダ (RFC 5023 9.7) はパーセントエンコードされている
+    let slug = headers
+        .get("slug")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| render::slugify(&percent_decode(value)));
+    // タグを作る前に検証して、失敗した投稿のためにタグだけが増えないようにする
+    let mut payload = entry.into_create_blog(vec![], slug, Default::default());
+    payload.validate().map_err(validation_error)?;
+    payload.tags = tag_repository.ids_for_names(&categories).await.map_err(internal_error)?;
+    let blog = blog_repository.create(payload).await.map_err(internal_error)?;
+    webmentions.send(blog.id);
+
+    let mut response = entry_response(StatusCode::CREATED, &blog, &config.site)?;
+    let location = HeaderValue::from_str(&config.site.url(&atompub::member_path(blog.id)))
+        .map_err(internal_error)?;
+    response.headers_mut().insert(header::LOCATION, location.clone());
+    response.headers_mut().insert(header::CONTENT_LOCATION, location);
+    Ok(response)
+}
+
+// If-Match が現在のエントリの ETag と違えば、他のクライアントの更新を上書きしないよう 412 にする
+pub async fn replace_entry<B: BlogRepository, T: TagRepository>(
+    _user: AuthUser,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Extension(blog_repository): Extension<Arc<B>>,
+    Extension(tag_repository): Extension<Arc<T>>,
+    Extension(webmentions): Extension<WebmentionQueue>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    body: String,
+) -> Result<Response, (StatusCode, String)> {
+    let current = find_current(&*blog_repository, id, &headers, &config.site).await?;
+    let entry = parse(&headers, &body)?;
+    let categories = entry.categories.clone();
+    // スラッグと summary 以外の SEO 設定は Atom で表せないので引き継ぐ
+    let mut payload = entry.into_create_blog(vec![], current.slug.clone(), current.seo.clone());
+    payload.validate().map_err(validation_error)?;
+    payload.tags = tag_repository.ids_for_names(&categories).await.map_err(internal_error)?;
+    let blog = blog_repository
+        .update(id, UpdateBlog::from(payload))
+        .await
+        .map_err(internal_error)?;
+    webmentions.send(blog.id);
+    entry_response(StatusCode::OK, &blog, &config.site)
+}
+
+pub async fn delete_entry<B: BlogRepository>(
+    _user: AuthUser,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<B>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    find_current(&*repository, id, &headers, &config.site).await?;
+    repository
+        .delete(id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .or(Err((StatusCode::NOT_FOUND, String::new())))
+}
+
+async fn find_current<B: BlogRepository>(
+    repository: &B,
+    id: i32,
+    headers: &HeaderMap,
+    site: &SiteConfig,
+) -> Result<BlogEntity, (StatusCode, String)> {
+    let blog = repository
+        .find(id)
+        .await
+        .or(Err((StatusCode::NOT_FOUND, String::new())))?;
+    let etag = strong_etag(atompub::entry_document(&blog, site).as_bytes());
+    if !is_precondition_met(headers, &etag) {
+        return Err((StatusCode::PRECONDITION_FAILED, "entry has been modified".to_string()));
+    }
+    Ok(blog)
+}
+
+fn parse(headers: &HeaderMap, body: &str) -> Result<AtomEntry, (StatusCode, String)> {
+    let is_atom = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .map(|mime| mime.essence_str() == "application/atom+xml")
+        .unwrap_or(false);
+    if !is_atom {
+        return Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, String::new()));
+    }
+    atompub::parse_entry(body).map_err(|message| (StatusCode::BAD_REQUEST, message))
+}
+
+fn entry_response(
+    status: StatusCode,
+    blog: &BlogEntity,
+    site: &SiteConfig,
+) -> Result<Response, (StatusCode, String)> {
+    let body = atompub::entry_document(blog, site);
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(atompub::ENTRY_CONTENT_TYPE));
+    headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&strong_etag(body.as_bytes())).map_err(internal_error)?,
+    );
+    Ok((status, headers, body).into_response())
+}
+
+fn validation_error(errors: validator::ValidationErrors) -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, format!("Validation error: [{}]", errors).replace('\n', ", "))
+}
+
+fn internal_error<E: std::fmt::Display>(error: E) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+}
@@ -0,0 +1,136 @@
+use axum::{
+    async_trait,
+    extract::{Extension, FromRequest, RequestParts},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::auth::{self, TokenResponse};
+use crate::config::AppConfig;
+use crate::repositories::user::UserRepository;
+
+type AuthRejection = (StatusCode, HeaderMap, String);
+
+fn unauthorized(message: &str) -> AuthRejection {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    (StatusCode::UNAUTHORIZED, headers, message.to_string())
+}
+
+// 書き込みのハンドラの引数にすると、有効なアクセストークンが無いリクエストを 401 にする。
+// ボディを読む前に弾くよう、ValidatedJson より前に置くこと
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthUser {
+    pub id: i32,
+    pub username: String,
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for AuthUser
+where
+    B: Send,
+{
+    type Rejection = AuthRejection;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| unauthorized("access token is required"))?
+            .to_string();
+        let Extension(config) = Extension::<Arc<AppConfig>>::from_request(req)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, HeaderMap::new(), e.to_string()))?;
+        let claims = auth::decode_access_token(token.trim(), &config.auth)
+            .map_err(|_| unauthorized("access token is invalid or expired"))?;
+        let id = claims
+            .sub
+            .parse()
+            .map_err(|_| unauthorized("access token is invalid or expired"))?;
+        let user = AuthUser { id, username: claims.name };
+        tracing::debug!("authenticated as {} (id {})", user.username, user.id);
+        Ok(user)
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct LoginForm {
+    username: String,
+    password: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RefreshTokenForm {
+    refresh_token: String,
+}
+
+fn token_response(tokens: TokenResponse) -> impl IntoResponse {
+    // トークンを含むのでキャッシュさせない (RFC 6749 5.1)
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    (StatusCode::OK, headers, Json(tokens))
+}
+
+fn internal_error<E: std::fmt::Display>(error: E) -> AuthRejection {
+    (StatusCode::INTERNAL_SERVER_ERROR, HeaderMap::new(), error.to_string())
+}
+
+// ユーザー名とパスワードのどちらが違っても同じ 401 にする
+pub async fn login<U: UserRepository>(
+    Json(form): Json<LoginForm>,
+    Extension(repository): Extension<Arc<U>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, AuthRejection> {
+    let user = repository
+        .find_by_username(&form.username)
+        .await
+        .map_err(internal_error)?
+        .filter(|user| auth::verify_password(&form.password, &user.password_hash))
+        .ok_or_else(|| unauthorized("username or password is incorrect"))?;
+    let tokens = auth::issue_tokens(&*repository, &user, &config.auth)
+        .await
+        .map_err(internal_error)?;
+    Ok(token_response(tokens))
+}
+
+// 使ったリフレッシュトークンは失効させ、新しい組を返す
+pub async fn refresh<U: UserRepository>(
+    Json(form): Json<RefreshTokenForm>,
+    Extension(repository): Extension<Arc<U>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, AuthRejection> {
+    let invalid = || unauthorized("refresh token is invalid, expired or revoked");
+    let user_id = repository
+        .revoke_refresh_token(&auth::token_hash(&form.refresh_token))
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(invalid)?;
+    let user = repository
+        .find(user_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(invalid)?;
+    let tokens = auth::issue_tokens(&*repository, &user, &config.auth)
+        .await
+        .map_err(internal_error)?;
+    Ok(token_response(tokens))
+}
+
+// ログアウト。知らないトークンでも成功として扱う (RFC 7009 2.2)。
+// アクセストークンは失効させられないので、有効期間を短くしておく
+pub async fn revoke<U: UserRepository>(
+    Json(form): Json<RefreshTokenForm>,
+    Extension(repository): Extension<Arc<U>>,
+) -> Result<StatusCode, AuthRejection> {
+    repository
+        .revoke_refresh_token(&auth::token_hash(&form.refresh_token))
+        .await
+        .map_err(internal_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,55 +1,250 @@
 use axum::{
-    extract::{Extension, Path},
-    http::StatusCode,
+    async_trait,
+    extract::{Extension, FromRequest, Path, Query, RequestParts},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::IntoResponse,
     Json,
 };
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::sync::Arc;
+use validator::Validate;
 
-use crate::repositories::blog::{CreateBlog, BlogRepository, UpdateBlog};
+use crate::api::LegacyApi;
+use crate::config::AppConfig;
+use crate::render;
+use crate::seo::BlogMeta;
+use crate::webmention::WebmentionQueue;
+use crate::repositories::{
+    blog::{
+        BlogEntity, BlogField, BlogFields, BlogStatus, BulkMode, BulkOperation, CreateBlog,
+        BlogRepository, UpdateBlog,
+    },
+    idempotency::IdempotencyRepository,
+};
 
-use super::ValidatedJson;
+use super::{
+    auth::AuthUser,
+    cache::{conditional, conditional_json},
+    idempotency::{fingerprint, idempotent, IdempotencyKey},
+    negotiation::{negotiate, Representation},
+    ValidatedJson,
+};
 
-pub async fn create_blog<T: BlogRepository>(
+pub async fn create_blog<T: BlogRepository, I: IdempotencyRepository>(
+    user: AuthUser,
+    idempotency_key: IdempotencyKey,
     ValidatedJson(payload): ValidatedJson<CreateBlog>,
-    Extension(repository): Extension<Arc<T>>
+    Extension(repository): Extension<Arc<T>>,
+    Extension(idempotency): Extension<Arc<I>>,
+    Extension(webmentions): Extension<WebmentionQueue>,
+    Extension(config): Extension<Arc<AppConfig>>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let blog = repository
-        .create(payload)
-        .await
-        .or(Err(StatusCode::NOT_FOUND))?;
+    // キーはアカウントごとに分ける。別のユーザーが同じキーを使っても、その応答は返さない
+    let scope = format!("POST /blogs user:{}", user.id);
+    let fingerprint = fingerprint(&scope, &payload)?;
+    idempotent(&*idempotency, &config.idempotency, idempotency_key, &scope, fingerprint, || async move {
+        let blog = repository
+            .create(payload)
+            .await
+            .or(Err(StatusCode::NOT_FOUND))?;
+        webmentions.send(blog.id);
+
+        Ok((StatusCode::CREATED, blog))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct FieldsQuery {
+    fields: Option<String>,
+    include: Option<String>,
+}
+
+impl FieldsQuery {
+    // fields があればそのフィールドだけ、無ければ default に include を足す
+    fn resolve(&self, default: BlogFields) -> Result<BlogFields, StatusCode> {
+        let mut fields = match &self.fields {
+            Some(names) => BlogFields::new(parse_field_names(names)?),
+            None => default,
+        };
+        if let Some(names) = &self.include {
+            for field in parse_field_names(names)? {
+                fields.include(field);
+            }
+        }
+        Ok(fields)
+    }
+}
 
-    Ok((StatusCode::CREATED, Json(blog)))
+fn parse_field_names(names: &str) -> Result<Vec<BlogField>, StatusCode> {
+    names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| BlogField::parse(name).ok_or(StatusCode::BAD_REQUEST))
+        .collect()
 }
 
+fn sparse(blog: &BlogEntity, fields: &BlogFields) -> Result<Value, StatusCode> {
+    let value = serde_json::to_value(blog).or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let mut object = Map::new();
+    for name in fields.names() {
+        if let Some(field) = value.get(name) {
+            object.insert(name.to_string(), field.clone());
+        }
+    }
+    Ok(Value::Object(object))
+}
+
+// Accept か拡張子 (.md / .html) で JSON, Markdown, HTML を返し分ける
 pub async fn find_blog<T: BlogRepository>(
-    Path(id): Path<i32>,
+    Path(segment): Path<String>,
+    Query(query): Query<FieldsQuery>,
+    headers: HeaderMap,
     Extension(repository): Extension<Arc<T>>,
+    Extension(config): Extension<Arc<AppConfig>>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let blog = repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
-    Ok((StatusCode::OK, Json(blog)))
+    let (id, representation) = negotiate(&segment, &headers)?;
+    // fields は JSON のときだけ有効
+    let fields = match representation {
+        Representation::Json => query.resolve(BlogFields::all())?,
+        _ => BlogFields::all(),
+    };
+    let blog = repository.find_with(id, &fields).await.or(Err(StatusCode::NOT_FOUND))?;
+    let cache_control = &config.cache.blog;
+    let mut response = match representation {
+        Representation::Json => {
+            conditional_json(&headers, &sparse(&blog, &fields)?, Some(blog.updated_at), cache_control)
+        }
+        Representation::Markdown => conditional(
+            &headers,
+            render::markdown(&blog).into_bytes(),
+            representation.content_type(),
+            Some(blog.updated_at),
+            cache_control,
+        ),
+        Representation::Html => conditional(
+            &headers,
+            render::html_page(&blog, &config.site).into_bytes(),
+            representation.content_type(),
+            Some(blog.updated_at),
+            cache_control,
+        ),
+    }?;
+    response.headers_mut().insert(header::VARY, HeaderValue::from_static("accept"));
+    Ok(response)
 }
 
 pub async fn all_blog<T: BlogRepository>(
+    Query(query): Query<FieldsQuery>,
+    headers: HeaderMap,
+    legacy: Option<Extension<LegacyApi>>,
     Extension(repository): Extension<Arc<T>>,
+    Extension(config): Extension<Arc<AppConfig>>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let blog = repository.all().await.unwrap();
-    Ok((StatusCode::OK, Json(blog)))
+    // 一覧では body を返さない。旧パスは互換のため今まで通り全て返す
+    let default = match legacy {
+        Some(_) => BlogFields::all(),
+        None => BlogFields::without(BlogField::Body),
+    };
+    let fields = query.resolve(default)?;
+    let blog = repository.all_with(&fields).await.unwrap();
+    // 記事の updated_at の最大値では、削除しても変わらないので使わない
+    let last_modified = repository
+        .last_modified()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let blog = blog
+        .iter()
+        .map(|blog| sparse(blog, &fields))
+        .collect::<Result<Vec<Value>, StatusCode>>()?;
+    conditional_json(&headers, &blog, last_modified, &config.cache.blogs)
+}
+
+// OGP, Twitter カード, JSON-LD をまとめて返す
+pub async fn find_blog_meta<T: BlogRepository>(
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let blog = repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
+    let meta = BlogMeta::new(&blog, &config.site);
+    conditional_json(&headers, &meta, Some(blog.updated_at), &config.cache.blog)
+}
+
+const MERGE_PATCH_JSON: &str = "application/merge-patch+json";
+
+// PATCH は application/merge-patch+json と、互換のため application/json を受け付ける。
+// ボディを読む前に 415 にするよう、ValidatedJson より前に置くこと
+#[derive(Debug)]
+pub struct MergePatch;
+
+#[async_trait]
+impl<B> FromRequest<B> for MergePatch
+where
+    B: Send,
+{
+    type Rejection = (StatusCode, HeaderMap, String);
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let supported = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<mime::Mime>().ok())
+            .map(|mime| mime.essence_str() == MERGE_PATCH_JSON || mime.essence_str() == mime::APPLICATION_JSON.essence_str())
+            .unwrap_or(false);
+        if !supported {
+            let mut headers = HeaderMap::new();
+            headers.insert("accept-patch", HeaderValue::from_static(MERGE_PATCH_JSON));
+            return Err((
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                headers,
+                format!("Content-Type must be {}", MERGE_PATCH_JSON),
+            ));
+        }
+        Ok(MergePatch)
+    }
 }
 
 pub async fn update_blog<T: BlogRepository>(
+    _user: AuthUser,
+    _media_type: MergePatch,
     Path(id): Path<i32>,
     ValidatedJson(payload): ValidatedJson<UpdateBlog>,
     Extension(repository): Extension<Arc<T>>,
+    Extension(webmentions): Extension<WebmentionQueue>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let blog = repository
         .update(id, payload)
         .await
         .or(Err(StatusCode::NOT_FOUND))?;
-    Ok((StatusCode::CREATED, Json(blog)))
+    webmentions.send(blog.id);
+    let mut headers = HeaderMap::new();
+    headers.insert("accept-patch", HeaderValue::from_static(MERGE_PATCH_JSON));
+    Ok((StatusCode::OK, headers, Json(blog)))
+}
+
+pub async fn replace_blog<T: BlogRepository>(
+    _user: AuthUser,
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<CreateBlog>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(webmentions): Extension<WebmentionQueue>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let blog = repository
+        .update(id, UpdateBlog::from(payload))
+        .await
+        .or(Err(StatusCode::NOT_FOUND))?;
+    webmentions.send(blog.id);
+    Ok((StatusCode::OK, Json(blog)))
 }
 
 pub async fn delete_blog<T: BlogRepository>(
+    _user: AuthUser,
     Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>,
 ) -> StatusCode {
@@ -59,3 +254,37 @@ pub async fn delete_blog<T: BlogRepository>(
         .map(|_| StatusCode::NO_CONTENT)
         .unwrap_or(StatusCode::NOT_FOUND)
 }
+
+pub async fn bulk_blog<T: BlogRepository>(
+    _user: AuthUser,
+    ValidatedJson(payload): ValidatedJson<BulkBlog>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(webmentions): Extension<WebmentionQueue>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let operations = payload.operations.clone();
+    let outcome = repository
+        .bulk(payload.operations, payload.mode)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    // 一括で公開された記事からも Webmention を送る。失敗した操作の記事からは送らない
+    for operation in outcome.succeeded(&operations) {
+        if let BulkOperation::SetStatus { id, status: BlogStatus::Published } = operation {
+            webmentions.send(*id);
+        }
+    }
+    let status = if outcome.committed {
+        StatusCode::OK
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+    Ok((status, Json(outcome)))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, JsonSchema)]
+pub struct BulkBlog {
+    #[serde(default)]
+    mode: BulkMode,
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 100, message = "Over operations length"))]
+    operations: Vec<BulkOperation>,
+}
@@ -0,0 +1,106 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+// レスポンスボディのハッシュから強い ETag を作る
+pub fn strong_etag(body: &[u8]) -> String {
+    format!("\"{:x}\"", Sha256::digest(body))
+}
+
+pub fn http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+// If-None-Match が優先され、無い場合のみ If-Modified-Since を見る (RFC 7232)
+pub fn is_not_modified(
+    headers: &HeaderMap,
+    etag: &str,
+    last_modified: Option<DateTime<Utc>>,
+) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        let if_none_match = match if_none_match.to_str() {
+            Ok(value) => value,
+            Err(_) => return false,
+        };
+        return if_none_match.split(',').map(str::trim).any(|candidate| {
+            candidate == "*" || candidate.trim_start_matches("W/") == etag
+        });
+    }
+
+    let since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_http_date);
+    match (since, last_modified) {
+        (Some(since), Some(last_modified)) => last_modified.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
+
+// If-Match が無いか、現在の ETag と一致すれば更新してよい。比較は強い比較 (RFC 7232)
+pub fn is_precondition_met(headers: &HeaderMap, etag: &str) -> bool {
+    match headers.get(header::IF_MATCH).map(|value| value.to_str()) {
+        None => true,
+        Some(Ok(if_match)) => if_match
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || candidate == etag),
+        Some(Err(_)) => false,
+    }
+}
+
+pub fn conditional_json<T: Serialize>(
+    request_headers: &HeaderMap,
+    value: &T,
+    last_modified: Option<DateTime<Utc>>,
+    cache_control: &str,
+) -> Result<Response, StatusCode> {
+    let body = serde_json::to_vec(value).or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    conditional(request_headers, body, mime::APPLICATION_JSON.as_ref(), last_modified, cache_control)
+}
+
+pub fn conditional(
+    request_headers: &HeaderMap,
+    body: Vec<u8>,
+    content_type: &str,
+    last_modified: Option<DateTime<Utc>>,
+    cache_control: &str,
+) -> Result<Response, StatusCode> {
+    let etag = strong_etag(&body);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).or(Err(StatusCode::INTERNAL_SERVER_ERROR))?,
+    );
+    if let Some(last_modified) = last_modified {
+        headers.insert(
+            header::LAST_MODIFIED,
+            HeaderValue::from_str(&http_date(last_modified))
+                .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?,
+        );
+    }
+    if let Ok(cache_control) = HeaderValue::from_str(cache_control) {
+        headers.insert(header::CACHE_CONTROL, cache_control);
+    }
+
+    if is_not_modified(request_headers, &etag, last_modified) {
+        return Ok((StatusCode::NOT_MODIFIED, headers, ()).into_response());
+    }
+
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(content_type).or(Err(StatusCode::INTERNAL_SERVER_ERROR))?,
+    );
+    Ok((StatusCode::OK, headers, body).into_response())
+}
@@ -0,0 +1,238 @@
+use axum::{
+    extract::{Extension, Path},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse},
+    Json,
+};
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::openapi;
+use crate::render::escape_html;
+
+pub const DOCS_ASSETS_PATH: &str = "/docs/assets/:file";
+pub const SWAGGER_INITIALIZER_PATH: &str = "/docs/swagger-initializer.js";
+
+// 外部のスクリプトを読まないので、オフラインでも厳しい CSP の下でも表示できる
+const CONTENT_SECURITY_POLICY: &str = "default-src 'none'; style-src 'unsafe-inline'";
+
+// Swagger UI もこのサーバーから配るファイルだけを読む。CDN には繋がない
+const SWAGGER_UI_CONTENT_SECURITY_POLICY: &str = "default-src 'none'; script-src 'self'; \
+    style-src 'self' 'unsafe-inline'; img-src 'self' data:; connect-src 'self'";
+
+// 配るのはこの一覧にあるファイルだけにして、assets_dir の外を読ませない
+const SWAGGER_UI_ASSETS: [(&str, &str); 2] = [
+    ("swagger-ui-bundle.js", "application/javascript; charset=utf-8"),
+    ("swagger-ui.css", "text/css; charset=utf-8"),
+];
+
+// インラインのスクリプトは CSP で止まるので、初期化も別のファイルにする
+const SWAGGER_INITIALIZER: &str = r##"window.onload = function () {
+  window.ui = SwaggerUIBundle({
+    url: "/openapi.json",
+    dom_id: "#swagger-ui",
+    deepLinking: true,
+  });
+};
+"##;
+
+const SWAGGER_UI_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="ja">
+  <head>
+    <meta charset="utf-8" />
+    <title>API Reference</title>
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+    <link rel="stylesheet" href="/docs/assets/swagger-ui.css" />
+  </head>
+  <body>
+    <div id="swagger-ui"></div>
+    <script src="/docs/assets/swagger-ui-bundle.js"></script>
+    <script src="/docs/swagger-initializer.js"></script>
+  </body>
+</html>
+"#;
+
+const STYLE: &str = r#"
+      body { font-family: sans-serif; max-width: 960px; margin: 0 auto; padding: 1rem; color: #222; }
+      section { border-top: 1px solid #ddd; padding: 0.5rem 0; }
+      code, pre { background: #f6f8fa; }
+      pre { padding: 0.5rem; overflow-x: auto; }
+      table { border-collapse: collapse; }
+      th, td { border: 1px solid #ddd; padding: 0.25rem 0.5rem; text-align: left; }
+      .method { font-weight: bold; text-transform: uppercase; }
+"#;
+
+pub async fn openapi_json() -> Json<Value> {
+    Json(openapi::document())
+}
+
+// Swagger UI のファイルが置かれていればそれを使い、無ければ OpenAPI ドキュメントから作った HTML を返す
+pub async fn api_docs(Extension(config): Extension<Arc<AppConfig>>) -> impl IntoResponse {
+    let bundle = config.docs.assets_dir.join(SWAGGER_UI_ASSETS[0].0);
+    let (policy, page) = if tokio::fs::metadata(bundle).await.is_ok() {
+        (SWAGGER_UI_CONTENT_SECURITY_POLICY, SWAGGER_UI_PAGE.to_string())
+    } else {
+        (CONTENT_SECURITY_POLICY, render(&openapi::document()))
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_SECURITY_POLICY, HeaderValue::from_static(policy));
+    (StatusCode::OK, headers, Html(page))
+}
+
+pub async fn swagger_initializer() -> impl IntoResponse {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/javascript; charset=utf-8"),
+    );
+    (StatusCode::OK, headers, SWAGGER_INITIALIZER)
+}
+
+pub async fn docs_asset(
+    Path(file): Path<String>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let (name, content_type) = SWAGGER_UI_ASSETS
+        .iter()
+        .find(|(name, _)| *name == file)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let body = tokio::fs::read(config.docs.assets_dir.join(name))
+        .await
+        .or(Err(StatusCode::NOT_FOUND))?;
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("public, max-age=86400"));
+    Ok((StatusCode::OK, headers, body))
+}
+
+// OpenAPI ドキュメントをそのまま HTML にしたリファレンス
+pub fn render(document: &Value) -> String {
+    let mut operations = String::new();
+    for (path, methods) in document["paths"].as_object().into_iter().flatten() {
+        for (method, operation) in methods.as_object().into_iter().flatten() {
+            operations.push_str(&render_operation(document, path, method, operation));
+        }
+    }
+    let mut schemas = String::new();
+    for (name, schema) in document["components"]["schemas"].as_object().into_iter().flatten() {
+        schemas.push_str(&format!(
+            "<section id=\"schema-{name}\"><h3>{name}</h3><pre>{schema}</pre></section>\n",
+            name = escape_html(name),
+            schema = escape_html(&serde_json::to_string_pretty(schema).unwrap_or_default()),
+        ));
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="ja">
+  <head>
+    <meta charset="utf-8" />
+    <title>{title}</title>
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+    <style>{style}</style>
+  </head>
+  <body>
+    <h1>{title} <small>{version}</small></h1>
+    <p>機械で読む場合は <a href="/openapi.json">/openapi.json</a> を使う</p>
+    <h2>Endpoints</h2>
+{operations}
+    <h2>Schemas</h2>
+{schemas}
+  </body>
+</html>
+"#,
+        title = escape_html(document["info"]["title"].as_str().unwrap_or_default()),
+        version = escape_html(document["info"]["version"].as_str().unwrap_or_default()),
+        style = STYLE,
+        operations = operations,
+        schemas = schemas,
+    )
+}
+
+fn render_operation(document: &Value, path: &str, method: &str, operation: &Value) -> String {
+    let text = |value: &Value| escape_html(value.as_str().unwrap_or_default());
+
+    let mut html = format!(
+        "<section id=\"{id}\"><h3><span class=\"method\">{method}</span> <code>{path}</code></h3>\n<p>{summary}</p>\n",
+        id = text(&operation["operationId"]),
+        method = escape_html(method),
+        path = escape_html(path),
+        summary = text(&operation["summary"]),
+    );
+    if operation["description"].is_string() {
+        html.push_str(&format!("<p>{}</p>\n", text(&operation["description"])));
+    }
+    if operation["security"].is_array() {
+        html.push_str("<p>Authorization: Bearer のアクセストークンが必要</p>\n");
+    }
+
+    if let Some(parameters) = operation["parameters"].as_array().filter(|p| !p.is_empty()) {
+        html.push_str("<table><tr><th>Parameter</th><th>In</th><th>Required</th><th>Description</th></tr>\n");
+        for parameter in parameters {
+            html.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                text(&parameter["name"]),
+                text(&parameter["in"]),
+                parameter["required"].as_bool().unwrap_or(false),
+                text(&parameter["description"]),
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    if let Some(content) = operation["requestBody"]["content"].as_object() {
+        html.push_str("<p>Request body:</p><ul>\n");
+        for (media_type, body) in content {
+            html.push_str(&format!(
+                "<li><code>{}</code> {}</li>\n",
+                escape_html(media_type),
+                schema_link(&body["schema"]),
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    html.push_str("<p>Responses:</p><ul>\n");
+    for (status, response) in operation["responses"].as_object().into_iter().flatten() {
+        let response = resolve_response(document, response);
+        let schemas: Vec<String> = response["content"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(media_type, body)| format!("<code>{}</code> {}", escape_html(media_type), schema_link(&body["schema"])))
+            .collect();
+        html.push_str(&format!(
+            "<li><strong>{}</strong> {} {}</li>\n",
+            escape_html(status),
+            text(&response["description"]),
+            schemas.join(", "),
+        ));
+    }
+    html.push_str("</ul></section>\n");
+    html
+}
+
+// #/components/responses/ への参照を解決する
+fn resolve_response<'a>(document: &'a Value, response: &'a Value) -> &'a Value {
+    match response["$ref"].as_str().and_then(|reference| reference.rsplit('/').next()) {
+        Some(name) => &document["components"]["responses"][name],
+        None => response,
+    }
+}
+
+// スキーマ名へのリンク。配列なら要素のスキーマへリンクする
+fn schema_link(schema: &Value) -> String {
+    let (reference, array) = match schema["$ref"].as_str() {
+        Some(reference) => (Some(reference), false),
+        None => (schema["items"]["$ref"].as_str(), schema["type"] == "array"),
+    };
+    match reference.and_then(|reference| reference.rsplit('/').next()) {
+        Some(name) => format!(
+            "<a href=\"#schema-{name}\">{name}{array}</a>",
+            name = escape_html(name),
+            array = if array { "[]" } else { "" },
+        ),
+        None => escape_html(schema["type"].as_str().unwrap_or_default()),
+    }
+}
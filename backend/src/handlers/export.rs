@@ -0,0 +1,52 @@
+use axum::{
+    body::Body,
+    extract::Extension,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use super::auth::AuthUser;
+use crate::export;
+use crate::repositories::{blog::BlogRepository, tag::TagRepository};
+
+// 作りながら返すので、途中で失敗した場合はレスポンスを中断する。下書きも含むのでログインが要る
+pub async fn export_archive<B: BlogRepository, T: TagRepository>(
+    _user: AuthUser,
+    Extension(blog_repository): Extension<Arc<B>>,
+    Extension(tag_repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let (mut body_sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let (sender, receiver) = mpsc::channel::<Vec<u8>>(8);
+        // receiver はここに移して抜けるときに捨てる。クライアントが切断したら
+        // write_archive の send が失敗して、読み出しを途中でやめる
+        let forward = async {
+            let mut receiver = receiver;
+            while let Some(chunk) = receiver.recv().await {
+                if body_sender.send_data(chunk.into()).await.is_err() {
+                    break;
+                }
+            }
+        };
+        let (result, _) = tokio::join!(
+            export::write_archive(&*blog_repository, &*tag_repository, sender),
+            forward
+        );
+        if let Err(e) = result {
+            tracing::error!("export failed: {}", e);
+            body_sender.abort();
+        }
+    });
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(export::CONTENT_TYPE));
+    let disposition = format!("attachment; filename=\"{}\"", export::file_name(Utc::now()));
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&disposition).or(Err(StatusCode::INTERNAL_SERVER_ERROR))?,
+    );
+    Ok((headers, body))
+}
@@ -0,0 +1,120 @@
+use axum::{
+    extract::{Extension, Path, Query},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::feed::{atom, json, rss};
+use crate::repositories::{blog::BlogRepository, tag::TagRepository};
+
+use super::cache::conditional;
+
+// 返す記事の updated_at の最大値では、記事を削除したり非公開にしたりしても進まないので、
+// 記事の一覧が最後に変わった時刻を使う
+async fn collection_modified<T: BlogRepository>(repository: &T) -> Result<Option<DateTime<Utc>>, StatusCode> {
+    repository.last_modified().await.or(Err(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+pub async fn rss_feed<T: BlogRepository>(
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let blogs = repository
+        .recent_published(config.site.feed_limit, 0, None)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let last_modified = collection_modified(&*repository).await?;
+    conditional(
+        &headers,
+        rss::rss(&blogs, &config.site, last_modified).into_bytes(),
+        rss::CONTENT_TYPE,
+        last_modified,
+        &config.cache.feed,
+    )
+}
+
+pub async fn atom_feed<T: BlogRepository>(
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let blogs = repository
+        .recent_published(config.site.feed_limit, 0, None)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let last_modified = collection_modified(&*repository).await?;
+    conditional(
+        &headers,
+        atom::atom(&blogs, &config.site, None, last_modified).into_bytes(),
+        atom::CONTENT_TYPE,
+        last_modified,
+        &config.cache.feed,
+    )
+}
+
+pub async fn tag_atom_feed<B: BlogRepository, T: TagRepository>(
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Extension(blog_repository): Extension<Arc<B>>,
+    Extension(tag_repository): Extension<Arc<T>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let tag = tag_repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
+    let blogs = blog_repository
+        .recent_published(config.site.feed_limit, 0, Some(tag.id))
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    // タグの名前はフィードのタイトルに入るので、タグの一覧が変わった時刻も見る
+    let tag_modified = tag_repository
+        .last_modified()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let last_modified = collection_modified(&*blog_repository).await?.max(tag_modified);
+    conditional(
+        &headers,
+        atom::atom(&blogs, &config.site, Some(&tag), last_modified).into_bytes(),
+        atom::CONTENT_TYPE,
+        last_modified,
+        &config.cache.feed,
+    )
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct FeedPage {
+    page: Option<u32>,
+}
+
+pub async fn json_feed<T: BlogRepository>(
+    Query(query): Query<FeedPage>,
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let page = query.page.unwrap_or(1);
+    if page == 0 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let limit = config.site.feed_limit;
+    // 次のページがあるかを知るために1件多く読む
+    let mut blogs = repository
+        .recent_published(limit + 1, limit * (page as i64 - 1), None)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let has_next = blogs.len() as i64 > limit;
+    blogs.truncate(limit as usize);
+
+    let body = serde_json::to_vec(&json::json_feed(&blogs, &config.site, page, has_next))
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    conditional(
+        &headers,
+        body,
+        json::CONTENT_TYPE,
+        collection_modified(&*repository).await?,
+        &config.cache.feed,
+    )
+}
@@ -0,0 +1,37 @@
+use async_graphql::http::graphiql_source;
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::{extract::Extension, http::StatusCode, response::Html};
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::graphql::BlogSchema;
+use crate::repositories::{blog::BlogRepository, tag::TagRepository};
+use crate::webmention::WebmentionQueue;
+
+use super::auth::AuthUser;
+
+pub const GRAPHQL_PATH: &str = "/graphql";
+
+pub async fn graphql_handler<B: BlogRepository, T: TagRepository>(
+    Extension(schema): Extension<BlogSchema<B, T>>,
+    Extension(webmentions): Extension<WebmentionQueue>,
+    user: Option<AuthUser>,
+    req: GraphQLRequest,
+) -> GraphQLResponse {
+    // クエリは誰でも実行できる。ミューテーションはログインしたユーザーだけ
+    let mut req = req.into_inner().data(webmentions);
+    if let Some(user) = user {
+        req = req.data(user);
+    }
+    schema.execute(req).await.into()
+}
+
+// 本番では GRAPHIQL=true のときだけ開発用の画面を返す
+pub async fn graphiql(
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Html<String>, StatusCode> {
+    if !config.graphql.graphiql {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Html(graphiql_source(GRAPHQL_PATH, None)))
+}
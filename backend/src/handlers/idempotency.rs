@@ -0,0 +1,131 @@
+use axum::{
+    async_trait,
+    extract::{FromRequest, RequestParts},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+
+use crate::config::IdempotencyConfig;
+use crate::repositories::idempotency::{IdempotencyRepository, Reservation, StoredResponse};
+
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+#[derive(Debug)]
+pub struct IdempotencyKey(pub Option<String>);
+
+#[async_trait]
+impl<B> FromRequest<B> for IdempotencyKey
+where
+    B: Send,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let value = match req.headers().get(IDEMPOTENCY_KEY) {
+            Some(value) => value,
+            None => return Ok(IdempotencyKey(None)),
+        };
+        let key = value
+            .to_str()
+            .ok()
+            .map(str::trim)
+            .filter(|key| !key.is_empty() && key.len() <= 255)
+            .ok_or((
+                StatusCode::BAD_REQUEST,
+                "Idempotency-Key must be 1 to 255 visible characters".to_string(),
+            ))?;
+        Ok(IdempotencyKey(Some(key.to_string())))
+    }
+}
+
+// 同じキーで違う内容が送られてきたことを検出するための指紋
+pub fn fingerprint<T: Serialize>(scope: &str, payload: &T) -> Result<String, StatusCode> {
+    let payload = serde_json::to_vec(payload).or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let mut hasher = Sha256::new();
+    hasher.update(scope.as_bytes());
+    hasher.update(&payload);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn json_response(response: &StoredResponse, replayed: bool) -> Result<Response, StatusCode> {
+    let status = StatusCode::from_u16(response.status_code as u16)
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(mime::APPLICATION_JSON.as_ref()),
+    );
+    if replayed {
+        headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    }
+    Ok((status, headers, response.body.clone()).into_response())
+}
+
+pub async fn idempotent<I, R, F, Fut>(
+    store: &I,
+    config: &IdempotencyConfig,
+    key: IdempotencyKey,
+    scope: &str,
+    fingerprint: String,
+    handler: F,
+) -> Result<Response, StatusCode>
+where
+    I: IdempotencyRepository,
+    R: Serialize,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<(StatusCode, R), StatusCode>>,
+{
+    let key = match key.0 {
+        Some(key) => key,
+        None => {
+            let (status, value) = handler().await?;
+            let body = serde_json::to_string(&value).or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+            let response = StoredResponse { status_code: status.as_u16() as i32, body };
+            return json_response(&response, false);
+        }
+    };
+
+    let reservation = store
+        .reserve(scope, &key, &fingerprint, config.window, config.lock_timeout)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    match reservation {
+        Reservation::Completed { fingerprint: stored, response } => {
+            if stored != fingerprint {
+                return Err(StatusCode::UNPROCESSABLE_ENTITY);
+            }
+            json_response(&response, true)
+        }
+        Reservation::InProgress { fingerprint: stored } => {
+            if stored != fingerprint {
+                return Err(StatusCode::UNPROCESSABLE_ENTITY);
+            }
+            Err(StatusCode::CONFLICT)
+        }
+        Reservation::Reserved => {
+            let result = handler().await.and_then(|(status, value)| {
+                serde_json::to_string(&value)
+                    .map(|body| StoredResponse { status_code: status.as_u16() as i32, body })
+                    .or(Err(StatusCode::INTERNAL_SERVER_ERROR))
+            });
+            match result {
+                Ok(response) => {
+                    store
+                        .complete(scope, &key, response.clone())
+                        .await
+                        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+                    json_response(&response, false)
+                }
+                Err(status) => {
+                    // 失敗したリクエストはリトライできるようにキーを解放する
+                    let _ = store.release(scope, &key).await;
+                    Err(status)
+                }
+            }
+        }
+    }
+}
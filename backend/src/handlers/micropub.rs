@@ -0,0 +1,182 @@
+use axum::{
+    body::Bytes,
+    extract::{Extension, Query},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::auth;
+use crate::config::{AppConfig, AuthConfig};
+use crate::micropub::{self, Action, MicropubError, Post};
+use crate::oembed;
+use crate::repositories::{
+    blog::{BlogEntity, BlogRepository},
+    tag::TagRepository,
+};
+use crate::webmention::WebmentionQueue;
+
+impl IntoResponse for MicropubError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            MicropubError::Unauthorized => StatusCode::UNAUTHORIZED,
+            MicropubError::Forbidden => StatusCode::FORBIDDEN,
+            MicropubError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            MicropubError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(self.to_json())).into_response()
+    }
+}
+
+// Authorization ヘッダか本文の access_token のどちらか一方で送られたトークンを確かめる。
+// トークンは /auth/login で発行したアクセストークンで、他の書き込みの API と同じユーザーで投稿する
+fn authenticate(
+    headers: &HeaderMap,
+    body_token: Option<String>,
+    config: &AuthConfig,
+) -> Result<(), MicropubError> {
+    let header_token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+    let token = match (header_token, body_token) {
+        (Some(_), Some(_)) => {
+            return Err(MicropubError::InvalidRequest(
+                "access token must be sent either in the header or the body".to_string(),
+            ))
+        }
+        (Some(token), None) | (None, Some(token)) => token,
+        (None, None) => return Err(MicropubError::Unauthorized),
+    };
+    match auth::decode_access_token(&token, config) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(MicropubError::Forbidden),
+    }
+}
+
+async fn find_by_url<B: BlogRepository>(
+    repository: &B,
+    url: &str,
+    config: &AppConfig,
+) -> Result<BlogEntity, MicropubError> {
+    let not_found = || MicropubError::InvalidRequest(format!("post not found: {}", url));
+    let id = oembed::resolve(url, &config.site).ok_or_else(not_found)?;
+    repository.find(id).await.map_err(|_| not_found())
+}
+
+fn validation_error(error: validator::ValidationErrors) -> MicropubError {
+    MicropubError::InvalidRequest(error.to_string().replace('\n', ", "))
+}
+
+// q=config / q=source / q=syndicate-to
+pub async fn micropub_query<B: BlogRepository>(
+    Query(params): Query<Vec<(String, String)>>,
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<B>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Response, MicropubError> {
+    authenticate(&headers, None, &config.auth)?;
+    let param = |key: &str| params.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str());
+    match param("q") {
+        Some("config") => Ok(Json(micropub::config()).into_response()),
+        Some("syndicate-to") => Ok(Json(json!({ "syndicate-to": [] })).into_response()),
+        Some("source") => {
+            let url = param("url").ok_or_else(|| MicropubError::InvalidRequest("url is required".to_string()))?;
+            let blog = find_by_url(&*repository, url, &config).await?;
+            let names: Vec<String> = params
+                .iter()
+                .filter(|(name, _)| name == "properties" || name == "properties[]")
+                .map(|(_, value)| value.clone())
+                .collect();
+            Ok(Json(micropub::source(&blog, &names)).into_response())
+        }
+        _ => Err(MicropubError::InvalidRequest("unsupported query".to_string())),
+    }
+}
+
+// h-entry の作成と action=update / delete
+pub async fn micropub_post<B: BlogRepository, T: TagRepository>(
+    headers: HeaderMap,
+    Extension(blog_repository): Extension<Arc<B>>,
+    Extension(tag_repository): Extension<Arc<T>>,
+    Extension(webmentions): Extension<WebmentionQueue>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    body: Bytes,
+) -> Result<Response, MicropubError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let (action, body_token) = if content_type.starts_with(mime::APPLICATION_JSON.as_ref()) {
+        let action = serde_json::from_slice(&body)
+            .map_err(|e| MicropubError::InvalidRequest(format!("Json parse error: [{}]", e)))
+            .and_then(micropub::parse_json);
+        (action, None)
+    } else if content_type.starts_with(mime::APPLICATION_WWW_FORM_URLENCODED.as_ref()) {
+        let parsed = serde_urlencoded::from_bytes(&body)
+            .map_err(|e| MicropubError::InvalidRequest(format!("Form parse error: [{}]", e)))
+            .and_then(micropub::parse_form);
+        match parsed {
+            Ok((action, token)) => (Ok(action), token),
+            Err(e) => (Err(e), None),
+        }
+    } else {
+        (Err(MicropubError::InvalidRequest(format!("unsupported content type: {}", content_type))), None)
+    };
+    // 内容の誤りより先に認証の結果を返す
+    authenticate(&headers, body_token, &config.auth)?;
+
+    match action? {
+        Action::Create(properties) => {
+            let post = Post::from_properties(&properties)?;
+            let categories = post.categories.clone();
+            // タグを作る前に検証して、失敗した投稿のためにタグだけが増えないようにする
+            let mut payload = post.into_create_blog(vec![], Default::default());
+            payload.validate().map_err(validation_error)?;
+            payload.tags = tag_repository
+                .ids_for_names(&categories)
+                .await
+                .or(Err(MicropubError::Internal))?;
+            let blog = blog_repository
+                .create(payload)
+                .await
+                .or(Err(MicropubError::Internal))?;
+            webmentions.send(blog.id);
+            let location = HeaderValue::from_str(&config.site.blog_url(blog.id)).or(Err(MicropubError::Internal))?;
+            let mut headers = HeaderMap::new();
+            headers.insert(header::LOCATION, location);
+            Ok((StatusCode::CREATED, headers).into_response())
+        }
+        Action::Update { url, update } => {
+            let blog = find_by_url(&*blog_repository, &url, &config).await?;
+            let mut properties = micropub::properties(&blog);
+            micropub::apply(&mut properties, &update);
+            let post = Post::from_properties(&properties)?;
+            let categories = post.categories.clone();
+            let mut payload = post.into_create_blog(vec![], blog.seo.clone());
+            payload.validate().map_err(validation_error)?;
+            payload.tags = tag_repository
+                .ids_for_names(&categories)
+                .await
+                .or(Err(MicropubError::Internal))?;
+            blog_repository
+                .update(blog.id, payload.into())
+                .await
+                .or(Err(MicropubError::Internal))?;
+            webmentions.send(blog.id);
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+        Action::Delete { url } => {
+            let blog = find_by_url(&*blog_repository, &url, &config).await?;
+            blog_repository
+                .delete(blog.id)
+                .await
+                .or(Err(MicropubError::Internal))?;
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+    }
+}
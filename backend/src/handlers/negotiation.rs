@@ -0,0 +1,146 @@
+use axum::http::{header, HeaderMap, StatusCode};
+
+// 1件のブログで返せる表現
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Representation {
+    Json,
+    Markdown,
+    Html,
+}
+
+impl Representation {
+    // Accept で同じ q 値が並んだときはこの順に優先する
+    const ALL: [Representation; 3] = [Representation::Json, Representation::Markdown, Representation::Html];
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Representation::Json => "application/json",
+            Representation::Markdown => "text/markdown; charset=utf-8",
+            Representation::Html => "text/html; charset=utf-8",
+        }
+    }
+
+    fn media_type(&self) -> (&'static str, &'static str) {
+        match self {
+            Representation::Json => ("application", "json"),
+            Representation::Markdown => ("text", "markdown"),
+            Representation::Html => ("text", "html"),
+        }
+    }
+
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix {
+            "json" => Some(Representation::Json),
+            "md" => Some(Representation::Markdown),
+            "html" => Some(Representation::Html),
+            _ => None,
+        }
+    }
+}
+
+// Accept ヘッダの1要素 (例: "text/*;q=0.8")
+struct MediaRange {
+    type_: String,
+    subtype: String,
+    q: f32,
+}
+
+impl MediaRange {
+    fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split(';').map(str::trim);
+        let (type_, subtype) = parts.next()?.split_once('/')?;
+        let mut q = 1.0;
+        for param in parts {
+            if let Some((name, value)) = param.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("q") {
+                    q = value.trim().parse().ok()?;
+                }
+            }
+        }
+        Some(MediaRange {
+            type_: type_.trim().to_ascii_lowercase(),
+            subtype: subtype.trim().to_ascii_lowercase(),
+            q,
+        })
+    }
+
+    // 具体的な指定ほど優先する (RFC 7231 5.3.2)
+    fn specificity(&self, (type_, subtype): (&str, &str)) -> Option<u8> {
+        match (self.type_.as_str(), self.subtype.as_str()) {
+            (t, s) if t == type_ && s == subtype => Some(2),
+            (t, "*") if t == type_ => Some(1),
+            ("*", "*") => Some(0),
+            _ => None,
+        }
+    }
+}
+
+fn negotiate_accept(accept: &str) -> Option<Representation> {
+    let ranges: Vec<MediaRange> = accept.split(',').filter_map(MediaRange::parse).collect();
+    let mut best: Option<(Representation, f32)> = None;
+    for representation in Representation::ALL {
+        let q = ranges
+            .iter()
+            .filter_map(|range| range.specificity(representation.media_type()).map(|s| (s, range.q)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, q)| q)
+            .unwrap_or(0.0);
+        if q > 0.0 && best.map_or(true, |(_, best_q)| q > best_q) {
+            best = Some((representation, q));
+        }
+    }
+    best.map(|(representation, _)| representation)
+}
+
+// /blogs/12.md のような拡張子付きの id を分解する。拡張子は Accept より優先する
+pub fn negotiate(segment: &str, headers: &HeaderMap) -> Result<(i32, Representation), StatusCode> {
+    let (id, suffix) = match segment.split_once('.') {
+        Some((id, suffix)) => (id, Some(suffix)),
+        None => (segment, None),
+    };
+    let id = id.parse().or(Err(StatusCode::BAD_REQUEST))?;
+    if let Some(suffix) = suffix {
+        let representation = Representation::from_suffix(suffix).ok_or(StatusCode::NOT_ACCEPTABLE)?;
+        return Ok((id, representation));
+    }
+
+    let accept = match headers.get(header::ACCEPT) {
+        Some(accept) => accept.to_str().or(Err(StatusCode::NOT_ACCEPTABLE))?,
+        None => return Ok((id, Representation::Json)),
+    };
+    let representation = negotiate_accept(accept).ok_or(StatusCode::NOT_ACCEPTABLE)?;
+    Ok((id, representation))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn accept(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn negotiate_representation() {
+        let json = Ok((1, Representation::Json));
+        let markdown = Ok((1, Representation::Markdown));
+        let html = Ok((1, Representation::Html));
+
+        assert_eq!(json, negotiate("1", &HeaderMap::new()));
+        assert_eq!(json, negotiate("1", &accept("*/*")));
+        assert_eq!(markdown, negotiate("1", &accept("text/markdown")));
+        assert_eq!(html, negotiate("1", &accept("text/html,application/xhtml+xml,*/*;q=0.8")));
+        assert_eq!(markdown, negotiate("1", &accept("text/*;q=0.5, text/markdown, application/json;q=0.9")));
+        assert_eq!(markdown, negotiate("1", &accept("*/*;q=0.1, text/markdown;q=0.2")));
+        assert_eq!(Err(StatusCode::NOT_ACCEPTABLE), negotiate("1", &accept("application/pdf")));
+        assert_eq!(Err(StatusCode::NOT_ACCEPTABLE), negotiate("1", &accept("application/json;q=0")));
+
+        assert_eq!(markdown, negotiate("1.md", &accept("application/json")));
+        assert_eq!(html, negotiate("1.html", &HeaderMap::new()));
+        assert_eq!(Err(StatusCode::NOT_ACCEPTABLE), negotiate("1.pdf", &HeaderMap::new()));
+        assert_eq!(Err(StatusCode::BAD_REQUEST), negotiate("abc.md", &HeaderMap::new()));
+    }
+}
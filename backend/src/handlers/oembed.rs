@@ -0,0 +1,43 @@
+use axum::{
+    extract::{Extension, Query},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::oembed::{self, Embed, Format};
+use crate::repositories::blog::{BlogRepository, BlogStatus};
+
+use super::cache::conditional;
+
+#[derive(Debug, Deserialize)]
+pub struct OembedQuery {
+    url: String,
+    format: Option<String>,
+    maxwidth: Option<u32>,
+    maxheight: Option<u32>,
+}
+
+// 記事の URL を埋め込み用のカードにする。このサイトの記事でなければ 404
+pub async fn oembed_handler<B: BlogRepository>(
+    Query(query): Query<OembedQuery>,
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<B>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let format = Format::parse(query.format.as_deref()).ok_or(StatusCode::NOT_IMPLEMENTED)?;
+    let id = oembed::resolve(&query.url, &config.site).ok_or(StatusCode::NOT_FOUND)?;
+    let blog = repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
+    // 下書きは公開前なので存在しないものとして扱う
+    if blog.status == BlogStatus::Draft {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let embed = Embed::new(&blog, &config.site, query.maxwidth, query.maxheight);
+    let body = match format {
+        Format::Json => serde_json::to_vec(&embed).or(Err(StatusCode::INTERNAL_SERVER_ERROR))?,
+        Format::Xml => embed.to_xml().into_bytes(),
+    };
+    conditional(&headers, body, format.content_type(), Some(blog.updated_at), &config.cache.blog)
+}
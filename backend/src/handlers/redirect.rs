@@ -0,0 +1,30 @@
+use axum::{
+    extract::Extension,
+    http::{header::LOCATION, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::repositories::redirect::RedirectRepository;
+
+// どのルートにも一致しなかったパスを、取り込み時に記録した旧パーマリンクとして探す
+pub async fn permalink_redirect<R: RedirectRepository>(
+    uri: Uri,
+    Extension(repository): Extension<Arc<R>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let path = uri.path().trim_end_matches('/');
+    let blog_id = repository
+        .find(path)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        LOCATION,
+        HeaderValue::from_str(&config.site.blog_url(blog_id)).or(Err(StatusCode::INTERNAL_SERVER_ERROR))?,
+    );
+    Ok((StatusCode::MOVED_PERMANENTLY, headers))
+}
@@ -0,0 +1,104 @@
+use axum::{
+    extract::{Extension, Path},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::repositories::{
+    blog::{BlogField, BlogFields, BlogRepository},
+    tag::TagRepository,
+};
+use crate::sitemap::{self, SitemapCache, SitemapUrl, SitemapVersion, MAX_URLS};
+
+use super::cache::conditional;
+
+// 記事とタグの一覧が変わっていなければ、前に作った URL の一覧を使う。
+// 一覧が最後に変わった時刻も返すので、Last-Modified にはこちらを使う。
+// lastmod の最大値は記事を削除したり非公開にしたりしても進まない
+async fn sitemap_urls<B: BlogRepository, T: TagRepository>(
+    blog_repository: &B,
+    tag_repository: &T,
+    cache: &SitemapCache,
+    config: &AppConfig,
+) -> Result<(SitemapVersion, Arc<Vec<SitemapUrl>>), StatusCode> {
+    let version = (
+        blog_repository
+            .last_modified()
+            .await
+            .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?,
+        tag_repository
+            .last_modified()
+            .await
+            .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?,
+    );
+    if let Some(urls) = cache.get(version) {
+        return Ok((version, urls));
+    }
+
+    // URL と lastmod だけ作れればいいので body は読まない
+    let fields = BlogFields::new(vec![
+        BlogField::Id,
+        BlogField::Tags,
+        BlogField::Status,
+        BlogField::Seo,
+        BlogField::UpdatedAt,
+    ]);
+    let blogs = blog_repository
+        .all_with(&fields)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let tags = tag_repository
+        .all()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((version, cache.put(version, sitemap::urls(&blogs, &tags, &config.site))))
+}
+
+pub async fn sitemap_xml<B: BlogRepository, T: TagRepository>(
+    headers: HeaderMap,
+    Extension(blog_repository): Extension<Arc<B>>,
+    Extension(tag_repository): Extension<Arc<T>>,
+    Extension(cache): Extension<SitemapCache>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let ((blogs_modified, tags_modified), urls) =
+        sitemap_urls(&*blog_repository, &*tag_repository, &cache, &config).await?;
+    conditional(
+        &headers,
+        sitemap::sitemap(&urls, &config.site, MAX_URLS).into_bytes(),
+        sitemap::CONTENT_TYPE,
+        blogs_modified.max(tags_modified),
+        &config.cache.sitemap,
+    )
+}
+
+// sitemap index から参照される /sitemaps/{n}.xml
+pub async fn sitemap_page<B: BlogRepository, T: TagRepository>(
+    Path(file): Path<String>,
+    headers: HeaderMap,
+    Extension(blog_repository): Extension<Arc<B>>,
+    Extension(tag_repository): Extension<Arc<T>>,
+    Extension(cache): Extension<SitemapCache>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let page: usize = file
+        .strip_suffix(".xml")
+        .and_then(|page| page.parse().ok())
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let ((blogs_modified, tags_modified), urls) =
+        sitemap_urls(&*blog_repository, &*tag_repository, &cache, &config).await?;
+    let urls = sitemap::sitemap_page(&urls, page, MAX_URLS).ok_or(StatusCode::NOT_FOUND)?;
+    conditional(
+        &headers,
+        sitemap::urlset(urls).into_bytes(),
+        sitemap::CONTENT_TYPE,
+        blogs_modified.max(tags_modified),
+        &config.cache.sitemap,
+    )
+}
+
+pub async fn robots_txt(Extension(config): Extension<Arc<AppConfig>>) -> impl IntoResponse {
+    sitemap::robots(&config.site)
+}
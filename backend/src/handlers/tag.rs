@@ -1,41 +1,64 @@
 use axum::{
     extract::{Extension, Path},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
-    Json,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use validator::Validate;
 
-use crate::repositories::tag::TagRepository;
+use crate::config::AppConfig;
+use crate::repositories::{idempotency::IdempotencyRepository, tag::TagRepository};
 
-use super::ValidatedJson;
+use super::{
+    auth::AuthUser,
+    cache::conditional_json,
+    idempotency::{fingerprint, idempotent, IdempotencyKey},
+    ValidatedJson,
+};
 
-pub async fn create_tag<T: TagRepository>(
+pub async fn create_tag<T: TagRepository, I: IdempotencyRepository>(
+    user: AuthUser,
+    idempotency_key: IdempotencyKey,
     ValidatedJson(payload): ValidatedJson<CreateTag>,
-    Extension(repository): Extension<Arc<T>>
+    Extension(repository): Extension<Arc<T>>,
+    Extension(idempotency): Extension<Arc<I>>,
+    Extension(config): Extension<Arc<AppConfig>>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let tag = repository
-        .create(payload.name)
-        .await
-        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    // キーはアカウントごとに分ける。別のユーザーが同じキーを使っても、その応答は返さない
+    let scope = format!("POST /tags user:{}", user.id);
+    let fingerprint = fingerprint(&scope, &payload)?;
+    idempotent(&*idempotency, &config.idempotency, idempotency_key, &scope, fingerprint, || async move {
+        let tag = repository
+            .create(payload.name)
+            .await
+            .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
 
-    Ok((StatusCode::CREATED, Json(tag)))
+        Ok((StatusCode::CREATED, tag))
+    })
+    .await
 }
 
 pub async fn all_tag<T: TagRepository>(
-    Extension(repository): Extension<Arc<T>>
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(config): Extension<Arc<AppConfig>>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let tags = repository
         .all()
         .await
         .unwrap();
+    let last_modified = repository
+        .last_modified()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
 
-    Ok((StatusCode::OK, Json(tags)))
+    conditional_json(&headers, &tags, last_modified, &config.cache.tags)
 }
 
 pub async fn delete_tag<T: TagRepository>(
+    _user: AuthUser,
     Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>
 ) -> StatusCode {
@@ -46,7 +69,7 @@ pub async fn delete_tag<T: TagRepository>(
         .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Validate)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Validate, JsonSchema)]
 pub struct CreateTag {
     #[validate(length(min = 1, message = "Can not be empty"))]
     #[validate(length(max = 100, message = "Over text length"))]
@@ -0,0 +1,58 @@
+use axum::{
+    extract::{Extension, Form, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::repositories::{
+    blog::{BlogRepository, BlogStatus},
+    webmention::WebmentionRepository,
+};
+use crate::webmention::{self, WebmentionQueue};
+
+#[derive(Debug, Deserialize)]
+pub struct WebmentionForm {
+    source: String,
+    target: String,
+}
+
+// source の確認は外部サイトへのアクセスになるので、保存だけして 202 を返す
+pub async fn receive_webmention<B: BlogRepository, W: WebmentionRepository>(
+    Extension(blog_repository): Extension<Arc<B>>,
+    Extension(webmention_repository): Extension<Arc<W>>,
+    Extension(queue): Extension<WebmentionQueue>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Form(form): Form<WebmentionForm>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let blog_id = webmention::validate(&form.source, &form.target, &config.site)
+        .map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+    let blog = blog_repository
+        .find(blog_id)
+        .await
+        .ok()
+        .filter(|blog| blog.status == BlogStatus::Published)
+        .ok_or((StatusCode::BAD_REQUEST, "target post does not exist".to_string()))?;
+    let webmention = webmention_repository
+        .save(blog.id, &form.source, &form.target)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    queue.verify(webmention.id);
+    Ok((StatusCode::ACCEPTED, "webmention is queued for verification"))
+}
+
+pub async fn blog_mentions<B: BlogRepository, W: WebmentionRepository>(
+    Path(id): Path<i32>,
+    Extension(blog_repository): Extension<Arc<B>>,
+    Extension(webmention_repository): Extension<Arc<W>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    blog_repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
+    let webmentions = webmention_repository
+        .verified_for(id)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(Json(webmentions))
+}
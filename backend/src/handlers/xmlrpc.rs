@@ -0,0 +1,141 @@
+use axum::{
+    extract::Extension,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::auth;
+use crate::config::AppConfig;
+use crate::repositories::{blog::BlogRepository, tag::TagRepository, user::UserRepository};
+use crate::webmention::WebmentionQueue;
+use crate::xmlrpc::{
+    self,
+    metaweblog::{category_struct, post_struct, PostFields},
+    Fault, MethodCall, Value, FORBIDDEN, INVALID_PARAMS, METHOD_NOT_FOUND,
+};
+
+// XML-RPC ではエラーも 200 の fault として返す
+pub async fn xmlrpc_handler<B: BlogRepository, T: TagRepository, U: UserRepository>(
+    Extension(blog_repository): Extension<Arc<B>>,
+    Extension(tag_repository): Extension<Arc<T>>,
+    Extension(user_repository): Extension<Arc<U>>,
+    Extension(webmentions): Extension<WebmentionQueue>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    body: String,
+) -> impl IntoResponse {
+    let body = match xmlrpc::parse_call(&body) {
+        Ok(call) => match dispatch(&call, &*blog_repository, &*tag_repository, &*user_repository, &webmentions, &config).await {
+            Ok(value) => xmlrpc::response(&value),
+            Err(fault) => fault.to_xml(),
+        },
+        Err(fault) => fault.to_xml(),
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(xmlrpc::CONTENT_TYPE));
+    (StatusCode::OK, headers, body)
+}
+
+// /auth/login と同じユーザーとパスワードでログインする。どちらが違っても同じ fault にする
+async fn authenticate<U: UserRepository>(call: &MethodCall, index: usize, repository: &U) -> Result<(), Fault> {
+    let username = call.string(index)?;
+    let password = call.string(index + 1)?;
+    let user = repository.find_by_username(username).await?;
+    match user.filter(|user| auth::verify_password(password, &user.password_hash)) {
+        Some(_) => Ok(()),
+        None => Err(Fault::new(FORBIDDEN, "incorrect username or password")),
+    }
+}
+
+fn post_id(call: &MethodCall, index: usize) -> Result<i32, Fault> {
+    let id = call.int(index)?;
+    i32::try_from(id).map_err(|_| Fault::new(INVALID_PARAMS, format!("invalid post id: {}", id)))
+}
+
+async fn dispatch<B: BlogRepository, T: TagRepository, U: UserRepository>(
+    call: &MethodCall,
+    blog_repository: &B,
+    tag_repository: &T,
+    user_repository: &U,
+    webmentions: &WebmentionQueue,
+    config: &AppConfig,
+) -> Result<Value, Fault> {
+    let site = &config.site;
+    match call.name.as_str() {
+        // (appkey, username, password)
+        "blogger.getUsersBlogs" => {
+            authenticate(call, 1, user_repository).await?;
+            let mut blog = BTreeMap::new();
+            blog.insert("blogid".to_string(), Value::from("1"));
+            blog.insert("blogName".to_string(), Value::from(site.title.clone()));
+            blog.insert("url".to_string(), Value::from(site.url("/")));
+            blog.insert("xmlrpc".to_string(), Value::from(site.url(xmlrpc::XMLRPC_PATH)));
+            blog.insert("isAdmin".to_string(), Value::from(true));
+            Ok(Value::from(vec![Value::from(blog)]))
+        }
+        // (blogid, username, password, struct, publish)
+        "metaWeblog.newPost" => {
+            authenticate(call, 1, user_repository).await?;
+            let fields = PostFields::from_struct(call.members(3)?, call.boolean(4)?)?;
+            let categories = fields.categories.clone().unwrap_or_default();
+            // タグを作る前に検証して、失敗した投稿のためにタグだけが増えないようにする
+            let mut payload = fields.into_create_blog(vec![]);
+            payload.validate()?;
+            payload.tags = tag_repository.ids_for_names(&categories).await?;
+            let blog = blog_repository.create(payload).await?;
+            webmentions.send(blog.id);
+            Ok(Value::from(blog.id.to_string()))
+        }
+        // (postid, username, password, struct, publish)
+        "metaWeblog.editPost" => {
+            authenticate(call, 1, user_repository).await?;
+            let id = post_id(call, 0)?;
+            let fields = PostFields::from_struct(call.members(3)?, call.boolean(4)?)?;
+            let categories = fields.categories.clone();
+            let mut payload = fields.into_update_blog(None);
+            payload.validate()?;
+            blog_repository.find(id).await?;
+            if let Some(categories) = categories {
+                payload.tags = Some(Some(tag_repository.ids_for_names(&categories).await?));
+            }
+            blog_repository.update(id, payload).await?;
+            webmentions.send(id);
+            Ok(Value::from(true))
+        }
+        // (postid, username, password)
+        "metaWeblog.getPost" => {
+            authenticate(call, 1, user_repository).await?;
+            let id = post_id(call, 0)?;
+            let blog = blog_repository.find(id).await?;
+            Ok(post_struct(&blog, site))
+        }
+        // (blogid, username, password, numberOfPosts)
+        "metaWeblog.getRecentPosts" => {
+            authenticate(call, 1, user_repository).await?;
+            let limit = usize::try_from(call.int(3)?).unwrap_or_default();
+            let mut blogs = blog_repository.all().await?;
+            blogs.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+            Ok(Value::from(
+                blogs.iter().take(limit).map(|blog| post_struct(blog, site)).collect::<Vec<_>>(),
+            ))
+        }
+        // (blogid, username, password)
+        "metaWeblog.getCategories" => {
+            authenticate(call, 1, user_repository).await?;
+            let tags = tag_repository.all().await?;
+            Ok(Value::from(
+                tags.iter().map(|tag| category_struct(tag, site)).collect::<Vec<_>>(),
+            ))
+        }
+        // (appkey, postid, username, password, publish)
+        "blogger.deletePost" => {
+            authenticate(call, 2, user_repository).await?;
+            let id = post_id(call, 1)?;
+            blog_repository.delete(id).await?;
+            Ok(Value::from(true))
+        }
+        name => Err(Fault::new(METHOD_NOT_FOUND, format!("method not found: {}", name))),
+    }
+}
@@ -0,0 +1,240 @@
+pub mod html;
+pub mod markdown;
+pub mod wordpress;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use validator::Validate;
+
+use crate::repositories::{
+    blog::{BlogRepository, BlogSeo, BlogStatus, CreateBlog},
+    import::ImportRepository,
+    redirect::RedirectRepository,
+    tag::TagRepository,
+};
+
+// 取り込み元から読み出した1記事分。source は取り込み元の中で一意なキー
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportEntry {
+    pub source: String,
+    pub checksum: String,
+    pub title: String,
+    pub body: String,
+    pub tags: Vec<String>,
+    pub status: BlogStatus,
+    pub slug: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+    // 移行前の URL のパス。記事へのリダイレクトとして記録する
+    pub redirects: Vec<String>,
+}
+
+// URL やパスからリダイレクトに使うパスを取り出す。末尾の / とクエリは取り除く
+pub fn permalink_path(url: &str) -> Option<String> {
+    let path = match url.find("://") {
+        Some(scheme) => {
+            let rest = &url[scheme + 3..];
+            &rest[rest.find('/')?..]
+        }
+        None => url,
+    };
+    let path = path.split(|c| c == '?' || c == '#').next().unwrap_or_default();
+    let path = path.trim_end_matches('/');
+    match path.starts_with('/') {
+        true => Some(path.to_string()),
+        false => None,
+    }
+}
+
+// WordPress の post_name や AtomPub の Slug ヘッダの日本語はパーセントエンコードされている
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        match (bytes[i], hex.and_then(|hex| u8::from_str_radix(hex, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportAction {
+    Create,
+    Update,
+    // 前回取り込んだときから変わっていない
+    Skip,
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportItem {
+    pub source: String,
+    pub title: Option<String>,
+    pub action: ImportAction,
+    pub blog_id: Option<i32>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub items: Vec<ImportItem>,
+    pub new_tags: Vec<String>,
+}
+
+impl ImportReport {
+    pub fn count(&self, action: ImportAction) -> usize {
+        self.items.iter().filter(|item| item.action == action).count()
+    }
+}
+
+// dry_run の場合は記事もタグも作らず、何が起きるかだけを report に積む
+pub struct Importer<'a, B, T, I, R> {
+    blog_repository: &'a B,
+    tag_repository: &'a T,
+    import_repository: &'a I,
+    redirect_repository: &'a R,
+    dry_run: bool,
+    tags: HashMap<String, i32>,
+    report: ImportReport,
+}
+
+impl<'a, B, T, I, R> Importer<'a, B, T, I, R>
+where
+    B: BlogRepository,
+    T: TagRepository,
+    I: ImportRepository,
+    R: RedirectRepository,
+{
+    pub async fn new(
+        blog_repository: &'a B,
+        tag_repository: &'a T,
+        import_repository: &'a I,
+        redirect_repository: &'a R,
+        dry_run: bool,
+    ) -> anyhow::Result<Importer<'a, B, T, I, R>> {
+        let tags = tag_repository
+            .all()
+            .await?
+            .into_iter()
+            .map(|tag| (tag.name, tag.id))
+            .collect();
+        Ok(Importer {
+            blog_repository,
+            tag_repository,
+            import_repository,
+            redirect_repository,
+            dry_run,
+            tags,
+            report: ImportReport { dry_run, ..ImportReport::default() },
+        })
+    }
+
+    pub async fn import(&mut self, entry: ImportEntry) {
+        let source = entry.source.clone();
+        let title = Some(entry.title.clone());
+        let item = match self.apply(entry).await {
+            Ok((action, blog_id)) => ImportItem { source, title, action, blog_id, error: None },
+            Err(e) => ImportItem {
+                source,
+                title,
+                action: ImportAction::Fail,
+                blog_id: None,
+                error: Some(e.to_string()),
+            },
+        };
+        self.report.items.push(item);
+    }
+
+    // 読み込みや解析に失敗したもの
+    pub fn fail(&mut self, source: String, error: anyhow::Error) {
+        self.report.items.push(ImportItem {
+            source,
+            title: None,
+            action: ImportAction::Fail,
+            blog_id: None,
+            error: Some(error.to_string()),
+        });
+    }
+
+    pub fn finish(self) -> ImportReport {
+        self.report
+    }
+
+    async fn apply(&mut self, entry: ImportEntry) -> anyhow::Result<(ImportAction, Option<i32>)> {
+        let imported = self.import_repository.find(&entry.source).await?;
+        if let Some(imported) = &imported {
+            if imported.checksum == entry.checksum {
+                return Ok((ImportAction::Skip, Some(imported.blog_id)));
+            }
+        }
+
+        // タグを作る前に検証して、失敗する記事のためにタグだけが増えないようにする
+        let mut payload = CreateBlog {
+            title: entry.title,
+            body: entry.body,
+            tags: vec![],
+            status: entry.status,
+            seo: BlogSeo::default(),
+            slug: entry.slug,
+            published_at: entry.published_at,
+        };
+        payload.validate()?;
+        payload.tags = self.resolve_tags(&entry.tags).await?;
+
+        let action = match imported {
+            Some(_) => ImportAction::Update,
+            None => ImportAction::Create,
+        };
+        if self.dry_run {
+            return Ok((action, imported.map(|imported| imported.blog_id)));
+        }
+
+        let blog = match imported {
+            Some(imported) => self.blog_repository.update(imported.blog_id, payload.into()).await?,
+            None => self.blog_repository.create(payload).await?,
+        };
+        for path in &entry.redirects {
+            self.redirect_repository.save(path, blog.id).await?;
+        }
+        // 最後に記録するので、途中で止まっても再実行すれば記録の無いものから続きを取り込める
+        self.import_repository
+            .record(&entry.source, blog.id, &entry.checksum)
+            .await?;
+        Ok((action, Some(blog.id)))
+    }
+
+    // 同じ名前のタグがあれば使い、無ければ作る
+    async fn resolve_tags(&mut self, names: &[String]) -> anyhow::Result<Vec<i32>> {
+        let mut ids = Vec::new();
+        for name in names {
+            if let Some(id) = self.tags.get(name) {
+                if !ids.contains(id) {
+                    ids.push(*id);
+                }
+                continue;
+            }
+            if !self.report.new_tags.contains(name) {
+                self.report.new_tags.push(name.clone());
+            }
+            if self.dry_run {
+                continue;
+            }
+            let tag = self.tag_repository.create(name.clone()).await?;
+            self.tags.insert(tag.name, tag.id);
+            ids.push(tag.id);
+        }
+        Ok(ids)
+    }
+}
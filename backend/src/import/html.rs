@@ -0,0 +1,442 @@
+// WordPress の投稿本文 (HTML) を Markdown にする。閉じタグの欠けた HTML も受け付けるように、
+// 対応していない要素はタグだけ取り除いて中身のテキストを残す
+
+enum Token<'a> {
+    Text(&'a str),
+    Start { name: String, attributes: &'a str, closed: bool },
+    End(String),
+}
+
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        // Gutenberg のブロックコメント (<!-- wp:paragraph -->) もここで捨てる
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map(|end| &comment[end + 3..]).unwrap_or("");
+            continue;
+        }
+        if rest.starts_with('<') {
+            if let Some(end) = rest.find('>') {
+                if let Some(token) = tag(&rest[1..end]) {
+                    tokens.push(token);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+        }
+        let next = rest[1..].find('<').map(|i| i + 1).unwrap_or(rest.len());
+        tokens.push(Token::Text(&rest[..next]));
+        rest = &rest[next..];
+    }
+    tokens
+}
+
+// タグとして読めないもの (本文中の "a < b" など) は None にしてテキストとして扱う
+fn tag(inner: &str) -> Option<Token<'_>> {
+    if let Some(name) = inner.strip_prefix('/') {
+        let name = name.trim();
+        return match !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric()) {
+            true => Some(Token::End(name.to_ascii_lowercase())),
+            false => None,
+        };
+    }
+    let closed = inner.ends_with('/');
+    let inner = inner.trim_end_matches('/');
+    let name_end = inner.find(char::is_whitespace).unwrap_or(inner.len());
+    let name = &inner[..name_end];
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    Some(Token::Start {
+        name: name.to_ascii_lowercase(),
+        attributes: &inner[name_end..],
+        closed,
+    })
+}
+
+pub fn attribute(attributes: &str, key: &str) -> Option<String> {
+    let mut rest = attributes.trim_start();
+    while !rest.is_empty() {
+        let name_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let name = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (value, next) = match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = value[1..].find(quote).map(|i| i + 1).unwrap_or(value.len());
+                        (&value[1..end], value.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                        (&value[..end], &value[end..])
+                    }
+                };
+                rest = next.trim_start();
+                value
+            }
+            None => "",
+        };
+        if name.eq_ignore_ascii_case(key) {
+            return Some(decode_entities(value));
+        }
+    }
+    None
+}
+
+// 開始タグの名前と属性部分の一覧。Webmention でほかのサイトのページからリンクを探すのに使う
+pub fn start_tags(html: &str) -> Vec<(String, &str)> {
+    tokenize(html)
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Start { name, attributes, .. } => Some((name, attributes)),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').filter(|end| *end <= 10).map(|end| &rest[1..end + 1]);
+        let value = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            "hellip" => Some('…'),
+            "mdash" => Some('—'),
+            "ndash" => Some('–'),
+            "lsquo" => Some('‘'),
+            "rsquo" => Some('’'),
+            "ldquo" => Some('“'),
+            "rdquo" => Some('”'),
+            _ => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity
+                    .strip_prefix('#')
+                    .and_then(|decimal| decimal.parse().ok())
+                    .and_then(char::from_u32),
+            },
+        });
+        match (entity, value) {
+            (Some(entity), Some(value)) => {
+                decoded.push(value);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+enum Frame {
+    Root,
+    Quote,
+    Item(String),
+    Pre,
+    Heading(usize),
+    Link(Option<String>),
+}
+
+struct Converter {
+    // 入れ子の要素ごとに出力を分けておき、閉じたときに親へ書き込む
+    stack: Vec<(Frame, String)>,
+    // ul は None、ol は次の番号
+    lists: Vec<Option<usize>>,
+    skip: usize,
+}
+
+impl Converter {
+    fn out(&mut self) -> &mut String {
+        &mut self.stack.last_mut().unwrap().1
+    }
+
+    fn in_pre(&self) -> bool {
+        self.stack.iter().any(|(frame, _)| matches!(frame, Frame::Pre))
+    }
+
+    fn at_line_start(&self) -> bool {
+        let out = &self.stack.last().unwrap().1;
+        out.is_empty() || out.ends_with('\n')
+    }
+
+    // 段落の区切り (空行) を入れる
+    fn block(&mut self) {
+        let out = self.out();
+        let trimmed = out.trim_end_matches(|c| c == ' ' || c == '\n').len();
+        out.truncate(trimmed);
+        if !out.is_empty() {
+            out.push_str("\n\n");
+        }
+    }
+
+    // 入れ子のリストは空行を入れずに続ける
+    fn list_break(&mut self) {
+        if !matches!(self.stack.last(), Some((Frame::Item(_), _))) {
+            return self.block();
+        }
+        let out = self.out();
+        let trimmed = out.trim_end_matches(|c| c == ' ' || c == '\n').len();
+        out.truncate(trimmed);
+        if !out.is_empty() {
+            out.push('\n');
+        }
+    }
+
+    fn push_block(&mut self, text: &str) {
+        self.block();
+        self.out().push_str(text);
+        self.block();
+    }
+
+    fn open(&mut self, frame: Frame) {
+        self.stack.push((frame, String::new()));
+    }
+
+    // 一致する要素まで閉じる。途中の閉じられていない要素は中身だけを親に戻す
+    fn close(&mut self, matches: fn(&Frame) -> bool) -> Option<(Frame, String)> {
+        let index = self.stack.iter().rposition(|(frame, _)| matches(frame))?;
+        if index == 0 {
+            return None;
+        }
+        while self.stack.len() > index + 1 {
+            let (_, text) = self.stack.pop().unwrap();
+            self.out().push_str(&text);
+        }
+        self.stack.pop()
+    }
+
+    fn text(&mut self, text: &str) {
+        let text = decode_entities(text);
+        if self.in_pre() {
+            self.out().push_str(&text);
+        } else if self.at_line_start() {
+            self.out().push_str(text.trim_start());
+        } else {
+            self.out().push_str(&text);
+        }
+    }
+
+    fn start(&mut self, name: &str, attributes: &str, closed: bool) {
+        match name {
+            "script" | "style" if !closed => self.skip += 1,
+            "p" | "div" | "figure" | "figcaption" | "section" | "article" | "table" | "tr" => self.block(),
+            "td" | "th" => self.out().push(' '),
+            "br" if self.in_pre() => self.out().push('\n'),
+            "br" => self.out().push_str("  \n"),
+            "hr" => self.push_block("---"),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.block();
+                self.open(Frame::Heading(name[1..].parse().unwrap_or(1)));
+            }
+            "blockquote" => {
+                self.block();
+                self.open(Frame::Quote);
+            }
+            "ul" => {
+                self.list_break();
+                self.lists.push(None);
+            }
+            "ol" => {
+                self.list_break();
+                let start = attribute(attributes, "start").and_then(|start| start.parse().ok());
+                self.lists.push(Some(start.unwrap_or(1)));
+            }
+            "li" => {
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.open(Frame::Item(marker));
+            }
+            "pre" => {
+                self.block();
+                self.open(Frame::Pre);
+            }
+            "code" if !self.in_pre() => self.out().push('`'),
+            "strong" | "b" => self.out().push_str("**"),
+            "em" | "i" => self.out().push('_'),
+            "del" | "s" => self.out().push_str("~~"),
+            "a" if !closed => self.open(Frame::Link(attribute(attributes, "href"))),
+            "img" => {
+                if let Some(src) = attribute(attributes, "src") {
+                    let alt = attribute(attributes, "alt").unwrap_or_default();
+                    let image = format!("![{}]({})", alt, src);
+                    self.out().push_str(&image);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        match name {
+            "p" | "div" | "figure" | "figcaption" | "section" | "article" | "table" | "tr" => self.block(),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                if let Some((Frame::Heading(level), text)) = self.close(|frame| matches!(frame, Frame::Heading(_))) {
+                    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    self.push_block(&format!("{} {}", "#".repeat(level), text));
+                }
+            }
+            "blockquote" => {
+                if let Some((_, text)) = self.close(|frame| matches!(frame, Frame::Quote)) {
+                    let quoted: Vec<String> = text
+                        .trim()
+                        .lines()
+                        .map(|line| format!("> {}", line).trim_end().to_string())
+                        .collect();
+                    self.push_block(&quoted.join("\n"));
+                }
+            }
+            "ul" | "ol" => {
+                self.lists.pop();
+                self.list_break();
+            }
+            "li" => {
+                if let Some((Frame::Item(marker), text)) = self.close(|frame| matches!(frame, Frame::Item(_))) {
+                    let indent = " ".repeat(marker.len());
+                    let mut item = String::new();
+                    for (i, line) in text.trim().lines().enumerate() {
+                        match (i, line.is_empty()) {
+                            (0, _) => item.push_str(&format!("{}{}", marker, line)),
+                            (_, true) => item.push('\n'),
+                            (_, false) => item.push_str(&format!("\n{}{}", indent, line)),
+                        }
+                    }
+                    if !self.at_line_start() {
+                        self.out().push('\n');
+                    }
+                    self.out().push_str(&item);
+                    self.out().push('\n');
+                }
+            }
+            "pre" => {
+                if let Some((_, text)) = self.close(|frame| matches!(frame, Frame::Pre)) {
+                    self.push_block(&format!("```\n{}\n```", text.trim_matches('\n')));
+                }
+            }
+            "code" if !self.in_pre() => self.out().push('`'),
+            "strong" | "b" => self.out().push_str("**"),
+            "em" | "i" => self.out().push('_'),
+            "del" | "s" => self.out().push_str("~~"),
+            "a" => {
+                if let Some((Frame::Link(href), text)) = self.close(|frame| matches!(frame, Frame::Link(_))) {
+                    let link = match href {
+                        Some(href) if !text.trim().is_empty() => format!("[{}]({})", text.trim(), href),
+                        _ => text,
+                    };
+                    self.out().push_str(&link);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(mut self) -> String {
+        while self.stack.len() > 1 {
+            let (_, text) = self.stack.pop().unwrap();
+            self.out().push_str(&text);
+        }
+        let (_, markdown) = self.stack.pop().unwrap();
+        let mut normalized = String::with_capacity(markdown.len());
+        let mut blank_lines = 0;
+        for line in markdown.lines() {
+            let line = match line.trim().is_empty() {
+                true => "",
+                false => line,
+            };
+            blank_lines = if line.is_empty() { blank_lines + 1 } else { 0 };
+            if blank_lines < 2 {
+                normalized.push_str(line);
+                normalized.push('\n');
+            }
+        }
+        normalized.trim().to_string()
+    }
+}
+
+pub fn html_to_markdown(html: &str) -> String {
+    let mut converter = Converter {
+        stack: vec![(Frame::Root, String::new())],
+        lists: Vec::new(),
+        skip: 0,
+    };
+    for token in tokenize(html) {
+        match token {
+            Token::End(name) if converter.skip > 0 => {
+                if name == "script" || name == "style" {
+                    converter.skip -= 1;
+                }
+            }
+            _ if converter.skip > 0 => {}
+            Token::Text(text) => converter.text(text),
+            Token::Start { name, attributes, closed } => converter.start(&name, attributes, closed),
+            Token::End(name) => converter.end(&name),
+        }
+    }
+    converter.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn convert_wordpress_html() {
+        let html = r#"<!-- wp:heading -->
+<h2>Hello &amp; <em>welcome</em></h2>
+<!-- /wp:heading -->
+<p>Read <a href="https://example.com/?a=1&amp;b=2">the <strong>docs</strong></a>.<br>Next line</p>
+<ul>
+  <li>one</li>
+  <li>two
+    <ol start="3"><li>three</li></ol>
+  </li>
+</ul>
+<blockquote><p>quoted</p><p>twice</p></blockquote>
+<pre><code>fn main() {
+    println!("&lt;hi&gt;");
+}</code></pre>
+<p><img src="/a.png" alt="A"> 1 &lt; 2 and a < b</p>
+<script>alert(1)</script>
+<p>unclosed <strong>bold"#;
+        let expected = r#"## Hello & _welcome_
+
+Read [the **docs**](https://example.com/?a=1&b=2).  
+Next line
+
+- one
+- two
+  3. three
+
+> quoted
+>
+> twice
+
+```
+fn main() {
+    println!("<hi>");
+}
+```
+
+![A](/a.png) 1 < 2 and a < b
+
+unclosed **bold"#;
+        assert_eq!(expected, html_to_markdown(html));
+    }
+}
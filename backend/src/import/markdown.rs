@@ -0,0 +1,334 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{permalink_path, ImportEntry, Importer, ImportReport};
+use crate::render::slugify;
+use crate::repositories::{
+    blog::{BlogRepository, BlogStatus},
+    import::ImportRepository,
+    redirect::RedirectRepository,
+    tag::TagRepository,
+};
+
+const SOURCE_PREFIX: &str = "markdown:";
+
+// Hugo の content ディレクトリや Jekyll の _posts ディレクトリ以下の Markdown をまとめて取り込む
+pub async fn import_dir<B, T, I, R>(
+    dir: &Path,
+    blog_repository: &B,
+    tag_repository: &T,
+    import_repository: &I,
+    redirect_repository: &R,
+    dry_run: bool,
+) -> anyhow::Result<ImportReport>
+where
+    B: BlogRepository,
+    T: TagRepository,
+    I: ImportRepository,
+    R: RedirectRepository,
+{
+    let mut importer = Importer::new(
+        blog_repository,
+        tag_repository,
+        import_repository,
+        redirect_repository,
+        dry_run,
+    )
+    .await?;
+    for path in markdown_files(dir)? {
+        let relative = path.strip_prefix(dir).unwrap_or(&path);
+        let source = format!("{}{}", SOURCE_PREFIX, relative.to_string_lossy().replace('\\', "/"));
+        match fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| parse(&source, relative, &content))
+        {
+            Ok(entry) => importer.import(entry).await,
+            Err(e) => importer.fail(source, e),
+        }
+    }
+    Ok(importer.finish())
+}
+
+// 隠しファイルと Hugo のセクションページ (_index.md) を除いた Markdown をパス順に並べる
+fn markdown_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+    for path in entries {
+        let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        if name.starts_with('.') || name == "_index.md" {
+            continue;
+        }
+        if path.is_dir() {
+            files.extend(markdown_files(&path)?);
+        } else if matches!(path.extension().and_then(|ext| ext.to_str()), Some("md" | "markdown")) {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FrontMatter {
+    title: Option<String>,
+    tags: Option<Tags>,
+    date: Option<String>,
+    draft: Option<bool>,
+    // Jekyll は published: false で下書きになる
+    published: Option<bool>,
+    slug: Option<String>,
+    // Hugo の aliases と jekyll-redirect-from の redirect_from は旧 URL としてリダイレクトにする
+    aliases: Vec<String>,
+    redirect_from: Vec<String>,
+}
+
+// Jekyll ではスペース区切りの文字列でも書ける
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Tags {
+    List(Vec<String>),
+    Text(String),
+}
+
+impl Tags {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Tags::List(tags) => tags,
+            Tags::Text(tags) => tags.split_whitespace().map(String::from).collect(),
+        }
+    }
+}
+
+pub fn parse(source: &str, path: &Path, content: &str) -> anyhow::Result<ImportEntry> {
+    let content = content.trim_start_matches('\u{feff}');
+    let (front_matter, body) = split_front_matter(content)?;
+    let (file_date, file_slug) = from_file_name(path);
+
+    let title = front_matter
+        .title
+        .filter(|title| !title.trim().is_empty())
+        .ok_or_else(|| anyhow::anyhow!("title is missing"))?;
+    let published_at = match front_matter.date {
+        Some(date) => Some(parse_date(&date).ok_or_else(|| anyhow::anyhow!("unknown date format [{}]", date))?),
+        None => file_date,
+    };
+    let draft = front_matter.draft.unwrap_or(false) || front_matter.published == Some(false);
+    let mut tags: Vec<String> = Vec::new();
+    for tag in front_matter.tags.map(Tags::into_vec).unwrap_or_default() {
+        let tag = tag.trim().to_string();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    Ok(ImportEntry {
+        source: source.to_string(),
+        checksum: format!("{:x}", Sha256::digest(content.as_bytes())),
+        title: title.trim().to_string(),
+        body: body.trim().to_string(),
+        tags,
+        status: match draft {
+            true => BlogStatus::Draft,
+            false => BlogStatus::Published,
+        },
+        slug: front_matter.slug.as_deref().and_then(slugify).or(file_slug),
+        published_at,
+        redirects: front_matter
+            .aliases
+            .iter()
+            .chain(&front_matter.redirect_from)
+            .filter_map(|alias| permalink_path(alias))
+            .collect(),
+    })
+}
+
+// --- なら YAML、+++ なら TOML の front matter
+fn split_front_matter(content: &str) -> anyhow::Result<(FrontMatter, &str)> {
+    let delimiter = match content.lines().next().map(str::trim_end) {
+        Some("---") => "---",
+        Some("+++") => "+++",
+        _ => return Ok((FrontMatter::default(), content)),
+    };
+    let rest = &content[content.find('\n').map(|i| i + 1).unwrap_or(content.len())..];
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == delimiter {
+            let raw = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            let value = match delimiter {
+                "---" => serde_yaml::from_str::<Option<Value>>(raw)?.unwrap_or(Value::Null),
+                _ => toml_to_json(toml::from_str(raw)?),
+            };
+            let front_matter = match value {
+                Value::Null => FrontMatter::default(),
+                value => serde_json::from_value(value)?,
+            };
+            return Ok((front_matter, body));
+        }
+        offset += line.len();
+    }
+    Err(anyhow::anyhow!("front matter is not closed with {}", delimiter))
+}
+
+// TOML の日付型は文字列にして YAML と同じ形で扱う
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(value) => Value::String(value),
+        toml::Value::Integer(value) => Value::from(value),
+        toml::Value::Float(value) => Value::from(value),
+        toml::Value::Boolean(value) => Value::Bool(value),
+        toml::Value::Datetime(value) => Value::String(value.to_string()),
+        toml::Value::Array(values) => Value::Array(values.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table.into_iter().map(|(key, value)| (key, toml_to_json(value))).collect(),
+        ),
+    }
+}
+
+// Jekyll の 2023-10-30-my-post.md から日付と slug を、Hugo の page bundle (my-post/index.md) はディレクトリ名を slug にする
+fn from_file_name(path: &Path) -> (Option<DateTime<Utc>>, Option<String>) {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let stem = match stem.as_str() {
+        "index" => path
+            .parent()
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        _ => stem,
+    };
+    let date = stem
+        .get(..10)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+    match date {
+        Some(date) if stem[10..].starts_with('-') => (
+            Some(DateTime::from_utc(date.and_hms(0, 0, 0), Utc)),
+            slugify(&stem[11..]),
+        ),
+        _ => (None, slugify(&stem)),
+    }
+}
+
+// タイムゾーンが無いものは UTC として扱う
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S %z", "%Y-%m-%d %H:%M %z"] {
+        if let Ok(date) = DateTime::parse_from_str(value, format) {
+            return Some(date.with_timezone(&Utc));
+        }
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(DateTime::from_utc(date, Utc));
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .map(|date| DateTime::from_utc(date.and_hms(0, 0, 0), Utc))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::import::ImportAction;
+    use crate::repositories::{
+        blog::test_utils::BlogRepositoryForMemory,
+        import::test_utils::ImportRepositoryForMemory,
+        redirect::{test_utils::RedirectRepositoryForMemory, RedirectRepository},
+        tag::{test_utils::TagRepositoryForMemory, Tag},
+    };
+    use chrono::TimeZone;
+
+    #[test]
+    fn parse_hugo_and_jekyll_front_matter() {
+        let hugo = "+++\ntitle = \"Hello Hugo\"\ntags = [\"rust\", \"hugo\"]\ndate = 2023-10-30T09:00:00+09:00\ndraft = true\nslug = \"hello\"\naliases = [\"/old/hello/\"]\n+++\n\nbody\n";
+        let entry = parse("markdown:hello.md", Path::new("post/hello.md"), hugo).unwrap();
+        assert_eq!("Hello Hugo", entry.title);
+        assert_eq!(vec!["rust", "hugo"], entry.tags);
+        assert_eq!(Some(Utc.ymd(2023, 10, 30).and_hms(0, 0, 0)), entry.published_at);
+        assert_eq!(BlogStatus::Draft, entry.status);
+        assert_eq!(Some("hello".to_string()), entry.slug);
+        assert_eq!("body", entry.body);
+        assert_eq!(vec!["/old/hello".to_string()], entry.redirects);
+
+        let jekyll = "---\ntitle: Hello Jekyll\ntags: rust jekyll\n---\nbody\n";
+        let entry = parse("markdown:_posts/2023-10-29-hello-jekyll.md", Path::new("_posts/2023-10-29-hello-jekyll.md"), jekyll).unwrap();
+        assert_eq!(vec!["rust", "jekyll"], entry.tags);
+        assert_eq!(Some(Utc.ymd(2023, 10, 29).and_hms(0, 0, 0)), entry.published_at);
+        assert_eq!(BlogStatus::Published, entry.status);
+        assert_eq!(Some("hello-jekyll".to_string()), entry.slug);
+
+        let bundle = "---\ntitle: Bundle\ndate: 2023-10-28 12:00:00 +0900\npublished: false\n---\n";
+        let entry = parse("markdown:bundle/index.md", Path::new("bundle/index.md"), bundle).unwrap();
+        assert_eq!(Some(Utc.ymd(2023, 10, 28).and_hms(3, 0, 0)), entry.published_at);
+        assert_eq!(BlogStatus::Draft, entry.status);
+        assert_eq!(Some("bundle".to_string()), entry.slug);
+
+        assert!(parse("markdown:no-title.md", Path::new("no-title.md"), "---\ndraft: true\n---\n").is_err());
+        assert!(parse("markdown:open.md", Path::new("open.md"), "---\ntitle: open\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn dry_run_then_apply_idempotently() {
+        let dir = std::env::temp_dir().join(format!("next_blog-import-{}", std::process::id()));
+        fs::create_dir_all(dir.join("posts")).unwrap();
+        fs::write(
+            dir.join("posts/2023-10-01-first.md"),
+            "---\ntitle: First\ntags: [rust, hugo]\nredirect_from: [/2023/10/first.html]\n---\nfirst\n",
+        )
+        .unwrap();
+        fs::write(dir.join("posts/second.md"), "+++\ntitle = \"Second\"\ntags = [\"rust\"]\n+++\nsecond\n").unwrap();
+        fs::write(dir.join("posts/broken.md"), "---\ntags: [rust]\n---\nno title\n").unwrap();
+        fs::write(dir.join("_index.md"), "---\ntitle: Section\n---\n").unwrap();
+
+        let tag_repository = TagRepositoryForMemory::new();
+        tag_repository.create("rust".to_string()).await.unwrap();
+        let blog_repository = BlogRepositoryForMemory::new(vec![
+            Tag::new(1, "rust".to_string()),
+            Tag::new(2, "hugo".to_string()),
+        ]);
+        let import_repository = ImportRepositoryForMemory::new();
+        let redirect_repository = RedirectRepositoryForMemory::new();
+        let (path, blogs, tags) = (dir.as_path(), &blog_repository, &tag_repository);
+        let (imports, redirects) = (&import_repository, &redirect_repository);
+        let run = move |dry_run| import_dir(path, blogs, tags, imports, redirects, dry_run);
+
+        // dry-run では何も作らない
+        let report = run(true).await.unwrap();
+        assert_eq!(2, report.count(ImportAction::Create));
+        assert_eq!(1, report.count(ImportAction::Fail));
+        assert_eq!(vec!["hugo".to_string()], report.new_tags);
+        assert!(blog_repository.all().await.unwrap().is_empty());
+        assert_eq!(1, tag_repository.all().await.unwrap().len());
+
+        let report = run(false).await.unwrap();
+        assert_eq!(2, report.count(ImportAction::Create));
+        let blogs = blog_repository.all().await.unwrap();
+        assert_eq!(2, blogs.len());
+        let first = blogs.iter().find(|blog| blog.title == "First").unwrap();
+        assert_eq!(vec!["rust", "hugo"], first.tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(Some("first".to_string()), first.slug);
+        assert_eq!(Utc.ymd(2023, 10, 1).and_hms(0, 0, 0), first.created_at);
+        assert_eq!(Some(first.id), redirect_repository.find("/2023/10/first.html").await.unwrap());
+
+        // 再実行しても増えず、変更したファイルだけ更新する
+        fs::write(dir.join("posts/second.md"), "+++\ntitle = \"Second (edited)\"\n+++\nsecond\n").unwrap();
+        let report = run(false).await.unwrap();
+        assert_eq!(1, report.count(ImportAction::Skip));
+        assert_eq!(1, report.count(ImportAction::Update));
+        let blogs = blog_repository.all().await.unwrap();
+        assert_eq!(2, blogs.len());
+        assert!(blogs.iter().any(|blog| blog.title == "Second (edited)"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
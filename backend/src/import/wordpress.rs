@@ -0,0 +1,296 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use quick_xml::{events::Event, Reader};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::{html::html_to_markdown, percent_decode, permalink_path, ImportEntry, Importer, ImportReport};
+use crate::render::slugify;
+use crate::repositories::{
+    blog::{BlogRepository, BlogStatus},
+    import::ImportRepository,
+    redirect::RedirectRepository,
+    tag::TagRepository,
+};
+
+const SOURCE_PREFIX: &str = "wordpress:";
+// 下書きなど公開日時が決まっていない投稿の post_date_gmt
+const ZERO_DATE: &str = "0000-00-00 00:00:00";
+
+// WordPress eXtended RSS (WXR) の item のうち、取り込みに使う要素
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WxrItem {
+    pub title: String,
+    pub link: String,
+    pub guid: String,
+    pub post_id: String,
+    pub post_name: String,
+    pub post_type: String,
+    pub status: String,
+    pub post_date: String,
+    pub post_date_gmt: String,
+    pub content: String,
+    // カテゴリとタグ (domain が category と post_tag の category 要素) の名前
+    pub categories: Vec<String>,
+}
+
+pub fn parse_wxr(xml: &str) -> anyhow::Result<Vec<WxrItem>> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut items = Vec::new();
+    let mut item: Option<WxrItem> = None;
+    let mut text = String::new();
+    // WordPress が最初から作る「未分類」はタグにしない
+    let mut uncategorized = false;
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(start) => {
+                text.clear();
+                match start.name() {
+                    b"item" => item = Some(WxrItem::default()),
+                    b"category" => {
+                        uncategorized = start
+                            .attributes()
+                            .flatten()
+                            .any(|attribute| attribute.key == b"nicename" && &*attribute.value == b"uncategorized");
+                    }
+                    _ => {}
+                }
+            }
+            // quick-xml は CDATA の中身もエスケープした状態で返す
+            Event::Text(value) | Event::CData(value) => text.push_str(&value.unescape_and_decode(&reader)?),
+            Event::End(end) => {
+                let name = end.name().to_vec();
+                if name == b"item" {
+                    items.extend(item.take());
+                    continue;
+                }
+                let item = match item.as_mut() {
+                    Some(item) => item,
+                    None => continue,
+                };
+                let value = std::mem::take(&mut text);
+                match name.as_slice() {
+                    b"title" => item.title = value,
+                    b"link" => item.link = value,
+                    b"guid" => item.guid = value,
+                    b"wp:post_id" => item.post_id = value,
+                    b"wp:post_name" => item.post_name = value,
+                    b"wp:post_type" => item.post_type = value,
+                    b"wp:status" => item.status = value,
+                    b"wp:post_date" => item.post_date = value,
+                    b"wp:post_date_gmt" => item.post_date_gmt = value,
+                    b"content:encoded" => item.content = value,
+                    b"category" if !uncategorized => {
+                        let value = value.trim().to_string();
+                        if !value.is_empty() && !item.categories.contains(&value) {
+                            item.categories.push(value);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(items)
+}
+
+// 投稿だけを取り込む。固定ページや添付ファイル、ゴミ箱の投稿は対象外
+fn is_importable(item: &WxrItem) -> bool {
+    item.post_type == "post" && !matches!(item.status.as_str(), "trash" | "auto-draft" | "inherit")
+}
+
+pub fn entry(item: &WxrItem) -> anyhow::Result<ImportEntry> {
+    let title = item.title.trim();
+    if title.is_empty() {
+        return Err(anyhow::anyhow!("title is missing"));
+    }
+    let key = match item.guid.trim() {
+        "" => item.post_id.trim(),
+        guid => guid,
+    };
+    let published_at = [&item.post_date_gmt, &item.post_date]
+        .iter()
+        .map(|date| date.trim())
+        .find(|date| !date.is_empty() && *date != ZERO_DATE)
+        .map(|date| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S"))
+        .transpose()?
+        .map(|date| DateTime::from_utc(date, Utc));
+
+    Ok(ImportEntry {
+        source: format!("{}{}", SOURCE_PREFIX, key),
+        checksum: format!("{:x}", Sha256::digest(&serde_json::to_vec(item)?)),
+        title: title.to_string(),
+        body: html_to_markdown(&item.content),
+        tags: item.categories.clone(),
+        // 予約投稿や非公開、レビュー待ちは下書きとして取り込む
+        status: match item.status.as_str() {
+            "publish" => BlogStatus::Published,
+            _ => BlogStatus::Draft,
+        },
+        slug: slugify(&percent_decode(&item.post_name)),
+        published_at,
+        redirects: permalink_path(&item.link).into_iter().collect(),
+    })
+}
+
+// エクスポートファイル全体を1回のバッチで取り込む。取り込み済みの投稿は記録から判断して飛ばすので、
+// 途中で失敗しても同じファイルで再実行すれば続きから取り込める
+pub async fn import_wxr<B, T, I, R>(
+    xml: &str,
+    blog_repository: &B,
+    tag_repository: &T,
+    import_repository: &I,
+    redirect_repository: &R,
+    dry_run: bool,
+) -> anyhow::Result<ImportReport>
+where
+    B: BlogRepository,
+    T: TagRepository,
+    I: ImportRepository,
+    R: RedirectRepository,
+{
+    let items = parse_wxr(xml)?;
+    let mut importer = Importer::new(
+        blog_repository,
+        tag_repository,
+        import_repository,
+        redirect_repository,
+        dry_run,
+    )
+    .await?;
+    for item in items.iter().filter(|item| is_importable(item)) {
+        match entry(item) {
+            Ok(entry) => importer.import(entry).await,
+            Err(e) => importer.fail(format!("{}{}", SOURCE_PREFIX, item.post_id), e),
+        }
+    }
+    Ok(importer.finish())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::import::ImportAction;
+    use crate::repositories::{
+        blog::test_utils::BlogRepositoryForMemory,
+        import::test_utils::ImportRepositoryForMemory,
+        redirect::test_utils::RedirectRepositoryForMemory,
+        tag::{test_utils::TagRepositoryForMemory, Tag},
+    };
+    use chrono::TimeZone;
+
+    fn wxr(items: &[&str]) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8" ?>
+<rss version="2.0"
+    xmlns:content="http://purl.org/rss/1.0/modules/content/"
+    xmlns:wp="http://wordpress.org/export/1.2/">
+<channel>
+    <title>Old blog</title>
+    <wp:wxr_version>1.2</wp:wxr_version>
+    {}
+</channel>
+</rss>"#,
+            items.join("\n")
+        )
+    }
+
+    const HELLO: &str = r#"<item>
+        <title>Hello &amp; welcome</title>
+        <link>https://old.example.com/2023/10/30/hello-world/</link>
+        <guid isPermaLink="false">https://old.example.com/?p=12</guid>
+        <content:encoded><![CDATA[<p>First <strong>post</strong></p>]]></content:encoded>
+        <wp:post_id>12</wp:post_id>
+        <wp:post_date><![CDATA[2023-10-30 18:00:00]]></wp:post_date>
+        <wp:post_date_gmt><![CDATA[2023-10-30 09:00:00]]></wp:post_date_gmt>
+        <wp:post_name><![CDATA[hello-world]]></wp:post_name>
+        <wp:status><![CDATA[publish]]></wp:status>
+        <wp:post_type><![CDATA[post]]></wp:post_type>
+        <category domain="category" nicename="uncategorized"><![CDATA[Uncategorized]]></category>
+        <category domain="category" nicename="rust"><![CDATA[Rust]]></category>
+        <category domain="post_tag" nicename="wordpress"><![CDATA[WordPress]]></category>
+        <wp:postmeta><wp:meta_key><![CDATA[_edit_last]]></wp:meta_key></wp:postmeta>
+    </item>"#;
+
+    const DRAFT: &str = r#"<item>
+        <title>下書き</title>
+        <link>https://old.example.com/?p=13</link>
+        <guid isPermaLink="false">https://old.example.com/?p=13</guid>
+        <content:encoded><![CDATA[draft]]></content:encoded>
+        <wp:post_id>13</wp:post_id>
+        <wp:post_date><![CDATA[2023-10-31 10:00:00]]></wp:post_date>
+        <wp:post_date_gmt><![CDATA[0000-00-00 00:00:00]]></wp:post_date_gmt>
+        <wp:post_name><![CDATA[%e4%b8%8b%e6%9b%b8%e3%81%8d-draft]]></wp:post_name>
+        <wp:status><![CDATA[draft]]></wp:status>
+        <wp:post_type><![CDATA[post]]></wp:post_type>
+        <category domain="category" nicename="rust"><![CDATA[Rust]]></category>
+    </item>"#;
+
+    const PAGE: &str = r#"<item>
+        <title>About</title>
+        <wp:post_id>2</wp:post_id>
+        <wp:status><![CDATA[publish]]></wp:status>
+        <wp:post_type><![CDATA[page]]></wp:post_type>
+    </item>"#;
+
+    #[test]
+    fn parse_wxr_items() {
+        let items = parse_wxr(&wxr(&[HELLO, DRAFT, PAGE])).unwrap();
+        assert_eq!(3, items.len());
+        assert_eq!(vec!["Rust", "WordPress"], items[0].categories);
+        assert!(!is_importable(&items[2]));
+
+        let hello = entry(&items[0]).unwrap();
+        assert_eq!("wordpress:https://old.example.com/?p=12", hello.source);
+        assert_eq!("Hello & welcome", hello.title);
+        assert_eq!("First **post**", hello.body);
+        assert_eq!(BlogStatus::Published, hello.status);
+        assert_eq!(Some("hello-world".to_string()), hello.slug);
+        assert_eq!(Some(Utc.ymd(2023, 10, 30).and_hms(9, 0, 0)), hello.published_at);
+        assert_eq!(vec!["/2023/10/30/hello-world".to_string()], hello.redirects);
+
+        let draft = entry(&items[1]).unwrap();
+        assert_eq!(BlogStatus::Draft, draft.status);
+        assert_eq!(Some("draft".to_string()), draft.slug);
+        assert_eq!(Some(Utc.ymd(2023, 10, 31).and_hms(10, 0, 0)), draft.published_at);
+        assert!(draft.redirects.is_empty());
+    }
+
+    #[tokio::test]
+    async fn resume_import_after_partial_run() {
+        let tag_repository = TagRepositoryForMemory::new();
+        let blog_repository = BlogRepositoryForMemory::new(vec![
+            Tag::new(1, "Rust".to_string()),
+            Tag::new(2, "WordPress".to_string()),
+        ]);
+        let import_repository = ImportRepositoryForMemory::new();
+        let redirect_repository = RedirectRepositoryForMemory::new();
+        let import = |xml: String| {
+            let (blogs, tags) = (blog_repository.clone(), tag_repository.clone());
+            let (imports, redirects) = (import_repository.clone(), redirect_repository.clone());
+            async move { import_wxr(&xml, &blogs, &tags, &imports, &redirects, false).await.unwrap() }
+        };
+
+        // 1件目だけ取り込んだところで止まった状態から、ファイル全体で再実行する
+        let report = import(wxr(&[HELLO])).await;
+        assert_eq!(1, report.count(ImportAction::Create));
+        let report = import(wxr(&[HELLO, DRAFT, PAGE])).await;
+        assert_eq!(1, report.count(ImportAction::Skip));
+        assert_eq!(1, report.count(ImportAction::Create));
+        assert_eq!(2, report.items.len());
+
+        let blogs = blog_repository.all().await.unwrap();
+        assert_eq!(2, blogs.len());
+        let hello = blogs.iter().find(|blog| blog.title == "Hello & welcome").unwrap();
+        assert_eq!(vec!["Rust", "WordPress"], hello.tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(Utc.ymd(2023, 10, 30).and_hms(9, 0, 0), hello.created_at);
+        assert_eq!(
+            Some(hello.id),
+            redirect_repository.find("/2023/10/30/hello-world").await.unwrap()
+        );
+    }
+}
@@ -1,22 +1,65 @@
+mod activitypub;
+mod api;
+mod atompub;
+mod auth;
+mod commands;
+mod config;
+mod export;
+mod feed;
+mod graphql;
 mod handlers;
+mod import;
+mod micropub;
+mod oembed;
+mod openapi;
+mod outbound;
+mod render;
 mod repositories;
+mod seo;
+mod sitemap;
+mod static_site;
+mod webmention;
+mod xmlrpc;
 
+use crate::activitypub::delivery::{FederatedBlogRepository, Federation};
+use crate::config::AppConfig;
 use crate::repositories::{
     blog::{BlogRepository, BlogRepositoryForDb},
+    follower::{FollowerRepository, FollowerRepositoryForDb},
+    idempotency::{IdempotencyRepository, IdempotencyRepositoryForDb},
+    redirect::{RedirectRepository, RedirectRepositoryForDb},
     tag::TagRepository,
+    user::{UserRepository, UserRepositoryForDb},
+    webmention::{WebmentionRepository, WebmentionRepositoryForDb},
 };
 use axum::{
     extract::Extension,
-    routing::{get, post, delete},
+    handler::Handler,
+    routing::{get, post},
     Router,
 };
-use handlers::{
-    blog::{all_blog, create_blog, delete_blog, find_blog, update_blog},
-    tag::{all_tag, create_tag, delete_tag}
+use handlers::activitypub::{actor, followers, inbox, outbox, webfinger};
+use handlers::atompub::{
+    categories_document, create_entry, delete_entry, find_entry, list_entries, replace_entry,
+    service_document,
 };
+use handlers::auth::{login, refresh, revoke};
+use handlers::docs::{self, api_docs, docs_asset, openapi_json, swagger_initializer};
+use handlers::feed::{atom_feed, json_feed, rss_feed, tag_atom_feed};
+use handlers::graphql::{graphiql, graphql_handler, GRAPHQL_PATH};
+use handlers::micropub::{micropub_post, micropub_query};
+use handlers::oembed::oembed_handler;
+use handlers::redirect::permalink_redirect;
+use handlers::sitemap::{robots_txt, sitemap_page, sitemap_xml};
+use sitemap::SitemapCache;
+use handlers::webmention::receive_webmention;
+use handlers::xmlrpc::xmlrpc_handler;
 use std::net::SocketAddr;
 use std::{env, sync::Arc};
-use hyper::header::CONTENT_TYPE;
+use hyper::header::{
+    HeaderName, AUTHORIZATION, CONTENT_TYPE, ETAG, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    LAST_MODIFIED, LINK, LOCATION,
+};
 use sqlx::PgPool;
 use dotenv::dotenv;
 use tower_http::cors::{
@@ -38,9 +81,25 @@ async fn main() {
     let pool = PgPool::connect(database_url)
         .await
         .expect(&format!("fail connect database, url is [{}]", database_url));
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some((command, args)) = args.split_first() {
+        if let Err(e) = commands::run(command, args, pool).await {
+            tracing::error!("{} failed: {}", command, e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let app = create_app(
         BlogRepositoryForDb::new(pool.clone()),
-        TagRepositoryForDb::new(pool.clone())
+        TagRepositoryForDb::new(pool.clone()),
+        IdempotencyRepositoryForDb::new(pool.clone()),
+        RedirectRepositoryForDb::new(pool.clone()),
+        WebmentionRepositoryForDb::new(pool.clone()),
+        FollowerRepositoryForDb::new(pool.clone()),
+        UserRepositoryForDb::new(pool.clone()),
+        AppConfig::from_env(),
     );
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     tracing::debug!("listening on {}", addr);
@@ -50,28 +109,161 @@ async fn main() {
         .unwrap();
 }
 
-fn create_app<Blog: BlogRepository, Tag: TagRepository>(
+#[allow(clippy::too_many_arguments)]
+fn create_app<
+    Blog: BlogRepository,
+    Tag: TagRepository,
+    Idempotency: IdempotencyRepository,
+    Redirect: RedirectRepository,
+    Webmention: WebmentionRepository,
+    Follower: FollowerRepository,
+    User: UserRepository,
+>(
     blog_repository: Blog,
     tag_repository: Tag,
+    idempotency_repository: Idempotency,
+    redirect_repository: Redirect,
+    webmention_repository: Webmention,
+    follower_repository: Follower,
+    user_repository: User,
+    config: AppConfig,
 ) -> Router {
-    Router::new()
+    // どの API から記事を変更してもフォロワーに配られるよう、リポジトリごと包む
+    let follower_repository = Arc::new(follower_repository);
+    let federation = Federation::spawn(
+        follower_repository.clone(),
+        config.site.clone(),
+        config.activitypub.clone(),
+        config.outbound.clone(),
+    );
+    let blog_repository = FederatedBlogRepository::new(blog_repository, federation.clone(), config.site.clone());
+    router(
+        blog_repository,
+        tag_repository,
+        idempotency_repository,
+        redirect_repository,
+        webmention_repository,
+        follower_repository,
+        user_repository,
+        federation,
+        config,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn router<
+    Blog: BlogRepository,
+    Tag: TagRepository,
+    Idempotency: IdempotencyRepository,
+    Redirect: RedirectRepository,
+    Webmention: WebmentionRepository,
+    Follower: FollowerRepository,
+    User: UserRepository,
+>(
+    blog_repository: Blog,
+    tag_repository: Tag,
+    idempotency_repository: Idempotency,
+    redirect_repository: Redirect,
+    webmention_repository: Webmention,
+    follower_repository: Arc<Follower>,
+    user_repository: User,
+    federation: Federation,
+    config: AppConfig,
+) -> Router {
+    let schema = graphql::schema(blog_repository.clone(), tag_repository.clone(), &config.graphql);
+    let blog_repository = Arc::new(blog_repository);
+    let webmention_repository = Arc::new(webmention_repository);
+    let webmentions = webmention::spawn(
+        blog_repository.clone(),
+        webmention_repository.clone(),
+        config.site.clone(),
+        config.outbound.clone(),
+    );
+    let routes = Router::new()
         .route("/", get(root))
-        .route("/blogs", post(create_blog::<Blog>).get(all_blog::<Blog>))
+        .route("/openapi.json", get(openapi_json))
+        .route("/docs", get(api_docs))
+        .route(docs::SWAGGER_INITIALIZER_PATH, get(swagger_initializer))
+        .route(docs::DOCS_ASSETS_PATH, get(docs_asset))
+        .route("/feed.rss", get(rss_feed::<Blog>))
+        .route("/feed.atom", get(atom_feed::<Blog>))
+        .route(feed::json::FEED_PATH, get(json_feed::<Blog>))
+        .route("/tags/:id/feed.atom", get(tag_atom_feed::<Blog, Tag>))
+        .route("/sitemap.xml", get(sitemap_xml::<Blog, Tag>))
+        .route("/sitemaps/:page", get(sitemap_page::<Blog, Tag>))
+        .route("/robots.txt", get(robots_txt))
+        .route(oembed::OEMBED_PATH, get(oembed_handler::<Blog>))
+        .route(
+            micropub::MICROPUB_PATH,
+            get(micropub_query::<Blog>).post(micropub_post::<Blog, Tag>),
+        )
+        .route(xmlrpc::XMLRPC_PATH, post(xmlrpc_handler::<Blog, Tag, User>))
+        .route(atompub::SERVICE_PATH, get(service_document))
+        .route(
+            atompub::COLLECTION_PATH,
+            get(list_entries::<Blog>).post(create_entry::<Blog, Tag>),
+        )
         .route(
-            "/blogs/:id",
-            get(find_blog::<Blog>)
-                .delete(delete_blog::<Blog>)
-                .patch(update_blog::<Blog>),
+            "/atompub/entries/:id",
+            get(find_entry::<Blog>)
+                .put(replace_entry::<Blog, Tag>)
+                .delete(delete_entry::<Blog>),
         )
-        .route("/tags", post(create_tag::<Tag>).get(all_tag::<Tag>))
-        .route("/tag/:id", delete(delete_tag::<Tag>))
-        .layer(Extension(Arc::new(blog_repository)))
+        .route(atompub::CATEGORIES_PATH, get(categories_document::<Tag>))
+        .route(
+            webmention::WEBMENTION_PATH,
+            post(receive_webmention::<Blog, Webmention>),
+        )
+        .route(activitypub::WEBFINGER_PATH, get(webfinger))
+        .route(activitypub::ACTOR_PATH, get(actor))
+        .route(activitypub::INBOX_PATH, post(inbox::<Follower>))
+        .route(activitypub::OUTBOX_PATH, get(outbox::<Blog>))
+        .route(activitypub::FOLLOWERS_PATH, get(followers::<Follower>))
+        .route(auth::LOGIN_PATH, post(login::<User>))
+        .route(auth::REFRESH_PATH, post(refresh::<User>))
+        .route(auth::REVOKE_PATH, post(revoke::<User>))
+        .route(GRAPHQL_PATH, get(graphiql).post(graphql_handler::<Blog, Tag>))
+        .nest(api::V1_PREFIX, api::v1::router::<Blog, Tag, Idempotency, Webmention>())
+        .merge(api::legacy::<Blog, Tag, Idempotency, Webmention>(&config.api.legacy_sunset))
+        .fallback(permalink_redirect::<Redirect>.into_service());
+    // /blogs/bulk などの固定のパスを先に試し、無ければ残りのルートに回す (api::v1::static_router)
+    Router::new()
+        .nest(api::V1_PREFIX, api::v1::static_router::<Blog>())
+        .merge(api::legacy_static::<Blog>(&config.api.legacy_sunset))
+        .fallback(routes)
+        .layer(Extension(blog_repository))
         .layer(Extension(Arc::new(tag_repository)))
+        .layer(Extension(Arc::new(idempotency_repository)))
+        .layer(Extension(Arc::new(redirect_repository)))
+        .layer(Extension(webmention_repository))
+        .layer(Extension(webmentions))
+        .layer(Extension(SitemapCache::default()))
+        .layer(Extension(follower_repository))
+        .layer(Extension(federation))
+        .layer(Extension(Arc::new(user_repository)))
+        .layer(Extension(schema))
+        .layer(Extension(Arc::new(config)))
         .layer(
             CorsLayer::new()
                 .allow_origin(Origin::exact("http://localhost:3001".parse().unwrap()))
                 .allow_methods(Any)
-                .allow_headers(vec![CONTENT_TYPE])
+                .allow_headers(vec![
+                    AUTHORIZATION,
+                    CONTENT_TYPE,
+                    IF_MATCH,
+                    IF_NONE_MATCH,
+                    IF_MODIFIED_SINCE,
+                    HeaderName::from_static("idempotency-key"),
+                    HeaderName::from_static("slug"),
+                ])
+                .expose_headers(vec![
+                    ETAG,
+                    LAST_MODIFIED,
+                    LINK,
+                    LOCATION,
+                    HeaderName::from_static("deprecation"),
+                    HeaderName::from_static("sunset"),
+                ])
         )
 }
 
@@ -85,30 +277,51 @@ mod test {
     use crate::repositories::tag::test_utils::TagRepositoryForMemory;
     use crate::repositories::tag::Tag;
     use crate::repositories::blog::test_utils::BlogRepositoryForMemory;
-    use crate::repositories::blog::BlogEntity;
+    use crate::repositories::blog::{BlogEntity, BlogSeo, BlogStatus, BulkOutcome, CreateBlog};
+    use crate::repositories::idempotency::test_utils::IdempotencyRepositoryForMemory;
+    use crate::repositories::redirect::test_utils::RedirectRepositoryForMemory;
+    use crate::repositories::webmention::test_utils::WebmentionRepositoryForMemory;
+    use crate::repositories::follower::test_utils::FollowerRepositoryForMemory;
+    use crate::repositories::user::{test_utils::UserRepositoryForMemory, UserEntity, UserRepository};
     use axum::response::Response;
     use axum::{
         body::Body,
-        http::{header, Method, Request},
+        http::{header, Method, Request, StatusCode},
     };
     use tower::ServiceExt;
 
+    // 書き込みに必要なアクセストークン。AppConfig::default() の鍵で署名する
+    fn bearer() -> String {
+        bearer_for(1, "admin")
+    }
+
+    fn bearer_for(id: i32, username: &str) -> String {
+        let user = UserEntity {
+            id,
+            username: username.to_string(),
+            password_hash: String::new(),
+        };
+        format!("Bearer {}", auth::issue_access_token(&user, &AppConfig::default().auth).unwrap())
+    }
+
     fn build_blog_req_with_json(path: &str, method: Method, json_body: String) -> Request<Body> {
         Request::builder()
             .uri(path)
             .method(method)
             .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .header(header::AUTHORIZATION, bearer())
             .body(Body::from(json_body))
             .unwrap()
     }
 
-    // fn build_blog_req_with_empty(method: Method, path: &str) -> Request<Body> {
-    //     Request::builder()
-    //         .uri(path)
-    //         .method(method)
-    //         .body(Body::empty())
-    //         .unwrap()
-    // }
+    fn build_blog_req_with_empty(method: Method, path: &str) -> Request<Body> {
+        Request::builder()
+            .uri(path)
+            .method(method)
+            .header(header::AUTHORIZATION, bearer())
+            .body(Body::empty())
+            .unwrap()
+    }
 
     async fn res_to_blog(res: Response) -> BlogEntity {
         let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
@@ -118,6 +331,17 @@ mod test {
         blog
     }
 
+    // 旧パスには deprecation と sunset が付き、v1 には付かない
+    fn assert_deprecation(prefix: &str, res: &Response) {
+        if prefix.is_empty() {
+            assert_eq!("true", res.headers()["deprecation"]);
+            assert_eq!(AppConfig::default().api.legacy_sunset, res.headers()["sunset"]);
+        } else {
+            assert!(!res.headers().contains_key("deprecation"));
+            assert!(!res.headers().contains_key("sunset"));
+        }
+    }
+
     fn tag_fixture() -> (Vec<Tag>, Vec<i32>) {
         let id = 999;
         (
@@ -131,11 +355,20 @@ mod test {
 
     #[tokio::test]
     async fn should_created_blog() {
+        check_created_blog("").await;
+    }
+
+    #[tokio::test]
+    async fn should_created_blog_v1() {
+        check_created_blog(api::V1_PREFIX).await;
+    }
+
+    async fn check_created_blog(prefix: &str) {
         let (tags, _tag_ids) = tag_fixture();
         let expected = BlogEntity::new(1, "blog title".to_string(), "blog body".to_string(), tags.clone());
 
         let req = build_blog_req_with_json(
-            "/blogs",
+            &format!("{}/blogs", prefix),
              Method::POST, 
             r#"{
             "title": "blog title",
@@ -147,15 +380,1739 @@ mod test {
         let res = create_app(
             BlogRepositoryForMemory::new(tags),
             TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
         )
         .oneshot(req)
         .await
         .unwrap();
+        assert_deprecation(prefix, &res);
         let blog = res_to_blog(res).await;
+        let expected = BlogEntity {
+            created_at: blog.created_at,
+            updated_at: blog.updated_at,
+            ..expected
+        };
         assert_eq!(expected, blog);
     }
-}
 
+    #[tokio::test]
+    async fn should_return_not_modified_for_matching_etag() {
+        check_return_not_modified_for_matching_etag("").await;
+    }
 
+    #[tokio::test]
+    async fn should_return_not_modified_for_matching_etag_v1() {
+        check_return_not_modified_for_matching_etag(api::V1_PREFIX).await;
+    }
 
+    async fn check_return_not_modified_for_matching_etag(prefix: &str) {
+        let (tags, tag_ids) = tag_fixture();
+        let blog_repository = BlogRepositoryForMemory::new(tags);
+        blog_repository
+            .create(CreateBlog {
+                title: "blog title".to_string(),
+                body: "blog body".to_string(),
+                tags: tag_ids,
+                status: BlogStatus::Published,
+                seo: BlogSeo::default(),
+                slug: None,
+                published_at: None,
+            })
+            .await
+            .unwrap();
+        let app = create_app(
+            blog_repository,
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
 
+        let req = build_blog_req_with_empty(Method::GET, &format!("{}/blogs/1", prefix));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("no-cache", res.headers()[header::CACHE_CONTROL]);
+        assert!(res.headers().contains_key(header::LAST_MODIFIED));
+        let etag = res.headers()[header::ETAG].clone();
+
+        let req = Request::builder()
+            .uri(&format!("{}/blogs/1", prefix))
+            .header(header::IF_NONE_MATCH, etag.clone())
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_MODIFIED, res.status());
+        assert_deprecation(prefix, &res);
+        assert_eq!(etag, res.headers()[header::ETAG]);
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert!(bytes.is_empty());
+
+        let req = Request::builder()
+            .uri(&format!("{}/blogs/1", prefix))
+            .header(header::IF_NONE_MATCH, "\"stale\"")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_return_not_modified_since_last_modified() {
+        check_return_not_modified_since_last_modified("").await;
+    }
+
+    #[tokio::test]
+    async fn should_return_not_modified_since_last_modified_v1() {
+        check_return_not_modified_since_last_modified(api::V1_PREFIX).await;
+    }
+
+    async fn check_return_not_modified_since_last_modified(prefix: &str) {
+        let tag_repository = TagRepositoryForMemory::new();
+        tag_repository.create("test tag".to_string()).await.unwrap();
+        let app = create_app(
+            BlogRepositoryForMemory::new(vec![]),
+            tag_repository,
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_empty(Method::GET, &format!("{}/tags", prefix)))
+            .await
+            .unwrap();
+        let last_modified = res.headers()[header::LAST_MODIFIED].clone();
+
+        let req = Request::builder()
+            .uri(&format!("{}/tags", prefix))
+            .header(header::IF_MODIFIED_SINCE, last_modified)
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_MODIFIED, res.status());
+        assert_deprecation(prefix, &res);
+    }
+
+    #[tokio::test]
+    async fn should_advance_last_modified_when_older_blog_is_deleted() {
+        use chrono::{Duration, Utc};
+
+        let blog_repository = BlogRepositoryForMemory::new(vec![]);
+        for title in ["older", "newer"] {
+            blog_repository
+                .create(CreateBlog {
+                    title: title.to_string(),
+                    body: "blog body".to_string(),
+                    tags: vec![],
+                    status: BlogStatus::Published,
+                    seo: BlogSeo::default(),
+                    slug: None,
+                    published_at: None,
+                })
+                .await
+                .unwrap();
+        }
+        blog_repository.set_last_modified(Utc::now() - Duration::hours(1));
+        let app = create_app(
+            blog_repository,
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_empty(Method::GET, "/api/v1/blogs"))
+            .await
+            .unwrap();
+        let last_modified = res.headers()[header::LAST_MODIFIED].clone();
+
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_empty(Method::DELETE, "/api/v1/blogs/1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+
+        let req = Request::builder()
+            .uri("/api/v1/blogs")
+            .header(header::IF_MODIFIED_SINCE, last_modified)
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_replay_blog_creation_with_same_idempotency_key() {
+        check_replay_blog_creation_with_same_idempotency_key("").await;
+    }
+
+    #[tokio::test]
+    async fn should_replay_blog_creation_with_same_idempotency_key_v1() {
+        check_replay_blog_creation_with_same_idempotency_key(api::V1_PREFIX).await;
+    }
+
+    async fn check_replay_blog_creation_with_same_idempotency_key(prefix: &str) {
+        let (tags, _tag_ids) = tag_fixture();
+        let blog_repository = BlogRepositoryForMemory::new(tags);
+        let app = create_app(
+            blog_repository.clone(),
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+        let build_req_as = |title: &str, bearer: String| {
+            Request::builder()
+                .uri(&format!("{}/blogs", prefix))
+                .method(Method::POST)
+                .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .header("Idempotency-Key", "retry-1")
+                .header(header::AUTHORIZATION, bearer)
+                .body(Body::from(format!(
+                    r#"{{"title": "{}", "body": "blog body", "tags": [999]}}"#,
+                    title
+                )))
+                .unwrap()
+        };
+        let build_req = |title: &str| build_req_as(title, bearer());
+
+        let res = app.clone().oneshot(build_req("blog title")).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let created = res_to_blog(res).await;
+
+        let res = app.clone().oneshot(build_req("blog title")).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert_eq!("true", res.headers()["idempotent-replayed"]);
+        assert_deprecation(prefix, &res);
+        let replayed = res_to_blog(res).await;
+        assert_eq!(created, replayed);
+        assert_eq!(1, blog_repository.all().await.unwrap().len());
+
+        let res = app.clone().oneshot(build_req("another title")).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+
+        // 別のユーザーが同じキーを使っても、前の応答は返さずに新しく作る
+        let res = app.oneshot(build_req_as("another title", bearer_for(2, "editor"))).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert!(res.headers().get("idempotent-replayed").is_none());
+        assert_eq!("another title", res_to_blog(res).await.title);
+        assert_eq!(2, blog_repository.all().await.unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn should_apply_bulk_operations() {
+        check_apply_bulk_operations("").await;
+    }
+
+    #[tokio::test]
+    async fn should_apply_bulk_operations_v1() {
+        check_apply_bulk_operations(api::V1_PREFIX).await;
+    }
+
+    async fn check_apply_bulk_operations(prefix: &str) {
+        let (tags, tag_ids) = tag_fixture();
+        let blog_repository = BlogRepositoryForMemory::new(tags);
+        for title in ["blog 1", "blog 2"] {
+            blog_repository
+                .create(CreateBlog {
+                    title: title.to_string(),
+                    body: "blog body".to_string(),
+                    tags: tag_ids.clone(),
+                    status: BlogStatus::Draft,
+                    seo: BlogSeo::default(),
+                    slug: None,
+                    published_at: None,
+                })
+                .await
+                .unwrap();
+        }
+        let app = create_app(
+            blog_repository.clone(),
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+        let operations = r#"[
+            {"op": "set_status", "id": 1, "status": "published"},
+            {"op": "remove_tags", "id": 1, "tags": [999]},
+            {"op": "delete", "id": 42},
+            {"op": "delete", "id": 2}
+        ]"#;
+
+        // all_or_nothing では1件の失敗で全てロールバックされる
+        let req = build_blog_req_with_json(
+            &format!("{}/blogs/bulk", prefix),
+            Method::POST,
+            format!(r#"{{"mode": "all_or_nothing", "operations": {}}}"#, operations),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+        assert_deprecation(prefix, &res);
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let outcome: BulkOutcome = serde_json::from_slice(&bytes).unwrap();
+        assert!(!outcome.committed);
+        assert_eq!(BlogStatus::Draft, blog_repository.find(1).await.unwrap().status);
+        assert_eq!(2, blog_repository.all().await.unwrap().len());
+
+        // best_effort では失敗した操作だけが飛ばされる
+        let req = build_blog_req_with_json(
+            &format!("{}/blogs/bulk", prefix),
+            Method::POST,
+            format!(r#"{{"mode": "best_effort", "operations": {}}}"#, operations),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let outcome: BulkOutcome = serde_json::from_slice(&bytes).unwrap();
+        assert!(outcome.committed);
+        assert!(outcome.results[2].error.is_some());
+        let blog = blog_repository.find(1).await.unwrap();
+        assert_eq!(BlogStatus::Published, blog.status);
+        assert!(blog.tags.is_empty());
+        assert!(blog_repository.find(2).await.is_err());
+
+        // 一括操作は /blogs/bulk だけで受け付ける
+        let req = build_blog_req_with_json(
+            &format!("{}/blogs/1", prefix),
+            Method::POST,
+            format!(r#"{{"mode": "best_effort", "operations": {}}}"#, operations),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_merge_patch_and_replace_blog() {
+        check_merge_patch_and_replace_blog("").await;
+    }
+
+    #[tokio::test]
+    async fn should_merge_patch_and_replace_blog_v1() {
+        check_merge_patch_and_replace_blog(api::V1_PREFIX).await;
+    }
+
+    async fn check_merge_patch_and_replace_blog(prefix: &str) {
+        let (tags, tag_ids) = tag_fixture();
+        let blog_repository = BlogRepositoryForMemory::new(tags.clone());
+        blog_repository
+            .create(CreateBlog {
+                title: "blog title".to_string(),
+                body: "blog body".to_string(),
+                tags: tag_ids.clone(),
+                status: BlogStatus::Published,
+                seo: BlogSeo::default(),
+                slug: None,
+                published_at: None,
+            })
+            .await
+            .unwrap();
+        let app = create_app(
+            blog_repository,
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+        let build_patch_req = |json_body: &str| {
+            Request::builder()
+                .uri(&format!("{}/blogs/1", prefix))
+                .method(Method::PATCH)
+                .header(header::CONTENT_TYPE, "application/merge-patch+json")
+                .header(header::AUTHORIZATION, bearer())
+                .body(Body::from(json_body.to_string()))
+                .unwrap()
+        };
+
+        // 無いフィールドは変更せず、null はクリアする
+        let res = app
+            .clone()
+            .oneshot(build_patch_req(r#"{"body": null, "tags": null}"#))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_deprecation(prefix, &res);
+        let blog = res_to_blog(res).await;
+        assert_eq!("blog title", blog.title);
+        assert_eq!("", blog.body);
+        assert!(blog.tags.is_empty());
+
+        let res = app
+            .clone()
+            .oneshot(build_patch_req(r#"{"title": null}"#))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        // ボディを読む前に Content-Type で弾く
+        let req = Request::builder()
+            .uri(&format!("{}/blogs/1", prefix))
+            .method(Method::PATCH)
+            .header(header::CONTENT_TYPE, mime::TEXT_PLAIN.as_ref())
+            .header(header::AUTHORIZATION, bearer())
+            .body(Body::from("not json"))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, res.status());
+        assert_eq!("application/merge-patch+json", res.headers()["accept-patch"]);
+        assert_deprecation(prefix, &res);
+
+        let req = build_blog_req_with_json(
+            &format!("{}/blogs/1", prefix),
+            Method::PUT,
+            r#"{"title": "replaced", "body": "replaced body", "tags": [999], "status": "draft"}"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let blog = res_to_blog(res).await;
+        assert_eq!("replaced", blog.title);
+        assert_eq!("replaced body", blog.body);
+        assert_eq!(tags, blog.tags);
+        assert_eq!(BlogStatus::Draft, blog.status);
+    }
+
+    #[tokio::test]
+    async fn should_serve_vendored_swagger_ui() {
+        let app = |assets_dir: std::path::PathBuf| {
+            let mut config = AppConfig::default();
+            config.docs.assets_dir = assets_dir;
+            create_app(
+                BlogRepositoryForMemory::new(vec![]),
+                TagRepositoryForMemory::new(),
+                IdempotencyRepositoryForMemory::new(),
+                RedirectRepositoryForMemory::new(),
+                WebmentionRepositoryForMemory::new(),
+                FollowerRepositoryForMemory::new(),
+                UserRepositoryForMemory::new(),
+                config,
+            )
+        };
+        let get = |app: Router, path: &str| {
+            let req = Request::builder().uri(path).body(Body::empty()).unwrap();
+            async move { app.oneshot(req).await.unwrap() }
+        };
+
+        // ファイルが無ければ JavaScript を使わないリファレンスを返す
+        let missing = std::env::temp_dir().join(format!("swagger-ui-missing-{}", std::process::id()));
+        let res = get(app(missing), "/docs").await;
+        assert_eq!(StatusCode::OK, res.status());
+        assert!(!res.headers()[header::CONTENT_SECURITY_POLICY].to_str().unwrap().contains("script-src"));
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert!(!String::from_utf8(bytes.to_vec()).unwrap().contains("<script"));
+
+        let assets_dir = std::env::temp_dir().join(format!("swagger-ui-{}", std::process::id()));
+        std::fs::create_dir_all(&assets_dir).unwrap();
+        std::fs::write(assets_dir.join("swagger-ui-bundle.js"), "var SwaggerUIBundle;").unwrap();
+        std::fs::write(assets_dir.join("swagger-ui.css"), "body {}").unwrap();
+        std::fs::write(assets_dir.join("secret.txt"), "secret").unwrap();
+        let app = app(assets_dir.clone());
+
+        let res = get(app.clone(), "/docs").await;
+        assert_eq!(StatusCode::OK, res.status());
+        let policy = res.headers()[header::CONTENT_SECURITY_POLICY].to_str().unwrap().to_string();
+        assert!(policy.contains("script-src 'self'"));
+        assert!(!policy.contains("https:"));
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let page = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(page.contains(r#"<script src="/docs/assets/swagger-ui-bundle.js"></script>"#));
+        assert!(!page.contains("https://"));
+
+        let res = get(app.clone(), "/docs/swagger-initializer.js").await;
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert!(String::from_utf8(bytes.to_vec()).unwrap().contains(r#"url: "/openapi.json""#));
+
+        let res = get(app.clone(), "/docs/assets/swagger-ui-bundle.js").await;
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(
+            "application/javascript; charset=utf-8",
+            res.headers()[header::CONTENT_TYPE]
+        );
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&b"var SwaggerUIBundle;"[..], &bytes[..]);
+
+        // 一覧に無いファイルは置いてあっても返さない
+        let res = get(app, "/docs/assets/secret.txt").await;
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        std::fs::remove_dir_all(assets_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_serve_legacy_paths_with_deprecation_headers() {
+        let tag_repository = TagRepositoryForMemory::new();
+        tag_repository.create("test tag".to_string()).await.unwrap();
+        let app = create_app(
+            BlogRepositoryForMemory::new(vec![]),
+            tag_repository,
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_empty(Method::GET, "/tags"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("true", res.headers()["deprecation"]);
+        assert_eq!(AppConfig::default().api.legacy_sunset, res.headers()["sunset"]);
+
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_empty(Method::DELETE, "/tag/1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        assert!(res.headers().contains_key("deprecation"));
+
+        let res = app
+            .oneshot(build_blog_req_with_empty(Method::GET, "/api/v1/tags"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert!(!res.headers().contains_key("deprecation"));
+    }
+
+    #[tokio::test]
+    async fn should_return_sparse_fieldsets() {
+        let (tags, tag_ids) = tag_fixture();
+        let blog_repository = BlogRepositoryForMemory::new(tags);
+        blog_repository
+            .create(CreateBlog {
+                title: "blog title".to_string(),
+                body: "blog body".to_string(),
+                tags: tag_ids,
+                status: BlogStatus::Published,
+                seo: BlogSeo::default(),
+                slug: None,
+                published_at: None,
+            })
+            .await
+            .unwrap();
+        let app = create_app(
+            blog_repository,
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+        let get_json = |path: &str| {
+            let app = app.clone();
+            let req = build_blog_req_with_empty(Method::GET, path);
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                let status = res.status();
+                let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap_or_default())
+            }
+        };
+
+        let (_, blogs) = get_json("/api/v1/blogs").await;
+        assert!(blogs[0].get("body").is_none());
+        assert_eq!("blog title", blogs[0]["title"]);
+
+        let (_, blogs) = get_json("/api/v1/blogs?include=body").await;
+        assert_eq!("blog body", blogs[0]["body"]);
+
+        let (_, blog) = get_json("/api/v1/blogs/1?fields=id,title").await;
+        assert_eq!(serde_json::json!({"id": 1, "title": "blog title"}), blog);
+
+        let (status, _) = get_json("/api/v1/blogs?fields=id,unknown").await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+
+        // 旧パスは今まで通り body を返す
+        let (_, blogs) = get_json("/blogs").await;
+        assert_eq!("blog body", blogs[0]["body"]);
+    }
+
+    #[tokio::test]
+    async fn should_serve_graphql() {
+        let (tags, tag_ids) = tag_fixture();
+        let blog_repository = BlogRepositoryForMemory::new(tags);
+        blog_repository
+            .create(CreateBlog {
+                title: "blog title".to_string(),
+                body: "blog body".to_string(),
+                tags: tag_ids,
+                status: BlogStatus::Published,
+                seo: BlogSeo::default(),
+                slug: None,
+                published_at: None,
+            })
+            .await
+            .unwrap();
+        let app = create_app(
+            blog_repository,
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+        let query = serde_json::json!({ "query": "{ blog(id: 1) { title tags { id } } }" });
+        let req = build_blog_req_with_json(GRAPHQL_PATH, Method::POST, query.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            serde_json::json!({ "blog": { "title": "blog title", "tags": [{ "id": 999 }] } }),
+            body["data"]
+        );
+
+        let mut config = AppConfig::default();
+        config.graphql.graphiql = false;
+        let app = create_app(
+            BlogRepositoryForMemory::new(vec![]),
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let res = app.oneshot(build_blog_req_with_empty(Method::GET, GRAPHQL_PATH)).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_negotiate_blog_representation() {
+        let (tags, tag_ids) = tag_fixture();
+        let blog_repository = BlogRepositoryForMemory::new(tags);
+        blog_repository
+            .create(CreateBlog {
+                title: "blog title".to_string(),
+                body: "# blog body".to_string(),
+                tags: tag_ids,
+                status: BlogStatus::Published,
+                seo: BlogSeo::default(),
+                slug: None,
+                published_at: None,
+            })
+            .await
+            .unwrap();
+        let app = create_app(
+            blog_repository,
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+        let get = |path: &str, accept: &str| {
+            let req = Request::builder()
+                .uri(path)
+                .method(Method::GET)
+                .header(header::ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+
+        let res = get("/api/v1/blogs/1", "text/markdown").await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("text/markdown; charset=utf-8", res.headers()[header::CONTENT_TYPE]);
+        assert_eq!("accept", res.headers()[header::VARY]);
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let markdown = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(markdown.starts_with("---\ntitle: \"blog title\"\ntags: [\"test tag\"]\n"));
+        assert!(markdown.ends_with("---\n\n# blog body\n"));
+
+        let res = get("/api/v1/blogs/1.html", "application/json").await.unwrap();
+        assert_eq!("text/html; charset=utf-8", res.headers()[header::CONTENT_TYPE]);
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert!(String::from_utf8(bytes.to_vec()).unwrap().contains("<h1>blog body</h1>"));
+
+        let res = get("/api/v1/blogs/1", "application/json").await.unwrap();
+        let blog = res_to_blog(res).await;
+        assert_eq!("blog title", blog.title);
+
+        let res = get("/api/v1/blogs/1", "application/pdf").await.unwrap();
+        assert_eq!(StatusCode::NOT_ACCEPTABLE, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_serve_rss_feed_of_published_blogs() {
+        let (tags, tag_ids) = tag_fixture();
+        let blog_repository = BlogRepositoryForMemory::new(tags);
+        for (title, status) in [
+            ("first", BlogStatus::Published),
+            ("draft", BlogStatus::Draft),
+            ("second", BlogStatus::Published),
+            ("third", BlogStatus::Published),
+        ] {
+            blog_repository
+                .create(CreateBlog {
+                    title: title.to_string(),
+                    body: "blog body".to_string(),
+                    tags: tag_ids.clone(),
+                    status,
+                    seo: BlogSeo::default(),
+                    slug: None,
+                    published_at: None,
+                })
+                .await
+                .unwrap();
+        }
+        let mut config = AppConfig::default();
+        config.site.feed_limit = 2;
+        let app = create_app(
+            blog_repository,
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let res = app.oneshot(build_blog_req_with_empty(Method::GET, "/feed.rss")).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("application/rss+xml; charset=utf-8", res.headers()[header::CONTENT_TYPE]);
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let rss = String::from_utf8(bytes.to_vec()).unwrap();
+        assert_eq!(2, rss.matches("<item>").count());
+        assert!(rss.find("<title>third</title>").unwrap() < rss.find("<title>second</title>").unwrap());
+        assert!(!rss.contains("<title>draft</title>"));
+        assert!(rss.contains("<category>test tag</category>"));
+    }
+
+    #[tokio::test]
+    async fn should_advance_feed_last_modified_when_blog_is_deleted() {
+        use chrono::{Duration, Utc};
+
+        let blog_repository = BlogRepositoryForMemory::new(vec![]);
+        for title in ["older", "newer"] {
+            blog_repository
+                .create(CreateBlog {
+                    title: title.to_string(),
+                    body: "blog body".to_string(),
+                    tags: vec![],
+                    status: BlogStatus::Published,
+                    seo: BlogSeo::default(),
+                    slug: None,
+                    published_at: None,
+                })
+                .await
+                .unwrap();
+        }
+        blog_repository.set_last_modified(Utc::now() - Duration::hours(1));
+        let app = create_app(
+            blog_repository,
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+
+        // 古い方の記事を消しても、残った記事の updated_at の最大値は変わらない
+        let paths = ["/feed.rss", "/feed.atom", "/feed.json", "/sitemap.xml"];
+        let mut last_modified = Vec::new();
+        for path in paths {
+            let res = app.clone().oneshot(build_blog_req_with_empty(Method::GET, path)).await.unwrap();
+            last_modified.push(res.headers()[header::LAST_MODIFIED].clone());
+        }
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_empty(Method::DELETE, "/api/v1/blogs/1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+
+        for (path, since) in paths.into_iter().zip(last_modified) {
+            let req = Request::builder()
+                .uri(path)
+                .header(header::IF_MODIFIED_SINCE, since)
+                .body(Body::empty())
+                .unwrap();
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::OK, res.status(), "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_serve_atom_feed_per_tag() {
+        let rust = Tag { id: 1, name: "rust".to_string() };
+        let other = Tag { id: 2, name: "other".to_string() };
+        let blog_repository = BlogRepositoryForMemory::new(vec![rust.clone(), other.clone()]);
+        for (title, tag) in [("rust post", rust.id), ("other post", other.id)] {
+            blog_repository
+                .create(CreateBlog {
+                    title: title.to_string(),
+                    body: "blog body".to_string(),
+                    tags: vec![tag],
+                    status: BlogStatus::Published,
+                    seo: BlogSeo::default(),
+                    slug: None,
+                    published_at: None,
+                })
+                .await
+                .unwrap();
+        }
+        let tag_repository = TagRepositoryForMemory::new();
+        tag_repository.create(rust.name.clone()).await.unwrap();
+        tag_repository.create(other.name.clone()).await.unwrap();
+        let app = create_app(
+            blog_repository,
+            tag_repository,
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+        let get = |path: &str| app.clone().oneshot(build_blog_req_with_empty(Method::GET, path));
+
+        let res = get("/tags/1/feed.atom").await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("application/atom+xml; charset=utf-8", res.headers()[header::CONTENT_TYPE]);
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let feed = feed::atom::test_utils::assert_valid_atom(&String::from_utf8(bytes.to_vec()).unwrap());
+        let entries = feed.all("entry");
+        assert_eq!(1, entries.len());
+        assert_eq!("rust post", entries[0].one("title").text);
+
+        let res = get("/feed.atom").await.unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let feed = feed::atom::test_utils::assert_valid_atom(&String::from_utf8(bytes.to_vec()).unwrap());
+        assert_eq!(2, feed.all("entry").len());
+
+        let res = get("/tags/99/feed.atom").await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_paginate_json_feed() {
+        let blog_repository = BlogRepositoryForMemory::new(vec![]);
+        for title in ["first", "second", "third"] {
+            blog_repository
+                .create(CreateBlog {
+                    title: title.to_string(),
+                    body: "blog body".to_string(),
+                    tags: vec![],
+                    status: BlogStatus::Published,
+                    seo: BlogSeo::default(),
+                    slug: None,
+                    published_at: None,
+                })
+                .await
+                .unwrap();
+        }
+        let mut config = AppConfig::default();
+        config.site.feed_limit = 2;
+        let app = create_app(
+            blog_repository,
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let get_feed = |path: &str| {
+            let req = build_blog_req_with_empty(Method::GET, path);
+            let app = app.clone();
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                assert_eq!(StatusCode::OK, res.status());
+                assert_eq!("application/feed+json; charset=utf-8", res.headers()[header::CONTENT_TYPE]);
+                let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+            }
+        };
+
+        let feed = get_feed("/feed.json").await;
+        assert_eq!(2, feed["items"].as_array().unwrap().len());
+        assert_eq!("third", feed["items"][0]["title"]);
+        let next_url = feed["next_url"].as_str().unwrap();
+        assert_eq!("http://localhost:3001/feed.json?page=2", next_url);
+
+        let feed = get_feed(next_url.trim_start_matches("http://localhost:3001")).await;
+        assert_eq!(1, feed["items"].as_array().unwrap().len());
+        assert_eq!("first", feed["items"][0]["title"]);
+        assert!(feed.get("next_url").is_none());
+    }
+
+    #[tokio::test]
+    async fn should_serve_sitemap_and_robots() {
+        let (tags, tag_ids) = tag_fixture();
+        let blog_repository = BlogRepositoryForMemory::new(tags);
+        blog_repository
+            .create(CreateBlog {
+                title: "blog title".to_string(),
+                body: "blog body".to_string(),
+                tags: tag_ids,
+                status: BlogStatus::Published,
+                seo: BlogSeo::default(),
+                slug: None,
+                published_at: None,
+            })
+            .await
+            .unwrap();
+        let mut config = AppConfig::default();
+        config.site.base_url = "https://blog.example.com".to_string();
+        let app = create_app(
+            blog_repository,
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let get_text = |path: &str| {
+            let req = build_blog_req_with_empty(Method::GET, path);
+            let app = app.clone();
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                let status = res.status();
+                let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+                (status, String::from_utf8(bytes.to_vec()).unwrap())
+            }
+        };
+
+        let (status, xml) = get_text("/sitemap.xml").await;
+        assert_eq!(StatusCode::OK, status);
+        assert!(xml.contains("<loc>https://blog.example.com/blogs/1</loc><lastmod>"));
+
+        let (status, robots) = get_text("/robots.txt").await;
+        assert_eq!(StatusCode::OK, status);
+        assert!(robots.ends_with("Sitemap: https://blog.example.com/sitemap.xml\n"));
+
+        // URL が上限以下なら index にしないので分割したページは無い
+        let (status, _) = get_text("/sitemaps/1.xml").await;
+        assert_eq!(StatusCode::NOT_FOUND, status);
+    }
+
+    #[tokio::test]
+    async fn should_update_seo_and_return_blog_meta() {
+        let (tags, _tag_ids) = tag_fixture();
+        let app = create_app(
+            BlogRepositoryForMemory::new(tags),
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+        let req = build_blog_req_with_json(
+            "/api/v1/blogs",
+            Method::POST,
+            r#"{ "title": "blog title", "body": "blog body", "tags": [], "seo": { "description": "share me", "noindex": true } }"#
+                .to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        let req = build_blog_req_with_json(
+            "/api/v1/blogs/1",
+            Method::PATCH,
+            r#"{ "seo": { "noindex": false, "image": "https://example.com/og.png" } }"#.to_string(),
+        );
+        let blog = res_to_blog(app.clone().oneshot(req).await.unwrap()).await;
+        assert_eq!(
+            BlogSeo {
+                description: Some("share me".to_string()),
+                canonical_url: None,
+                noindex: false,
+                image: Some("https://example.com/og.png".to_string()),
+            },
+            blog.seo
+        );
+
+        let req = build_blog_req_with_json(
+            "/api/v1/blogs/1",
+            Method::PATCH,
+            r#"{ "seo": { "image": "not a url" } }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        let res = app
+            .oneshot(build_blog_req_with_empty(Method::GET, "/api/v1/blogs/1/meta"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let meta: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!("share me", meta["description"]);
+        assert_eq!("BlogPosting", meta["json_ld"]["@type"]);
+        assert!(meta["html"].as_str().unwrap().contains(r#"<meta name="twitter:card" content="summary_large_image" />"#));
+    }
+
+    #[tokio::test]
+    async fn should_redirect_imported_permalink() {
+        let redirect_repository = RedirectRepositoryForMemory::new();
+        redirect_repository.save("/2023/10/30/hello-world", 1).await.unwrap();
+        let mut config = AppConfig::default();
+        config.site.base_url = "https://blog.example.com".to_string();
+        let app = create_app(
+            BlogRepositoryForMemory::new(vec![]),
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            redirect_repository,
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+
+        let req = build_blog_req_with_empty(Method::GET, "/2023/10/30/hello-world/");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::MOVED_PERMANENTLY, res.status());
+        assert_eq!("https://blog.example.com/blogs/1", res.headers()[header::LOCATION]);
+
+        let req = build_blog_req_with_empty(Method::GET, "/2023/10/31/unknown");
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_serve_oembed_for_post_url() {
+        let blog_repository = BlogRepositoryForMemory::new(vec![]);
+        for status in [BlogStatus::Published, BlogStatus::Draft] {
+            blog_repository
+                .create(CreateBlog {
+                    title: "Hello oEmbed".to_string(),
+                    body: "blog body".to_string(),
+                    tags: vec![],
+                    status,
+                    seo: BlogSeo::default(),
+                    slug: None,
+                    published_at: None,
+                })
+                .await
+                .unwrap();
+        }
+        let mut config = AppConfig::default();
+        config.site.base_url = "https://blog.example.com".to_string();
+        let app = create_app(
+            blog_repository,
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+
+        let uri = "/oembed?url=https%3A%2F%2Fblog.example.com%2Fblogs%2F1&maxwidth=320";
+        let res = app.clone().oneshot(build_blog_req_with_empty(Method::GET, uri)).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("application/json; charset=utf-8", res.headers()[header::CONTENT_TYPE]);
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let embed: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!("rich", embed["type"]);
+        assert_eq!("Hello oEmbed", embed["title"]);
+        assert_eq!(320, embed["width"]);
+        assert!(embed["html"].as_str().unwrap().contains(r#"<a href="https://blog.example.com/blogs/1">"#));
+
+        let uri = "/oembed?url=https%3A%2F%2Fblog.example.com%2Fblogs%2F1&format=xml";
+        let res = app.clone().oneshot(build_blog_req_with_empty(Method::GET, uri)).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("text/xml; charset=utf-8", res.headers()[header::CONTENT_TYPE]);
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert!(String::from_utf8(bytes.to_vec()).unwrap().contains("<title>Hello oEmbed</title>"));
+
+        // 下書き、他サイトの URL、未対応の形式
+        for (uri, status) in [
+            ("/oembed?url=https%3A%2F%2Fblog.example.com%2Fblogs%2F2", StatusCode::NOT_FOUND),
+            ("/oembed?url=https%3A%2F%2Fother.example.com%2Fblogs%2F1", StatusCode::NOT_FOUND),
+            ("/oembed?url=https%3A%2F%2Fblog.example.com%2Fblogs%2F1&format=yaml", StatusCode::NOT_IMPLEMENTED),
+        ] {
+            let res = app.clone().oneshot(build_blog_req_with_empty(Method::GET, uri)).await.unwrap();
+            assert_eq!(status, res.status(), "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_publish_with_micropub() {
+        // Micropub のクライアントにも /auth/login で発行したアクセストークンを使う
+        let token = bearer().trim_start_matches("Bearer ").to_string();
+        // メモリ実装の記事リポジトリは渡したタグからしか名前を引けないので、Micropub で作られる順の id で用意しておく
+        let tags = vec![Tag::new(1, "rust".to_string()), Tag::new(2, "indieweb".to_string())];
+        let app = create_app(
+            BlogRepositoryForMemory::new(tags),
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+        let form = |body: &str, token: Option<&str>| {
+            let mut builder = Request::builder()
+                .uri("/micropub")
+                .method(Method::POST)
+                .header(header::CONTENT_TYPE, mime::APPLICATION_WWW_FORM_URLENCODED.as_ref());
+            if let Some(token) = token {
+                builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+            }
+            builder.body(Body::from(body.to_string())).unwrap()
+        };
+
+        let body = "h=entry&content=Hello+from+my+phone&category[]=rust&category[]=indieweb";
+        let res = app.clone().oneshot(form(body, None)).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        let res = app.clone().oneshot(form(body, Some("wrong"))).await.unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+        let res = app.clone().oneshot(form(body, Some(&token))).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert_eq!("http://localhost:3001/blogs/1", res.headers()[header::LOCATION]);
+
+        let source = |query: &str| {
+            Request::builder()
+                .uri(format!("/micropub?{}", query))
+                .header(header::AUTHORIZATION, bearer())
+                .body(Body::empty())
+                .unwrap()
+        };
+        let res = app
+            .clone()
+            .oneshot(source("q=source&url=http%3A%2F%2Flocalhost%3A3001%2Fblogs%2F1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let entry: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(serde_json::json!(["Hello from my phone"]), entry["properties"]["name"]);
+        assert_eq!(serde_json::json!(["rust", "indieweb"]), entry["properties"]["category"]);
+
+        let update = serde_json::json!({
+            "action": "update",
+            "url": "http://localhost:3001/blogs/1",
+            "replace": { "name": ["Hello"], "post-status": ["draft"] },
+            "delete": { "category": ["indieweb"] },
+        });
+        let req = build_blog_req_with_json("/micropub", Method::POST, update.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_empty(Method::GET, "/api/v1/blogs/1"))
+            .await
+            .unwrap();
+        let blog = res_to_blog(res).await;
+        assert_eq!("Hello", blog.title);
+        assert_eq!(BlogStatus::Draft, blog.status);
+        assert_eq!(vec!["rust"], blog.tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>());
+
+        let res = app.clone().oneshot(source("q=config")).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let body = format!("action=delete&url=http%3A%2F%2Flocalhost%3A3001%2Fblogs%2F1&access_token={}", token);
+        let res = app.clone().oneshot(form(&body, None)).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let res = app
+            .oneshot(build_blog_req_with_empty(Method::GET, "/api/v1/blogs/1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_edit_posts_with_metaweblog_xmlrpc() {
+        let user_repository = UserRepositoryForMemory::new();
+        user_repository
+            .create("admin", &auth::hash_password("secret").unwrap())
+            .await
+            .unwrap();
+        let tags = vec![Tag::new(1, "rust".to_string())];
+        let app = create_app(
+            BlogRepositoryForMemory::new(tags),
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            user_repository,
+            AppConfig::default(),
+        );
+        let call = |method: &str, params: &str| {
+            let body = format!(
+                "<?xml version=\"1.0\"?><methodCall><methodName>{}</methodName><params>{}</params></methodCall>",
+                method, params
+            );
+            Request::builder()
+                .uri("/xmlrpc")
+                .method(Method::POST)
+                .header(header::CONTENT_TYPE, "text/xml")
+                .body(Body::from(body))
+                .unwrap()
+        };
+        async fn res_to_string(res: Response) -> String {
+            assert_eq!(StatusCode::OK, res.status());
+            let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        }
+        let login = "<param><value>admin</value></param><param><value>secret</value></param>";
+
+        let post = "<param><value><struct>\
+            <member><name>title</name><value>Hello XML-RPC</value></member>\
+            <member><name>description</name><value>&lt;p&gt;from &lt;em&gt;desktop&lt;/em&gt;&lt;/p&gt;</value></member>\
+            <member><name>categories</name><value><array><data><value>rust</value></data></array></value></member>\
+            </struct></value></param>";
+        let params = format!(
+            "<param><value>1</value></param>{}{}<param><value><boolean>1</boolean></value></param>",
+            login, post
+        );
+        let res = app.clone().oneshot(call("metaWeblog.newPost", &params)).await.unwrap();
+        assert!(res_to_string(res).await.contains("<param><value><string>1</string></value></param>"));
+
+        let params = format!("<param><value>1</value></param>{}", login);
+        let res = app.clone().oneshot(call("metaWeblog.getPost", &params)).await.unwrap();
+        let body = res_to_string(res).await;
+        assert!(body.contains("<name>title</name><value><string>Hello XML-RPC</string></value>"));
+        assert!(body.contains("<name>mt_keywords</name><value><string>rust</string></value>"));
+        assert!(body.contains("&lt;p&gt;from &lt;em&gt;desktop&lt;/em&gt;&lt;/p&gt;"));
+
+        let params = format!(
+            "<param><value>1</value></param>{}<param><value><struct>\
+            <member><name>title</name><value>Edited</value></member>\
+            </struct></value></param><param><value><boolean>0</boolean></value></param>",
+            login
+        );
+        let res = app.clone().oneshot(call("metaWeblog.editPost", &params)).await.unwrap();
+        assert!(res_to_string(res).await.contains("<boolean>1</boolean>"));
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_empty(Method::GET, "/api/v1/blogs/1"))
+            .await
+            .unwrap();
+        let blog = res_to_blog(res).await;
+        assert_eq!("Edited", blog.title);
+        assert_eq!("from _desktop_", blog.body);
+        assert_eq!(BlogStatus::Draft, blog.status);
+
+        let params = format!("<param><value>1</value></param>{}<param><value><int>10</int></value></param>", login);
+        let res = app.clone().oneshot(call("metaWeblog.getRecentPosts", &params)).await.unwrap();
+        assert_eq!(1, res_to_string(res).await.matches("<name>postid</name>").count());
+        let params = format!("<param><value>1</value></param>{}", login);
+        let res = app.clone().oneshot(call("metaWeblog.getCategories", &params)).await.unwrap();
+        assert!(res_to_string(res).await.contains("<name>title</name><value><string>rust</string></value>"));
+
+        let params = format!(
+            "<param><value>key</value></param><param><value>1</value></param>{}<param><value><boolean>1</boolean></value></param>",
+            login
+        );
+        let res = app.clone().oneshot(call("blogger.deletePost", &params)).await.unwrap();
+        assert!(res_to_string(res).await.contains("<boolean>1</boolean>"));
+
+        // 削除済みの記事、誤ったパスワード、未対応のメソッドは fault
+        let params = format!("<param><value>1</value></param>{}", login);
+        let res = app.clone().oneshot(call("metaWeblog.getPost", &params)).await.unwrap();
+        assert!(res_to_string(res).await.contains("<name>faultCode</name><value><int>404</int></value>"));
+        let params = "<param><value>1</value></param><param><value>admin</value></param><param><value>wrong</value></param>";
+        let res = app.clone().oneshot(call("metaWeblog.getCategories", params)).await.unwrap();
+        assert!(res_to_string(res).await.contains("<int>403</int>"));
+        let res = app.oneshot(call("wp.getPages", "")).await.unwrap();
+        assert!(res_to_string(res).await.contains("<int>-32601</int>"));
+    }
+
+    #[tokio::test]
+    async fn should_publish_with_atompub() {
+        let tags = vec![Tag::new(1, "rust".to_string()), Tag::new(2, "axum".to_string())];
+        let app = create_app(
+            BlogRepositoryForMemory::new(tags),
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+        let entry = |title: &str, etag: Option<&str>, method: Method, uri: &str| {
+            let body = format!(
+                r#"<entry xmlns="http://www.w3.org/2005/Atom"><title>{}</title><category term="rust" /><content type="html">&lt;p&gt;from &lt;strong&gt;AtomPub&lt;/strong&gt;&lt;/p&gt;</content></entry>"#,
+                title
+            );
+            let mut req = Request::builder()
+                .uri(uri)
+                .method(method)
+                .header(header::CONTENT_TYPE, "application/atom+xml;type=entry")
+                .header(header::AUTHORIZATION, bearer())
+                .header("slug", "hello%20atompub");
+            if let Some(etag) = etag {
+                req = req.header(header::IF_MATCH, etag);
+            }
+            req.body(Body::from(body)).unwrap()
+        };
+
+        let res = app.clone().oneshot(entry("Hello AtomPub", None, Method::POST, "/atompub/entries")).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert_eq!(
+            "http://localhost:3001/atompub/entries/1",
+            res.headers()[header::LOCATION].to_str().unwrap()
+        );
+        let created_etag = res.headers()[header::ETAG].to_str().unwrap().to_string();
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_empty(Method::GET, "/api/v1/blogs/1"))
+            .await
+            .unwrap();
+        let blog = res_to_blog(res).await;
+        assert_eq!("from **AtomPub**", blog.body);
+        assert_eq!(Some("hello-atompub".to_string()), blog.slug);
+        assert_eq!(vec![Tag::new(1, "rust".to_string())], blog.tags);
+
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_empty(Method::GET, "/atompub/entries/1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let etag = res.headers()[header::ETAG].to_str().unwrap().to_string();
+        assert_eq!(created_etag, etag);
+
+        // 下書きも返すので、読むだけでもアクセストークンが要る
+        for uri in ["/atompub/entries", "/atompub/entries/1"] {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::UNAUTHORIZED, res.status(), "{}", uri);
+        }
+
+        let res = app
+            .clone()
+            .oneshot(entry("Edited", Some("\"stale\""), Method::PUT, "/atompub/entries/1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::PRECONDITION_FAILED, res.status());
+        let res = app
+            .clone()
+            .oneshot(entry("Edited", Some(&etag), Method::PUT, "/atompub/entries/1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_ne!(etag, res.headers()[header::ETAG].to_str().unwrap());
+        // 古い ETag での更新は他のクライアントの変更を上書きしない
+        let res = app
+            .clone()
+            .oneshot(entry("Again", Some(&etag), Method::PUT, "/atompub/entries/1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::PRECONDITION_FAILED, res.status());
+
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_empty(Method::GET, "/atompub/categories"))
+            .await
+            .unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert!(String::from_utf8(bytes.to_vec()).unwrap().contains(r#"term="rust""#));
+
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_empty(Method::DELETE, "/atompub/entries/1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let res = app
+            .oneshot(build_blog_req_with_empty(Method::GET, "/atompub/entries/1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_receive_and_send_webmentions() {
+        use crate::webmention::test_utils::serve;
+        use axum::{extract::Form, http::HeaderValue, response::Html};
+        use std::collections::HashMap;
+        use std::sync::Mutex;
+        use std::time::Duration;
+
+        // 外部サイトの代わり。/linking は記事 1 にリンクし、/target は Webmention のエンドポイントを告知する
+        let received: Arc<Mutex<Vec<HashMap<String, String>>>> = Arc::default();
+        let endpoint = {
+            let received = received.clone();
+            move |Form(form): Form<HashMap<String, String>>| async move {
+                received.lock().unwrap().push(form);
+                StatusCode::ACCEPTED
+            }
+        };
+        let target = || async {
+            let mut headers = axum::http::HeaderMap::new();
+            headers.insert(header::LINK, HeaderValue::from_static(r#"</endpoint>; rel="webmention""#));
+            (headers, Html("<p>target</p>"))
+        };
+        let addr = serve(
+            Router::new()
+                .route("/linking", get(|| async { Html(r#"<a href="http://localhost:3001/blogs/1">post</a>"#) }))
+                .route("/target", get(target))
+                .route("/endpoint", post(endpoint)),
+        );
+
+        // 相手のサイトの代わりに手元のサーバーを使うので、内部のアドレスへの取得を許す
+        let mut config = AppConfig::default();
+        config.outbound.allow_local = true;
+        let app = create_app(
+            BlogRepositoryForMemory::new(vec![]),
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let body = format!(
+            r#"{{"title": "linking post", "body": "see [this](http://{}/target)", "tags": []}}"#,
+            addr
+        );
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_json("/api/v1/blogs", Method::POST, body))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        let mention = |source: String, target: &str| {
+            Request::builder()
+                .uri("/webmention")
+                .method(Method::POST)
+                .header(header::CONTENT_TYPE, mime::APPLICATION_WWW_FORM_URLENCODED.as_ref())
+                .body(Body::from(serde_urlencoded::to_string(&[("source", source.as_str()), ("target", target)]).unwrap()))
+                .unwrap()
+        };
+        let res = app
+            .clone()
+            .oneshot(mention(format!("http://{}/linking", addr), "http://localhost:3001/blogs/1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::ACCEPTED, res.status());
+        let res = app
+            .clone()
+            .oneshot(mention(format!("http://{}/linking", addr), "http://localhost:3001/blogs/99"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        // 確認と送信はバックグラウンドのワーカーが行うので、終わるまで待つ
+        let mut mentions = vec![];
+        for _ in 0..100 {
+            let res = app
+                .clone()
+                .oneshot(build_blog_req_with_empty(Method::GET, "/api/v1/blogs/1/mentions"))
+                .await
+                .unwrap();
+            let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+            mentions = serde_json::from_slice::<Vec<serde_json::Value>>(&bytes).unwrap();
+            if !mentions.is_empty() && !received.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(1, mentions.len());
+        assert_eq!(format!("http://{}/linking", addr), mentions[0]["source"]);
+        assert_eq!("verified", mentions[0]["status"]);
+        let received = received.lock().unwrap();
+        assert_eq!(1, received.len());
+        assert_eq!("http://localhost:3001/blogs/1", received[0]["source"]);
+        assert_eq!(format!("http://{}/target", addr), received[0]["target"]);
+    }
+
+    #[tokio::test]
+    async fn should_export_markdown_archive() {
+        let (tags, tag_ids) = tag_fixture();
+        let blog_repository = BlogRepositoryForMemory::new(tags);
+        for title in ["Hello Axum", "日本語"] {
+            blog_repository
+                .create(CreateBlog {
+                    title: title.to_string(),
+                    body: "blog body".to_string(),
+                    tags: tag_ids.clone(),
+                    status: BlogStatus::Published,
+                    seo: BlogSeo::default(),
+                    slug: None,
+                    published_at: None,
+                })
+                .await
+                .unwrap();
+        }
+        let tag_repository = TagRepositoryForMemory::new();
+        tag_repository.create("test tag".to_string()).await.unwrap();
+        let app = create_app(
+            blog_repository,
+            tag_repository,
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        );
+        // 下書きも含むので、トークンが無ければ 401
+        let req = Request::builder().uri("/api/v1/export").body(Body::empty()).unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+
+        let res = app
+            .oneshot(build_blog_req_with_empty(Method::GET, "/api/v1/export"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("application/gzip", res.headers()[header::CONTENT_TYPE]);
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(&bytes[..]));
+        let mut files = std::collections::BTreeMap::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().to_string();
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut entry, &mut content).unwrap();
+            files.insert(path, content);
+        }
+        assert_eq!(
+            vec!["manifest.json", "posts/1-hello-axum.md", "posts/2-post-2.md", "tags.yaml"],
+            files.keys().collect::<Vec<_>>()
+        );
+        assert!(files["posts/1-hello-axum.md"]
+            .starts_with("---\nid: 1\nslug: \"hello-axum\"\ntitle: \"Hello Axum\"\ntags: [\"test tag\"]\n"));
+        assert_eq!("- id: 1\n  name: \"test tag\"\n", files["tags.yaml"]);
+        let manifest: serde_json::Value = serde_json::from_str(&files["manifest.json"]).unwrap();
+        assert_eq!("posts/2-post-2.md", manifest["blogs"][1]["path"]);
+    }
+
+    #[tokio::test]
+    async fn should_federate_with_activitypub() {
+        use crate::activitypub::delivery::test_utils::{fake_remote, TEST_PRIVATE_KEY};
+        use crate::activitypub::signature::{digest, ActorKey};
+        use crate::handlers::cache::http_date;
+        use axum::http::{HeaderMap, HeaderValue};
+        use chrono::Utc;
+        use std::time::Duration;
+
+        let remote = fake_remote(0);
+        let mut config = AppConfig::default();
+        config.activitypub.private_key = Some(TEST_PRIVATE_KEY.to_string());
+        // 偽のリモートはループバックの http で動く
+        config.outbound.allow_local = true;
+        let app = create_app(
+            BlogRepositoryForMemory::new(vec![]),
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let get_json = |path: &str| {
+            let app = app.clone();
+            let req = build_blog_req_with_empty(Method::GET, path);
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                let content_type = res.headers()[header::CONTENT_TYPE].to_str().unwrap().to_string();
+                let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+                (content_type, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+            }
+        };
+
+        let (content_type, webfinger) = get_json("/.well-known/webfinger?resource=acct:blog@localhost:3001").await;
+        assert_eq!("application/jrd+json", content_type);
+        assert_eq!("http://localhost:3001/activitypub/actor", webfinger["links"][0]["href"]);
+        let (content_type, actor) = get_json("/activitypub/actor").await;
+        assert_eq!("application/activity+json", content_type);
+        let key = ActorKey::from_pem(TEST_PRIVATE_KEY).unwrap();
+        assert_eq!(key.public_key_pem, actor["publicKey"]["publicKeyPem"]);
+
+        let follow = serde_json::json!({
+            "id": format!("{}/follows/1", remote.actor_id()),
+            "type": "Follow",
+            "actor": remote.actor_id(),
+            "object": "http://localhost:3001/activitypub/actor"
+        })
+        .to_string();
+        let inbox = |body: String, signed: bool| {
+            let mut headers = HeaderMap::new();
+            headers.insert("host", HeaderValue::from_static("localhost:3001"));
+            headers.insert("date", HeaderValue::from_str(&http_date(Utc::now())).unwrap());
+            headers.insert("digest", HeaderValue::from_str(&digest(body.as_bytes())).unwrap());
+            if signed {
+                let key_id = format!("{}#main-key", remote.actor_id());
+                let signature = key.sign(&key_id, "POST", "/activitypub/inbox", &headers).unwrap();
+                headers.insert("signature", HeaderValue::from_str(&signature).unwrap());
+            }
+            let mut req = build_blog_req_with_json("/activitypub/inbox", Method::POST, body);
+            req.headers_mut().extend(headers);
+            req
+        };
+        let res = app.clone().oneshot(inbox(follow.clone(), false)).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        let tampered = follow.replace("follows/1", "follows/2");
+        let mut req = inbox(follow.clone(), true);
+        *req.body_mut() = Body::from(tampered);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        let res = app.clone().oneshot(inbox(follow, true)).await.unwrap();
+        assert_eq!(StatusCode::ACCEPTED, res.status());
+        assert_eq!(1, get_json("/activitypub/followers").await.1["totalItems"]);
+
+        // 公開した記事はフォロワーの inbox に配られる
+        let body = r#"{"title": "federated", "body": "hello", "tags": []}"#.to_string();
+        let res = app
+            .clone()
+            .oneshot(build_blog_req_with_json("/api/v1/blogs", Method::POST, body))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        for _ in 0..100 {
+            if remote.received().len() >= 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let received = remote.received();
+        assert_eq!("Accept", received[0]["type"]);
+        assert_eq!("Create", received[1]["type"]);
+        assert_eq!("federated", received[1]["object"]["name"]);
+        assert_eq!(1, get_json("/activitypub/outbox").await.1["totalItems"]);
+    }
+
+    #[tokio::test]
+    async fn should_require_access_token_for_writes() {
+        let user_repository = UserRepositoryForMemory::new();
+        user_repository
+            .create("admin", &auth::hash_password("correct horse").unwrap())
+            .await
+            .unwrap();
+        let app = create_app(
+            BlogRepositoryForMemory::new(vec![]),
+            TagRepositoryForMemory::new(),
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            user_repository,
+            AppConfig::default(),
+        );
+        let request = |method: Method, path: &str, body: &str, token: Option<&str>| {
+            let mut builder = Request::builder()
+                .uri(path)
+                .method(method)
+                .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
+            if let Some(token) = token {
+                builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+            }
+            builder.body(Body::from(body.to_string())).unwrap()
+        };
+        let send = |req: Request<Body>| {
+            let app = app.clone();
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                let status = res.status();
+                let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap_or_default())
+            }
+        };
+        let blog = r#"{"title": "blog title", "body": "blog body", "tags": []}"#;
+
+        // 読み取りは誰でもできる。書き込みはトークンが無いか不正なら 401
+        let res = app.clone().oneshot(request(Method::POST, "/api/v1/blogs", blog, None)).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        assert_eq!("Bearer", res.headers()[header::WWW_AUTHENTICATE]);
+        for (method, path) in [
+            (Method::POST, "/blogs"),
+            (Method::PATCH, "/api/v1/blogs/1"),
+            (Method::DELETE, "/api/v1/blogs/1"),
+            (Method::POST, "/api/v1/tags"),
+            (Method::DELETE, "/api/v1/tags/1"),
+        ] {
+            let (status, _) = send(request(method, path, "{}", Some("invalid"))).await;
+            assert_eq!(StatusCode::UNAUTHORIZED, status, "{}", path);
+        }
+        let (status, _) = send(request(Method::GET, "/api/v1/blogs", "", None)).await;
+        assert_eq!(StatusCode::OK, status);
+
+        let login = |password: &str| {
+            let body = serde_json::json!({ "username": "admin", "password": password }).to_string();
+            request(Method::POST, "/auth/login", &body, None)
+        };
+        let (status, _) = send(login("wrong horse")).await;
+        assert_eq!(StatusCode::UNAUTHORIZED, status);
+        let res = app.clone().oneshot(login("correct horse")).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("no-store", res.headers()[header::CACHE_CONTROL]);
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let tokens: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!("Bearer", tokens["token_type"]);
+        assert_eq!(900, tokens["expires_in"]);
+        let access_token = tokens["access_token"].as_str().unwrap();
+        let (status, _) = send(request(Method::POST, "/api/v1/blogs", blog, Some(access_token))).await;
+        assert_eq!(StatusCode::CREATED, status);
+
+        // GraphQL もミューテーションだけトークンが要る
+        let mutation = serde_json::json!({ "query": r#"mutation { deleteBlog(id: 1) }"# }).to_string();
+        let (_, body) = send(request(Method::POST, GRAPHQL_PATH, &mutation, None)).await;
+        assert_eq!("Unauthorized: access token is required", body["errors"][0]["message"]);
+        let (_, body) = send(request(Method::POST, GRAPHQL_PATH, &mutation, Some(access_token))).await;
+        assert_eq!(serde_json::json!({ "deleteBlog": true }), body["data"]);
+
+        // リフレッシュトークンは 1 回しか使えず、失効させたら使えない
+        let refresh = |token: &serde_json::Value| {
+            let body = serde_json::json!({ "refresh_token": token }).to_string();
+            request(Method::POST, "/auth/refresh", &body, None)
+        };
+        let (status, refreshed) = send(refresh(&tokens["refresh_token"])).await;
+        assert_eq!(StatusCode::OK, status);
+        assert_ne!(tokens["refresh_token"], refreshed["refresh_token"]);
+        let (status, _) = send(refresh(&tokens["refresh_token"])).await;
+        assert_eq!(StatusCode::UNAUTHORIZED, status);
+        let body = serde_json::json!({ "refresh_token": refreshed["refresh_token"] }).to_string();
+        let (status, _) = send(request(Method::POST, "/auth/revoke", &body, None)).await;
+        assert_eq!(StatusCode::NO_CONTENT, status);
+        let (status, _) = send(refresh(&refreshed["refresh_token"])).await;
+        assert_eq!(StatusCode::UNAUTHORIZED, status);
+    }
+}
@@ -0,0 +1,405 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+use crate::import::html::html_to_markdown;
+use crate::render;
+use crate::repositories::blog::{BlogEntity, BlogSeo, BlogStatus, CreateBlog};
+
+pub const MICROPUB_PATH: &str = "/micropub";
+// name の無いノートは本文の先頭をタイトルにする
+const TITLE_LENGTH: usize = 50;
+
+// h-entry のプロパティ。Microformats2 の JSON と同じく値は常に配列
+pub type Properties = BTreeMap<String, Vec<Value>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MicropubError {
+    Unauthorized,
+    Forbidden,
+    InvalidRequest(String),
+    Internal,
+}
+
+impl MicropubError {
+    fn invalid(description: impl Into<String>) -> Self {
+        MicropubError::InvalidRequest(description.into())
+    }
+
+    // 仕様のエラーレスポンスの本文
+    pub fn to_json(&self) -> Value {
+        match self {
+            MicropubError::Unauthorized => json!({ "error": "unauthorized" }),
+            MicropubError::Forbidden => json!({ "error": "forbidden" }),
+            MicropubError::InvalidRequest(description) => {
+                json!({ "error": "invalid_request", "error_description": description })
+            }
+            MicropubError::Internal => json!({ "error": "server_error" }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Update {
+    pub replace: Properties,
+    pub add: Properties,
+    // 値が空ならプロパティごと、そうでなければその値だけを消す
+    pub delete: Properties,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Create(Properties),
+    Update { url: String, update: Update },
+    Delete { url: String },
+}
+
+// application/x-www-form-urlencoded (multipart 以外) のリクエスト。access_token は認証に使うので取り除いて返す
+pub fn parse_form(pairs: Vec<(String, String)>) -> Result<(Action, Option<String>), MicropubError> {
+    let mut access_token = None;
+    let mut action = None;
+    let mut url = None;
+    let mut kind = None;
+    let mut properties = Properties::new();
+    for (key, value) in pairs {
+        match key.as_str() {
+            "access_token" => access_token = Some(value),
+            "action" => action = Some(value),
+            "url" => url = Some(value),
+            "h" => kind = Some(value),
+            _ => {
+                // category[]=a&category[]=b は配列、category=a は1要素として扱う
+                let key = key.strip_suffix("[]").unwrap_or(&key).to_string();
+                properties.entry(key).or_default().push(Value::String(value));
+            }
+        }
+    }
+    let action = match action.as_deref() {
+        None => {
+            if kind.as_deref().unwrap_or("entry") != "entry" {
+                return Err(MicropubError::invalid("only h=entry is supported"));
+            }
+            Action::Create(properties)
+        }
+        Some("delete") => Action::Delete {
+            url: url.ok_or_else(|| MicropubError::invalid("url is required"))?,
+        },
+        Some(action) => return Err(MicropubError::invalid(format!("unsupported action: {}", action))),
+    };
+    Ok((action, access_token))
+}
+
+// application/json のリクエスト
+pub fn parse_json(value: Value) -> Result<Action, MicropubError> {
+    let url = || {
+        value["url"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| MicropubError::invalid("url is required"))
+    };
+    match value["action"].as_str() {
+        None => {
+            if value["type"] != json!(["h-entry"]) {
+                return Err(MicropubError::invalid("only h-entry is supported"));
+            }
+            Ok(Action::Create(properties_from_json(&value["properties"], "properties")?))
+        }
+        Some("update") => {
+            let delete = match &value["delete"] {
+                Value::Null => Properties::new(),
+                Value::Array(names) => names
+                    .iter()
+                    .map(|name| match name.as_str() {
+                        Some(name) => Ok((name.to_string(), vec![])),
+                        None => Err(MicropubError::invalid("delete must be property names")),
+                    })
+                    .collect::<Result<_, _>>()?,
+                delete => properties_from_json(delete, "delete")?,
+            };
+            let update = Update {
+                replace: properties_from_json(&value["replace"], "replace")?,
+                add: properties_from_json(&value["add"], "add")?,
+                delete,
+            };
+            Ok(Action::Update { url: url()?, update })
+        }
+        Some("delete") => Ok(Action::Delete { url: url()? }),
+        Some(action) => Err(MicropubError::invalid(format!("unsupported action: {}", action))),
+    }
+}
+
+fn properties_from_json(value: &Value, name: &str) -> Result<Properties, MicropubError> {
+    match value {
+        Value::Null => Ok(Properties::new()),
+        Value::Object(properties) => properties
+            .iter()
+            .map(|(key, values)| match values {
+                Value::Array(values) => Ok((key.clone(), values.clone())),
+                _ => Err(MicropubError::invalid(format!("{}.{} must be an array", name, key))),
+            })
+            .collect(),
+        _ => Err(MicropubError::invalid(format!("{} must be an object", name))),
+    }
+}
+
+// 投稿済みの記事に update を適用する
+pub fn apply(properties: &mut Properties, update: &Update) {
+    for (key, values) in &update.replace {
+        properties.insert(key.clone(), values.clone());
+    }
+    for (key, values) in &update.add {
+        let current = properties.entry(key.clone()).or_default();
+        for value in values {
+            if !current.contains(value) {
+                current.push(value.clone());
+            }
+        }
+    }
+    for (key, values) in &update.delete {
+        match values.is_empty() {
+            true => {
+                properties.remove(key);
+            }
+            false => {
+                if let Some(current) = properties.get_mut(key) {
+                    current.retain(|value| !values.contains(value));
+                }
+            }
+        }
+    }
+}
+
+// 記事をプロパティにする。q=source の応答と update の元になる
+pub fn properties(blog: &BlogEntity) -> Properties {
+    let mut properties = Properties::new();
+    let mut set = |key: &str, values: Vec<Value>| {
+        if !values.is_empty() {
+            properties.insert(key.to_string(), values);
+        }
+    };
+    set("name", vec![json!(blog.title)]);
+    set("content", vec![json!(blog.body)]);
+    set("category", blog.tags.iter().map(|tag| json!(tag.name)).collect());
+    let status = match blog.status {
+        BlogStatus::Published => "published",
+        BlogStatus::Draft => "draft",
+    };
+    set("post-status", vec![json!(status)]);
+    set("published", vec![json!(blog.created_at.to_rfc3339_opts(SecondsFormat::Secs, true))]);
+    set("mp-slug", blog.slug.iter().map(|slug| json!(slug)).collect());
+    set("summary", blog.seo.description.iter().map(|summary| json!(summary)).collect());
+    set("featured", blog.seo.image.iter().map(|image| json!(image)).collect());
+    properties
+}
+
+// q=source の応答。names があればそのプロパティだけを返す
+pub fn source(blog: &BlogEntity, names: &[String]) -> Value {
+    let properties: Map<String, Value> = properties(blog)
+        .into_iter()
+        .filter(|(key, _)| names.is_empty() || names.contains(key))
+        .map(|(key, values)| (key, Value::Array(values)))
+        .collect();
+    match names.is_empty() {
+        true => json!({ "type": ["h-entry"], "properties": properties }),
+        false => json!({ "properties": properties }),
+    }
+}
+
+pub fn config() -> Value {
+    json!({
+        "q": ["config", "source", "syndicate-to"],
+        "syndicate-to": [],
+        "post-types": [
+            { "type": "note", "name": "Note" },
+            { "type": "article", "name": "Article" },
+        ],
+    })
+}
+
+// プロパティから読み出した記事。未対応のプロパティは無視する
+#[derive(Debug, Clone, PartialEq)]
+pub struct Post {
+    pub title: String,
+    pub body: String,
+    pub categories: Vec<String>,
+    pub status: BlogStatus,
+    pub slug: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+    pub summary: Option<String>,
+    pub featured: Option<String>,
+}
+
+impl Post {
+    pub fn from_properties(properties: &Properties) -> Result<Post, MicropubError> {
+        let first = |key: &str| properties.get(key).and_then(|values| values.first());
+        let text = |key: &str| -> Result<Option<String>, MicropubError> {
+            match first(key) {
+                None => Ok(None),
+                Some(Value::String(value)) => Ok(Some(value.clone())),
+                Some(_) => Err(MicropubError::invalid(format!("{} must be a string", key))),
+            }
+        };
+
+        // content は文字列か {"html": ...} / {"value": ...}
+        let body = match first("content") {
+            None => String::new(),
+            Some(Value::String(content)) => content.clone(),
+            Some(Value::Object(content)) => match (content.get("html"), content.get("value")) {
+                (Some(Value::String(html)), _) => html_to_markdown(html),
+                (_, Some(Value::String(value))) => value.clone(),
+                _ => return Err(MicropubError::invalid("content must have html or value")),
+            },
+            Some(_) => return Err(MicropubError::invalid("content must be a string or an object")),
+        };
+        let title = match text("name")?.filter(|name| !name.trim().is_empty()) {
+            Some(name) => name,
+            None => render::excerpt(&body, TITLE_LENGTH),
+        };
+        if title.is_empty() {
+            return Err(MicropubError::invalid("name or content is required"));
+        }
+        // h-card などの埋め込みオブジェクトはタグにできないので文字列だけを使う
+        let mut categories: Vec<String> = Vec::new();
+        for category in properties.get("category").into_iter().flatten() {
+            if let Some(category) = category.as_str().map(str::trim).filter(|category| !category.is_empty()) {
+                if !categories.iter().any(|existing| existing == category) {
+                    categories.push(category.to_string());
+                }
+            }
+        }
+        let status = match text("post-status")?.as_deref() {
+            None | Some("published") => BlogStatus::Published,
+            Some("draft") => BlogStatus::Draft,
+            Some(status) => return Err(MicropubError::invalid(format!("unsupported post-status: {}", status))),
+        };
+        let published = match text("published")? {
+            None => None,
+            Some(published) => Some(
+                DateTime::parse_from_rfc3339(&published)
+                    .map_err(|_| MicropubError::invalid("published must be an RFC 3339 date"))?
+                    .with_timezone(&Utc),
+            ),
+        };
+
+        Ok(Post {
+            title,
+            body,
+            categories,
+            status,
+            slug: text("mp-slug")?,
+            published,
+            summary: text("summary")?,
+            featured: text("featured")?,
+        })
+    }
+
+    // seo は summary と featured 以外を引き継ぐ元
+    pub fn into_create_blog(self, tags: Vec<i32>, seo: BlogSeo) -> CreateBlog {
+        CreateBlog {
+            title: self.title,
+            body: self.body,
+            tags,
+            status: self.status,
+            seo: BlogSeo {
+                description: self.summary,
+                image: self.featured,
+                ..seo
+            },
+            slug: self.slug,
+            published_at: self.published,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::tag::Tag;
+
+    #[test]
+    fn parse_form_and_json_entries() {
+        let pairs = [
+            ("h", "entry"),
+            ("content", "Hello **world**"),
+            ("category[]", "rust"),
+            ("category[]", "axum"),
+            ("mp-slug", "hello"),
+            ("access_token", "secret"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        let (action, token) = parse_form(pairs).unwrap();
+        assert_eq!(Some("secret".to_string()), token);
+        let properties = match action {
+            Action::Create(properties) => properties,
+            action => panic!("unexpected action: {:?}", action),
+        };
+        let post = Post::from_properties(&properties).unwrap();
+        assert_eq!("Hello world", post.title);
+        assert_eq!(vec!["rust", "axum"], post.categories);
+        assert_eq!(Some("hello".to_string()), post.slug);
+        assert_eq!(BlogStatus::Published, post.status);
+
+        let action = parse_json(json!({
+            "type": ["h-entry"],
+            "properties": {
+                "name": ["Title"],
+                "content": [{ "html": "<p>a <b>b</b></p>" }],
+                "post-status": ["draft"],
+                "published": ["2023-11-13T09:00:00+09:00"],
+            },
+        }))
+        .unwrap();
+        let post = match action {
+            Action::Create(properties) => Post::from_properties(&properties).unwrap(),
+            action => panic!("unexpected action: {:?}", action),
+        };
+        assert_eq!("a **b**", post.body);
+        assert_eq!(BlogStatus::Draft, post.status);
+        assert_eq!("2023-11-13T00:00:00+00:00", post.published.unwrap().to_rfc3339());
+
+        assert_eq!(
+            Err(MicropubError::invalid("name or content is required")),
+            Post::from_properties(&Properties::new())
+        );
+        assert!(parse_json(json!({ "action": "undelete", "url": "x" })).is_err());
+        assert!(parse_json(json!({ "type": ["h-event"], "properties": {} })).is_err());
+    }
+
+    #[test]
+    fn apply_update_to_source() {
+        let mut blog = BlogEntity::new(
+            1,
+            "Title".to_string(),
+            "body".to_string(),
+            vec![Tag::new(1, "rust".to_string()), Tag::new(2, "axum".to_string())],
+        );
+        blog.seo.description = Some("summary".to_string());
+
+        let action = parse_json(json!({
+            "action": "update",
+            "url": "http://localhost:3001/blogs/1",
+            "replace": { "content": ["new body"] },
+            "add": { "category": ["web", "rust"] },
+            "delete": { "category": ["axum"] },
+        }))
+        .unwrap();
+        let update = match action {
+            Action::Update { update, .. } => update,
+            action => panic!("unexpected action: {:?}", action),
+        };
+        let mut updated = properties(&blog);
+        apply(&mut updated, &update);
+        apply(&mut updated, &Update { delete: [("summary".to_string(), vec![])].into(), ..Update::default() });
+        let post = Post::from_properties(&updated).unwrap();
+        assert_eq!("Title", post.title);
+        assert_eq!("new body", post.body);
+        assert_eq!(vec!["rust", "web"], post.categories);
+        assert_eq!(None, post.summary);
+
+        let selected = source(&blog, &["category".to_string()]);
+        assert_eq!(json!({ "properties": { "category": ["rust", "axum"] } }), selected);
+        assert_eq!(json!(["h-entry"]), source(&blog, &[])["type"]);
+    }
+}
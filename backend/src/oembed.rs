@@ -0,0 +1,228 @@
+use serde::Serialize;
+
+use crate::config::SiteConfig;
+use crate::render::{escape_html, excerpt};
+use crate::repositories::blog::BlogEntity;
+
+pub const OEMBED_PATH: &str = "/oembed";
+pub const JSON_CONTENT_TYPE: &str = "application/json; charset=utf-8";
+pub const XML_CONTENT_TYPE: &str = "text/xml; charset=utf-8";
+const VERSION: &str = "1.0";
+// 埋め込みカードの大きさ。maxwidth / maxheight が小さければそちらに合わせる
+const DEFAULT_WIDTH: u32 = 600;
+const DEFAULT_HEIGHT: u32 = 200;
+// 画像の実寸は保存していないので OGP 画像の推奨サイズとして扱う
+const THUMBNAIL_WIDTH: u32 = 1200;
+const THUMBNAIL_HEIGHT: u32 = 630;
+const EXCERPT_LENGTH: usize = 120;
+const CACHE_AGE: u32 = 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Xml,
+}
+
+impl Format {
+    // 未対応の形式は None (仕様では 501 を返す)
+    pub fn parse(format: Option<&str>) -> Option<Self> {
+        match format {
+            None | Some("json") => Some(Format::Json),
+            Some("xml") => Some(Format::Xml),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => JSON_CONTENT_TYPE,
+            Format::Xml => XML_CONTENT_TYPE,
+        }
+    }
+}
+
+// 埋め込みたい URL がこのサイトの /blogs/{id} ならその id。http と https の違いは無視する
+pub fn resolve(url: &str, site: &SiteConfig) -> Option<i32> {
+    let without_scheme = |url: &str| url.split_once("://").map(|(_, rest)| rest.to_string());
+    let url = without_scheme(url)?;
+    let base = without_scheme(&site.base_url)?;
+    let path = url.strip_prefix(&base)?;
+    let path = path.split(|c| c == '?' || c == '#').next().unwrap_or_default();
+    let id = path.strip_prefix("/blogs/")?.trim_end_matches('/');
+    id.strip_suffix(".html").unwrap_or(id).parse().ok()
+}
+
+// <head> に置く oEmbed の discovery リンク
+pub fn discovery_links(blog: &BlogEntity, site: &SiteConfig) -> String {
+    let url = site.blog_url(blog.id);
+    [("json", "application/json+oembed"), ("xml", "text/xml+oembed")]
+        .iter()
+        .map(|(format, media_type)| {
+            let href = format!("{}?url={}&format={}", site.url(OEMBED_PATH), encode_query(&url), format);
+            format!(
+                r#"<link rel="alternate" type="{}" href="{}" title="{}" />"#,
+                media_type,
+                escape_html(&href),
+                escape_html(&blog.title)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn encode_query(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// 縦横比を保ったまま maxwidth / maxheight に収める
+fn fit(width: u32, height: u32, max_width: Option<u32>, max_height: Option<u32>) -> (u32, u32) {
+    let (mut width, mut height) = (width, height);
+    if let Some(max) = max_width.filter(|max| *max < width) {
+        height = height * max / width;
+        width = max;
+    }
+    if let Some(max) = max_height.filter(|max| *max < height) {
+        width = width * max / height;
+        height = max;
+    }
+    (width, height)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Embed {
+    #[serde(rename = "type")]
+    pub embed_type: &'static str,
+    pub version: &'static str,
+    pub title: String,
+    pub author_name: String,
+    pub author_url: String,
+    pub provider_name: String,
+    pub provider_url: String,
+    pub cache_age: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_height: Option<u32>,
+    pub html: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Embed {
+    pub fn new(blog: &BlogEntity, site: &SiteConfig, max_width: Option<u32>, max_height: Option<u32>) -> Self {
+        let width = max_width.map_or(DEFAULT_WIDTH, |max| max.min(DEFAULT_WIDTH));
+        let height = max_height.map_or(DEFAULT_HEIGHT, |max| max.min(DEFAULT_HEIGHT));
+        let thumbnail = blog.seo.image.clone().map(|image| {
+            let (width, height) = fit(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT, max_width, max_height);
+            (image, width, height)
+        });
+
+        let url = site.blog_url(blog.id);
+        let html = format!(
+            r#"<blockquote class="next-blog-embed" style="max-width:{width}px;max-height:{height}px;overflow:hidden"><a href="{url}">{title}</a><p>{excerpt}</p><cite>{site}</cite></blockquote>"#,
+            width = width,
+            height = height,
+            url = escape_html(&url),
+            title = escape_html(&blog.title),
+            excerpt = escape_html(&excerpt(&blog.body, EXCERPT_LENGTH)),
+            site = escape_html(&site.title),
+        );
+        Embed {
+            embed_type: "rich",
+            version: VERSION,
+            title: blog.title.clone(),
+            author_name: site.author.clone(),
+            author_url: site.url("/"),
+            provider_name: site.title.clone(),
+            provider_url: site.url("/"),
+            cache_age: CACHE_AGE,
+            thumbnail_url: thumbnail.as_ref().map(|(url, _, _)| url.clone()),
+            thumbnail_width: thumbnail.as_ref().map(|(_, width, _)| *width),
+            thumbnail_height: thumbnail.as_ref().map(|(_, _, height)| *height),
+            html,
+            width,
+            height,
+        }
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut fields = vec![
+            ("type", self.embed_type.to_string()),
+            ("version", self.version.to_string()),
+            ("title", self.title.clone()),
+            ("author_name", self.author_name.clone()),
+            ("author_url", self.author_url.clone()),
+            ("provider_name", self.provider_name.clone()),
+            ("provider_url", self.provider_url.clone()),
+            ("cache_age", self.cache_age.to_string()),
+        ];
+        if let (Some(url), Some(width), Some(height)) = (&self.thumbnail_url, self.thumbnail_width, self.thumbnail_height) {
+            fields.push(("thumbnail_url", url.clone()));
+            fields.push(("thumbnail_width", width.to_string()));
+            fields.push(("thumbnail_height", height.to_string()));
+        }
+        fields.push(("html", self.html.clone()));
+        fields.push(("width", self.width.to_string()));
+        fields.push(("height", self.height.to_string()));
+        let elements: String = fields
+            .iter()
+            .map(|(name, value)| format!("\n  <{name}>{value}</{name}>", name = name, value = escape_html(value)))
+            .collect();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<oembed>{}\n</oembed>\n",
+            elements
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::blog::BlogSeo;
+
+    #[test]
+    fn resolve_post_urls() {
+        let site = SiteConfig { base_url: "https://blog.example.com".to_string(), ..SiteConfig::default() };
+        assert_eq!(Some(3), resolve("https://blog.example.com/blogs/3", &site));
+        assert_eq!(Some(3), resolve("http://blog.example.com/blogs/3/?utm=x", &site));
+        assert_eq!(Some(3), resolve("https://blog.example.com/blogs/3.html", &site));
+        assert_eq!(None, resolve("https://other.example.com/blogs/3", &site));
+        assert_eq!(None, resolve("https://blog.example.com/tags/3", &site));
+        assert_eq!(None, resolve("/blogs/3", &site));
+    }
+
+    #[test]
+    fn build_rich_embed_within_max_size() {
+        let site = SiteConfig::default();
+        let mut blog = BlogEntity::new(1, "a <b>".to_string(), "some **text**".to_string(), vec![]);
+        blog.seo = BlogSeo { image: Some("https://example.com/a.png".to_string()), ..BlogSeo::default() };
+
+        let embed = Embed::new(&blog, &site, None, None);
+        assert_eq!((600, 200), (embed.width, embed.height));
+        assert_eq!((Some(1200), Some(630)), (embed.thumbnail_width, embed.thumbnail_height));
+        assert!(embed.html.contains(r#"<a href="http://localhost:3001/blogs/1">a &lt;b&gt;</a><p>some text</p>"#));
+
+        let embed = Embed::new(&blog, &site, Some(300), Some(100));
+        assert_eq!((300, 100), (embed.width, embed.height));
+        assert_eq!((Some(191), Some(100)), (embed.thumbnail_width, embed.thumbnail_height));
+
+        let xml = embed.to_xml();
+        assert!(xml.contains("<type>rich</type>"));
+        assert!(xml.contains("<title>a &lt;b&gt;</title>"));
+        assert!(xml.contains("<html>&lt;blockquote"));
+
+        let links = discovery_links(&blog, &site);
+        assert!(links.contains(
+            r#"href="http://localhost:3001/oembed?url=http%3A%2F%2Flocalhost%3A3001%2Fblogs%2F1&amp;format=json""#
+        ));
+        assert!(links.contains(r#"type="text/xml+oembed""#));
+    }
+}
@@ -0,0 +1,522 @@
+use schemars::{gen::SchemaSettings, schema::Schema};
+use serde_json::{json, Map, Value};
+
+use crate::api;
+use crate::auth::{self, TokenResponse};
+use crate::handlers::{
+    auth::{LoginForm, RefreshTokenForm},
+    blog::BulkBlog,
+    tag::CreateTag,
+};
+use crate::repositories::{
+    blog::{BlogEntity, BlogField, BulkOutcome, CreateBlog, UpdateBlog},
+    tag::Tag,
+    webmention::WebmentionEntity,
+};
+use crate::seo::BlogMeta;
+
+fn json_content(schema: &Schema) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+fn id_parameter() -> Value {
+    json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "integer", "format": "int32" }
+    })
+}
+
+fn idempotency_key_parameter() -> Value {
+    json!({
+        "name": "Idempotency-Key",
+        "in": "header",
+        "required": false,
+        "description": "同じキーで再送されたリクエストには最初のレスポンスを返す",
+        "schema": { "type": "string", "minLength": 1, "maxLength": 255 }
+    })
+}
+
+fn conditional_parameters() -> Vec<Value> {
+    vec![
+        json!({ "name": "If-None-Match", "in": "header", "required": false, "schema": { "type": "string" } }),
+        json!({ "name": "If-Modified-Since", "in": "header", "required": false, "schema": { "type": "string" } }),
+    ]
+}
+
+fn fields_parameters() -> Vec<Value> {
+    let names: Vec<&str> = BlogField::ALL.iter().map(BlogField::name).collect();
+    vec![
+        json!({
+            "name": "fields",
+            "in": "query",
+            "required": false,
+            "description": format!("カンマ区切りで返すフィールドを指定する ({})", names.join(", ")),
+            "schema": { "type": "string" }
+        }),
+        json!({
+            "name": "include",
+            "in": "query",
+            "required": false,
+            "description": "デフォルトに追加するフィールド (例: body)",
+            "schema": { "type": "string" }
+        }),
+    ]
+}
+
+fn ok_response(description: &str, schema: &Schema) -> Value {
+    json!({ "description": description, "content": json_content(schema) })
+}
+
+// 書き込みには POST /auth/login で取得したアクセストークンが要る
+fn bearer_security() -> Value {
+    json!([{ "bearerAuth": [] }])
+}
+
+fn error_response(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/responses/{}", name) })
+}
+
+// /api/v1 に登録しているエンドポイントと、アクセストークンを発行する /auth の OpenAPI 3.1 ドキュメント
+pub fn document() -> Value {
+    let mut generator = SchemaSettings::draft2019_09()
+        .with(|settings| settings.definitions_path = "#/components/schemas/".to_string())
+        .into_generator();
+    let blog = generator.subschema_for::<BlogEntity>();
+    let blogs = generator.subschema_for::<Vec<BlogEntity>>();
+    let create_blog = generator.subschema_for::<CreateBlog>();
+    let update_blog = generator.subschema_for::<UpdateBlog>();
+    let bulk_blog = generator.subschema_for::<BulkBlog>();
+    let bulk_outcome = generator.subschema_for::<BulkOutcome>();
+    let blog_meta = generator.subschema_for::<BlogMeta>();
+    let tag = generator.subschema_for::<Tag>();
+    let tags = generator.subschema_for::<Vec<Tag>>();
+    let create_tag = generator.subschema_for::<CreateTag>();
+    let webmentions = generator.subschema_for::<Vec<WebmentionEntity>>();
+    let login_form = generator.subschema_for::<LoginForm>();
+    let refresh_token_form = generator.subschema_for::<RefreshTokenForm>();
+    let token_response = generator.subschema_for::<TokenResponse>();
+
+    let mut all_blog_parameters = fields_parameters();
+    all_blog_parameters.extend(conditional_parameters());
+    let mut get_blog_parameters = vec![id_parameter()];
+    get_blog_parameters.extend(fields_parameters());
+    get_blog_parameters.extend(conditional_parameters());
+
+    let v1_paths = json!({
+        "/blogs": {
+            "get": {
+                "operationId": "allBlog",
+                "summary": "ブログ一覧",
+                "parameters": all_blog_parameters,
+                "responses": {
+                    "200": ok_response("ブログ一覧。デフォルトでは body を含まない", &blogs),
+                    "304": error_response("NotModified")
+                }
+            },
+            "post": {
+                "operationId": "createBlog",
+                "summary": "ブログを作成する",
+                "parameters": [idempotency_key_parameter()],
+                "requestBody": { "required": true, "content": json_content(&create_blog) },
+                "security": bearer_security(),
+                "responses": {
+                    "201": ok_response("作成したブログ", &blog),
+                    "400": error_response("BadRequest"),
+                    "401": error_response("Unauthorized"),
+                    "409": error_response("Conflict"),
+                    "422": error_response("UnprocessableEntity")
+                }
+            }
+        },
+        "/blogs/bulk": {
+            "post": {
+                "operationId": "bulkBlog",
+                "summary": "複数のブログをまとめて操作する",
+                "requestBody": { "required": true, "content": json_content(&bulk_blog) },
+                "security": bearer_security(),
+                "responses": {
+                    "200": ok_response("操作ごとの結果", &bulk_outcome),
+                    "400": error_response("BadRequest"),
+                    "401": error_response("Unauthorized"),
+                    "422": ok_response("all_or_nothing で失敗した操作があった", &bulk_outcome)
+                }
+            }
+        },
+        "/blogs/{id}": {
+            "get": {
+                "operationId": "findBlog",
+                "summary": "ブログを取得する",
+                "description": "Accept に応じて JSON, front matter 付きの Markdown, HTML を返す",
+                "parameters": get_blog_parameters,
+                "responses": {
+                    "200": {
+                        "description": "ブログ。fields を指定した場合はそのフィールドだけを含む (JSON のみ)",
+                        "content": {
+                            "application/json": { "schema": blog },
+                            "text/markdown": { "schema": { "type": "string" } },
+                            "text/html": { "schema": { "type": "string" } }
+                        }
+                    },
+                    "304": error_response("NotModified"),
+                    "404": error_response("NotFound"),
+                    "406": error_response("NotAcceptable")
+                }
+            },
+            "patch": {
+                "operationId": "updateBlog",
+                "summary": "ブログを部分更新する (JSON Merge Patch)",
+                "parameters": [id_parameter()],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/merge-patch+json": { "schema": update_blog },
+                        "application/json": { "schema": update_blog }
+                    }
+                },
+                "security": bearer_security(),
+                "responses": {
+                    "200": ok_response("更新したブログ", &blog),
+                    "400": error_response("BadRequest"),
+                    "401": error_response("Unauthorized"),
+                    "404": error_response("NotFound"),
+                    "415": error_response("UnsupportedMediaType")
+                }
+            },
+            "put": {
+                "operationId": "replaceBlog",
+                "summary": "ブログを置き換える",
+                "parameters": [id_parameter()],
+                "requestBody": { "required": true, "content": json_content(&create_blog) },
+                "security": bearer_security(),
+                "responses": {
+                    "200": ok_response("置き換えたブログ", &blog),
+                    "400": error_response("BadRequest"),
+                    "401": error_response("Unauthorized"),
+                    "404": error_response("NotFound")
+                }
+            },
+            "delete": {
+                "operationId": "deleteBlog",
+                "summary": "ブログを削除する",
+                "parameters": [id_parameter()],
+                "security": bearer_security(),
+                "responses": {
+                    "204": { "description": "削除した" },
+                    "401": error_response("Unauthorized"),
+                    "404": error_response("NotFound")
+                }
+            }
+        },
+        "/blogs/{id}/meta": {
+            "get": {
+                "operationId": "findBlogMeta",
+                "summary": "ブログの OGP, Twitter カード, JSON-LD を取得する",
+                "parameters": [id_parameter()],
+                "responses": {
+                    "200": ok_response("head に埋め込むメタデータ", &blog_meta),
+                    "304": error_response("NotModified"),
+                    "404": error_response("NotFound")
+                }
+            }
+        },
+        "/blogs/{id}/mentions": {
+            "get": {
+                "operationId": "findBlogMentions",
+                "summary": "ブログへの確認済みの Webmention を取得する",
+                "parameters": [id_parameter()],
+                "responses": {
+                    "200": ok_response("source が target へのリンクを含むと確認できた Webmention", &webmentions),
+                    "404": error_response("NotFound")
+                }
+            }
+        },
+        "/blogs/{id}.md": {
+            "get": {
+                "operationId": "findBlogMarkdown",
+                "summary": "ブログを front matter 付きの Markdown で取得する",
+                "parameters": [id_parameter()],
+                "responses": {
+                    "200": { "description": "Markdown", "content": { "text/markdown": { "schema": { "type": "string" } } } },
+                    "304": error_response("NotModified"),
+                    "404": error_response("NotFound")
+                }
+            }
+        },
+        "/blogs/{id}.html": {
+            "get": {
+                "operationId": "findBlogHtml",
+                "summary": "ブログを HTML のページで取得する",
+                "parameters": [id_parameter()],
+                "responses": {
+                    "200": { "description": "HTML", "content": { "text/html": { "schema": { "type": "string" } } } },
+                    "304": error_response("NotModified"),
+                    "404": error_response("NotFound")
+                }
+            }
+        },
+        "/export": {
+            "get": {
+                "operationId": "exportArchive",
+                "summary": "全てのブログを front matter 付きの Markdown にして tar.gz で取得する",
+                "description": "posts/{id}-{slug}.md, tags.yaml, manifest.json を含むアーカイブをストリーミングで返す",
+                "security": bearer_security(),
+                "responses": {
+                    "200": {
+                        "description": "tar.gz のアーカイブ",
+                        "content": { "application/gzip": { "schema": { "type": "string", "contentEncoding": "binary" } } }
+                    },
+                    "401": error_response("Unauthorized")
+                }
+            }
+        },
+        "/tags": {
+            "get": {
+                "operationId": "allTag",
+                "summary": "タグ一覧",
+                "parameters": conditional_parameters(),
+                "responses": {
+                    "200": ok_response("タグ一覧", &tags),
+                    "304": error_response("NotModified")
+                }
+            },
+            "post": {
+                "operationId": "createTag",
+                "summary": "タグを作成する",
+                "parameters": [idempotency_key_parameter()],
+                "requestBody": { "required": true, "content": json_content(&create_tag) },
+                "security": bearer_security(),
+                "responses": {
+                    "201": ok_response("作成したタグ", &tag),
+                    "400": error_response("BadRequest"),
+                    "401": error_response("Unauthorized"),
+                    "409": error_response("Conflict"),
+                    "422": error_response("UnprocessableEntity")
+                }
+            }
+        },
+        "/tags/{id}": {
+            "delete": {
+                "operationId": "deleteTag",
+                "summary": "タグを削除する",
+                "parameters": [id_parameter()],
+                "security": bearer_security(),
+                "responses": {
+                    "204": { "description": "削除した" },
+                    "401": error_response("Unauthorized"),
+                    "500": error_response("InternalServerError")
+                }
+            }
+        }
+    });
+
+    // /auth はバージョンを付けずに登録しているので、パスは全てサーバーのルートからにする
+    let mut paths = Map::new();
+    for (path, item) in v1_paths.as_object().into_iter().flatten() {
+        paths.insert(format!("{}{}", api::V1_PREFIX, path), item.clone());
+    }
+    paths.insert(
+        auth::LOGIN_PATH.to_string(),
+        json!({
+            "post": {
+                "operationId": "login",
+                "summary": "ユーザー名とパスワードでログインし、アクセストークンとリフレッシュトークンを取得する",
+                "requestBody": { "required": true, "content": json_content(&login_form) },
+                "responses": {
+                    "200": ok_response("発行したトークン。書き込みには access_token を Authorization: Bearer で送る", &token_response),
+                    "401": error_response("Unauthorized")
+                }
+            }
+        }),
+    );
+    paths.insert(
+        auth::REFRESH_PATH.to_string(),
+        json!({
+            "post": {
+                "operationId": "refreshToken",
+                "summary": "リフレッシュトークンで新しいトークンの組を取得する",
+                "description": "使ったリフレッシュトークンは失効する",
+                "requestBody": { "required": true, "content": json_content(&refresh_token_form) },
+                "responses": {
+                    "200": ok_response("新しく発行したトークン", &token_response),
+                    "401": error_response("Unauthorized")
+                }
+            }
+        }),
+    );
+    paths.insert(
+        auth::REVOKE_PATH.to_string(),
+        json!({
+            "post": {
+                "operationId": "revokeToken",
+                "summary": "リフレッシュトークンを失効させる (ログアウト)",
+                "requestBody": { "required": true, "content": json_content(&refresh_token_form) },
+                "responses": {
+                    "204": { "description": "失効させた。知らないトークンでも成功として扱う" }
+                }
+            }
+        }),
+    );
+
+    let mut schemas = Map::new();
+    for (name, schema) in generator.take_definitions() {
+        schemas.insert(name, json!(schema));
+    }
+    schemas.insert(
+        "ErrorMessage".to_string(),
+        json!({
+            "type": "string",
+            "description": "エラー内容 (例: \"Validation error: [title: can not be empty]\")"
+        }),
+    );
+
+    let error_message = json!({ "text/plain": { "schema": { "$ref": "#/components/schemas/ErrorMessage" } } });
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "next_blog API",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" }
+            },
+            "responses": {
+                "NotModified": { "description": "If-None-Match / If-Modified-Since に一致した" },
+                "BadRequest": { "description": "JSON の形式かバリデーションのエラー", "content": error_message },
+                "Unauthorized": { "description": "アクセストークンかログインの情報が無いか、不正か期限切れ" },
+                "NotFound": { "description": "指定した id が存在しない" },
+                "Conflict": { "description": "同じ Idempotency-Key のリクエストが処理中" },
+                "UnprocessableEntity": { "description": "Idempotency-Key が別の内容で再利用された" },
+                "NotAcceptable": { "description": "Accept か拡張子で指定された形式に対応していない" },
+                "UnsupportedMediaType": { "description": "Content-Type が対応していない" },
+                "InternalServerError": { "description": "予期しないエラー" }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::create_app;
+    use crate::repositories::{
+        blog::{test_utils::BlogRepositoryForMemory, BlogRepository, BlogSeo, BlogStatus},
+        follower::test_utils::FollowerRepositoryForMemory,
+        idempotency::test_utils::IdempotencyRepositoryForMemory,
+        redirect::test_utils::RedirectRepositoryForMemory,
+        tag::{test_utils::TagRepositoryForMemory, TagRepository},
+        user::test_utils::UserRepositoryForMemory,
+        webmention::test_utils::WebmentionRepositoryForMemory,
+    };
+    use axum::{
+        body::Body,
+        http::{Method, Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    // id=1 のブログとタグがあるので、404 と 405 はルートが無いときだけ返る
+    async fn seeded_app() -> axum::Router {
+        let tag = Tag { id: 1, name: "tag".to_string() };
+        let blog_repository = BlogRepositoryForMemory::new(vec![tag.clone()]);
+        blog_repository
+            .create(CreateBlog {
+                title: "title".to_string(),
+                body: "body".to_string(),
+                tags: vec![tag.id],
+                status: BlogStatus::Published,
+                seo: BlogSeo::default(),
+                slug: None,
+                published_at: None,
+            })
+            .await
+            .unwrap();
+        let tag_repository = TagRepositoryForMemory::new();
+        tag_repository.create(tag.name).await.unwrap();
+        create_app(
+            blog_repository,
+            tag_repository,
+            IdempotencyRepositoryForMemory::new(),
+            RedirectRepositoryForMemory::new(),
+            WebmentionRepositoryForMemory::new(),
+            FollowerRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            AppConfig::default(),
+        )
+    }
+
+    // ドキュメントに書いたエンドポイントが全てルーターに存在することを確認する
+    #[tokio::test]
+    async fn document_matches_routes() {
+        let document = document();
+
+        for (path, operations) in document["paths"].as_object().unwrap() {
+            for method in operations.as_object().unwrap().keys() {
+                let uri = path.replace("{id}", "1");
+                let method = Method::from_bytes(method.to_uppercase().as_bytes()).unwrap();
+                assert!(is_routed(&method, &uri).await, "{} {} is not routed", method, uri);
+            }
+        }
+    }
+
+    // 逆に、ルーターにあるエンドポイントが全てドキュメントに書かれていることを確認する
+    #[tokio::test]
+    async fn routes_are_documented() {
+        let document = document();
+        let mut paths: Vec<String> = api::v1::routes::<
+            BlogRepositoryForMemory,
+            TagRepositoryForMemory,
+            IdempotencyRepositoryForMemory,
+            WebmentionRepositoryForMemory,
+        >()
+        .into_iter()
+        .chain(api::v1::static_routes::<BlogRepositoryForMemory>())
+        .map(|(path, _)| format!("{}{}", api::V1_PREFIX, path))
+        .collect();
+        paths.extend([auth::LOGIN_PATH, auth::REFRESH_PATH, auth::REVOKE_PATH].map(String::from));
+
+        for path in paths {
+            let documented = path.replace(":id", "{id}");
+            let uri = path.replace(":id", "1");
+            for method in [Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+                if !is_routed(&method, &uri).await {
+                    continue;
+                }
+                let operation = &document["paths"][&documented][method.as_str().to_lowercase()];
+                assert!(operation.is_object(), "{} {} is not documented", method, documented);
+            }
+        }
+    }
+
+    async fn is_routed(method: &Method, uri: &str) -> bool {
+        let req = Request::builder()
+            .uri(uri)
+            .method(method.clone())
+            .body(Body::empty())
+            .unwrap();
+        let res = seeded_app().await.oneshot(req).await.unwrap();
+        res.status() != StatusCode::NOT_FOUND && res.status() != StatusCode::METHOD_NOT_ALLOWED
+    }
+
+    // ドキュメントのページは CDN などの外部のスクリプトを読まない
+    #[test]
+    fn docs_page_is_self_contained() {
+        let html = crate::handlers::docs::render(&document());
+        assert!(!html.contains("<script"));
+        assert!(html.contains("id=\"bulkBlog\""));
+        assert!(html.contains("href=\"#schema-CreateBlog\""));
+    }
+
+    #[test]
+    fn document_includes_validation_constraints() {
+        let document = document();
+        let title = &document["components"]["schemas"]["CreateBlog"]["properties"]["title"];
+        assert_eq!(1, title["minLength"]);
+        assert_eq!(100, title["maxLength"]);
+        assert_eq!("3.1.0", document["openapi"]);
+    }
+}
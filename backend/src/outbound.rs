@@ -0,0 +1,212 @@
+use anyhow::anyhow;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    redirect::Policy,
+    Client, RequestBuilder, Response, Url,
+};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::OutboundConfig;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REDIRECTS: usize = 5;
+
+// 外から渡された URL を取得するクライアント。最初の URL もリダイレクト先も、
+// 公開アドレスの http(s) でなければ繋がない (OutboundConfig)
+#[derive(Debug, Clone)]
+pub struct OutboundClient {
+    client: Client,
+    allow_local: bool,
+}
+
+impl OutboundClient {
+    pub fn new(config: &OutboundConfig) -> Self {
+        let allow_local = config.allow_local;
+        let builder = Client::builder()
+            .timeout(TIMEOUT)
+            .redirect(Policy::custom(move |attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    return attempt.error("too many redirects");
+                }
+                match check_url(attempt.url(), allow_local) {
+                    Ok(()) => attempt.follow(),
+                    Err(e) => attempt.error(e),
+                }
+            }))
+            .user_agent(concat!("next_blog/", env!("CARGO_PKG_VERSION")));
+        // ホスト名は接続するときに解決したアドレスで確かめる。確かめた後で DNS の答えが変わっても内部には繋がない
+        let builder = match allow_local {
+            true => builder,
+            false => builder.dns_resolver(Arc::new(PublicResolver)),
+        };
+        OutboundClient {
+            client: builder.build().expect("fail build http client"),
+            allow_local,
+        }
+    }
+
+    pub fn allow_local(&self) -> bool {
+        self.allow_local
+    }
+
+    pub fn get(&self, url: &str) -> anyhow::Result<RequestBuilder> {
+        Ok(self.client.get(self.checked(url)?))
+    }
+
+    pub fn post(&self, url: &str) -> anyhow::Result<RequestBuilder> {
+        Ok(self.client.post(self.checked(url)?))
+    }
+
+    fn checked(&self, url: &str) -> anyhow::Result<Url> {
+        let url = Url::parse(url)?;
+        check_url(&url, self.allow_local).map_err(|e| anyhow!(e))?;
+        Ok(url)
+    }
+}
+
+// http(s) で、ホストが IP アドレスなら公開アドレスであること。名前のホストは PublicResolver が確かめる
+pub fn check_url(url: &Url, allow_local: bool) -> Result<(), String> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(format!("{} is not an http(s) URL", url));
+    }
+    if allow_local {
+        return Ok(());
+    }
+    let public = match url.host_str() {
+        Some(host) => match host.trim_start_matches('[').trim_end_matches(']').parse() {
+            Ok(ip) => is_public(ip),
+            Err(_) => true,
+        },
+        None => false,
+    };
+    match public {
+        true => Ok(()),
+        false => Err(format!("{} is not a public address", url)),
+    }
+}
+
+// ループバック、プライベート、リンクローカル (クラウドのメタデータ) などの、インターネットから届かないアドレスを除く
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64)
+                || (a == 192 && b == 0 && c == 0)
+                || (a == 198 && (b & 0xfe) == 18)
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+            let segments = ip.segments();
+            // NAT64 (64:ff9b::/96) は埋め込まれた IPv4 アドレスで判定する
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [a, b] = segments[6].to_be_bytes();
+                let [c, d] = segments[7].to_be_bytes();
+                return is_public(IpAddr::from([a, b, c, d]));
+            }
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+        }
+    }
+}
+
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: hyper::client::connect::dns::Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} does not resolve to a public address", name.as_str()).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+// 巨大なレスポンスで止まらないよう、本文は max バイトまでで打ち切る
+pub async fn read_body(response: &mut Response, max: usize) -> reqwest::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= max {
+            body.truncate(max);
+            break;
+        }
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::webmention::test_utils::serve;
+    use axum::{routing::get, Router};
+
+    #[test]
+    fn reject_private_addresses() {
+        for ip in ["8.8.8.8", "93.184.216.34", "2606:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+
+        let check = |url: &str, allow_local: bool| check_url(&Url::parse(url).unwrap(), allow_local);
+        assert!(check("https://example.com/post", false).is_ok());
+        assert!(check("http://169.254.169.254/latest/meta-data", false).is_err());
+        assert!(check("http://[::1]:3000/", false).is_err());
+        assert!(check("http://2130706433/", false).is_err());
+        assert!(check("file:///etc/passwd", false).is_err());
+        assert!(check("http://127.0.0.1:3000/", true).is_ok());
+        assert!(check("gopher://127.0.0.1/", true).is_err());
+    }
+
+    #[tokio::test]
+    async fn refuse_to_connect_to_local_server() {
+        let addr = serve(Router::new().route("/", get(|| async { "local" })));
+
+        let client = OutboundClient::new(&OutboundConfig::default());
+        assert!(client.get(&format!("http://{}/", addr)).is_err());
+        // 名前で指定しても、解決したアドレスがループバックなら繋がない
+        let req = client.get(&format!("http://localhost:{}/", addr.port())).unwrap();
+        assert!(req.send().await.is_err());
+
+        let client = OutboundClient::new(&OutboundConfig { allow_local: true });
+        let res = client.get(&format!("http://{}/", addr)).unwrap().send().await.unwrap();
+        assert!(res.status().is_success());
+    }
+}
@@ -0,0 +1,239 @@
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+
+use crate::config::SiteConfig;
+use crate::oembed;
+use crate::webmention;
+use crate::repositories::blog::{BlogEntity, BlogStatus};
+
+// YAML のダブルクォート文字列は JSON の文字列と互換なので serde_json でエスケープする
+pub fn yaml_string(value: &str) -> String {
+    serde_json::Value::String(value.to_string()).to_string()
+}
+
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// 本文の Markdown から記法を取り除いた先頭 length 文字の抜粋
+pub fn excerpt(markdown: &str, length: usize) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(markdown, Options::all()) {
+        match event {
+            Event::Text(value) | Event::Code(value) => text.push_str(&value),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+    }
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(length) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+// 保存済みの slug があればそれを、無ければタイトルから作る。英数字が無ければ post-{id} にする
+pub fn slug(blog: &BlogEntity) -> String {
+    if let Some(slug) = &blog.slug {
+        return slug.clone();
+    }
+    match slugify(&blog.title) {
+        Some(slug) => slug,
+        None => format!("post-{}", blog.id),
+    }
+}
+
+// 英数字を小文字にして、それ以外をハイフンにまとめた URL 用の文字列
+pub fn slugify(text: &str) -> Option<String> {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.len() >= 60 {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    match slug.is_empty() {
+        true => None,
+        false => Some(slug.to_string()),
+    }
+}
+
+// Hugo / Jekyll でそのまま読める front matter 付きの Markdown
+pub fn markdown(blog: &BlogEntity) -> String {
+    markdown_with(blog, &[])
+}
+
+// extra は front matter の先頭に追加する (値は YAML として書き出し済みのもの)
+pub fn markdown_with(blog: &BlogEntity, extra: &[(&str, String)]) -> String {
+    let extra: String = extra.iter().map(|(key, value)| format!("{}: {}\n", key, value)).collect();
+    let tags: Vec<String> = blog.tags.iter().map(|tag| yaml_string(&tag.name)).collect();
+    format!(
+        "---\n{}title: {}\ntags: [{}]\ndate: {}\nlastmod: {}\ndraft: {}\n---\n\n{}\n",
+        extra,
+        yaml_string(&blog.title),
+        tags.join(", "),
+        blog.created_at.to_rfc3339(),
+        blog.updated_at.to_rfc3339(),
+        blog.status == BlogStatus::Draft,
+        blog.body.trim_end(),
+    )
+}
+
+// 本文中の生の HTML はそのまま出さずにテキストとしてエスケープする。
+// リンクと画像の URL は javascript: などを落とす
+pub fn markdown_to_html(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, Options::all()).map(|event| match event {
+        Event::Html(raw) => Event::Text(raw),
+        Event::Start(tag) => Event::Start(sanitize_tag(tag)),
+        Event::End(tag) => Event::End(sanitize_tag(tag)),
+        event => event,
+    });
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, parser);
+    rendered
+}
+
+fn sanitize_tag(tag: Tag) -> Tag {
+    match tag {
+        Tag::Link(link_type, url, title) => Tag::Link(link_type, sanitize_url(url), title),
+        Tag::Image(link_type, url, title) => Tag::Image(link_type, sanitize_url(url), title),
+        tag => tag,
+    }
+}
+
+// http, https, mailto と相対 URL だけ残し、それ以外は空にする
+fn sanitize_url(url: CowStr) -> CowStr {
+    // ブラウザはスキーム中のタブや改行を無視するので、取り除いてから判定する
+    let normalized: String = url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .collect();
+    let allowed = match normalized.find([':', '/', '?', '#']) {
+        Some(end) if normalized[end..].starts_with(':') => matches!(
+            normalized[..end].to_ascii_lowercase().as_str(),
+            "http" | "https" | "mailto"
+        ),
+        _ => true,
+    };
+    if allowed {
+        url
+    } else {
+        CowStr::Borrowed("")
+    }
+}
+
+pub fn html_page(blog: &BlogEntity, site: &SiteConfig) -> String {
+    let tags: Vec<String> = blog
+        .tags
+        .iter()
+        .map(|tag| format!("<li>{}</li>", escape_html(&tag.name)))
+        .collect();
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="ja">
+  <head>
+    <meta charset="utf-8" />
+    <title>{title}</title>
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+    {discovery}
+  </head>
+  <body>
+    <article>
+      <h1>{title}</h1>
+      <time datetime="{date}">{date}</time>
+      <ul class="tags">{tags}</ul>
+      {body}
+    </article>
+  </body>
+</html>
+"#,
+        title = escape_html(&blog.title),
+        date = blog.created_at.to_rfc3339(),
+        tags = tags.join(""),
+        body = markdown_to_html(&blog.body),
+        discovery = match blog.status {
+            BlogStatus::Published => format!(
+                "{}\n{}",
+                oembed::discovery_links(blog, site),
+                webmention::discovery_link(site)
+            )
+            .replace('\n', "\n    "),
+            BlogStatus::Draft => String::new(),
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::tag::Tag;
+
+    #[test]
+    fn render_markdown_and_html() {
+        let blog = BlogEntity::new(
+            1,
+            "say \"hello\" <world>".to_string(),
+            "# heading\n\n<script>alert(1)</script>\n".to_string(),
+            vec![Tag::new(1, "rust".to_string())],
+        );
+
+        let markdown = markdown(&blog);
+        assert!(markdown.starts_with("---\ntitle: \"say \\\"hello\\\" <world>\"\ntags: [\"rust\"]\n"));
+        assert!(markdown.contains("draft: false\n---\n\n# heading\n"));
+
+        let html = html_page(&blog, &SiteConfig::default());
+        assert!(html.contains("<title>say &quot;hello&quot; &lt;world&gt;</title>"));
+        assert!(html.contains(r#"<link rel="alternate" type="application/json+oembed""#));
+        assert!(html.contains(r#"<link rel="webmention" href="http://localhost:3001/webmention" />"#));
+        assert!(html.contains("<h1>heading</h1>"));
+        assert!(!html.contains("<script>"));
+    }
+
+    #[test]
+    fn drop_unsafe_link_destinations() {
+        let html = markdown_to_html(
+            "[a](javascript:alert(1)) [b](JaVaScript&#58;alert(1)) ![c](data:text/html,x) <vbscript:x>",
+        );
+        assert!(html.contains(r#"<a href="">a</a>"#));
+        assert!(html.contains(r#"<a href="">b</a>"#));
+        assert!(html.contains(r#"<img src="" alt="c" />"#));
+        assert!(html.contains(r#"<a href="">vbscript:x</a>"#));
+
+        let html = markdown_to_html("[a](https://example.com) [b](/blogs/1) [c](mailto:me@example.com) [d](#top)");
+        assert!(html.contains(r#"<a href="https://example.com">a</a>"#));
+        assert!(html.contains(r#"<a href="/blogs/1">b</a>"#));
+        assert!(html.contains(r#"<a href="mailto:me@example.com">c</a>"#));
+        assert!(html.contains(r##"<a href="#top">d</a>"##));
+    }
+
+    #[test]
+    fn slug_from_title() {
+        let blog = |title: &str| BlogEntity::new(7, title.to_string(), String::new(), vec![]);
+        assert_eq!("hello-axum-0-4", slug(&blog("Hello, Axum 0.4!")));
+        assert_eq!("rust", slug(&blog("Rust 入門")));
+        assert_eq!("post-7", slug(&blog("日本語のタイトル")));
+        let stored = BlogEntity { slug: Some("custom".to_string()), ..blog("Hello") };
+        assert_eq!("custom", slug(&stored));
+    }
+
+    #[test]
+    fn excerpt_strips_markdown() {
+        assert_eq!("heading some code and text", excerpt("# heading\n\nsome `code`\nand *text*", 100));
+        assert_eq!("あいう…", excerpt("あいうえお", 3));
+    }
+}
@@ -1,14 +1,36 @@
 pub mod blog;
+pub mod follower;
+pub mod idempotency;
+pub mod import;
+pub mod redirect;
 pub mod tag;
+pub mod user;
+pub mod webmention;
 
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
-enum RepositoryError {
+pub enum RepositoryError {
     #[error("Unexpected Error: [{0}]")]
     Unexpected(String),
     #[error("NotFound, id is {0}")]
     NotFound(i32),
     #[error("Duplicate data, id is {0}")]
     Duplicate(i32),
-}
\ No newline at end of file
+}
+
+// 一覧の Last-Modified に使う。行の削除でも進むよう、トリガーが変更の度に記録している
+async fn collection_changed_at(pool: &PgPool, name: &str) -> anyhow::Result<Option<DateTime<Utc>>> {
+    let changed_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+        r#"
+        select changed_at from collection_changes where name=$1
+        "#
+    )
+    .bind(name)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(changed_at)
+}
@@ -1,15 +1,20 @@
-use std::vec;
+use std::{collections::HashMap, vec};
 
 use anyhow::Ok;
 use axum::async_trait;
-use serde::{Deserialize, Serialize};
-use validator::Validate;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+use validator::{Validate, ValidationError};
 use sqlx::{
+    Acquire,
     FromRow,
+    PgConnection,
     PgPool
 };
 
 use super::{
+    collection_changed_at,
     RepositoryError,
     tag::Tag
 };
@@ -19,48 +24,471 @@ use super::{
 #[async_trait]
 pub trait BlogRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
     async fn create(&self, payload: CreateBlog) -> anyhow::Result<BlogEntity>;
-    async fn find(&self, id: i32) -> anyhow::Result<BlogEntity>;
-    async fn all(&self) -> anyhow::Result<Vec<BlogEntity>>;
+    async fn find_with(&self, id: i32, fields: &BlogFields) -> anyhow::Result<BlogEntity>;
+    async fn all_with(&self, fields: &BlogFields) -> anyhow::Result<Vec<BlogEntity>>;
     async fn update(&self, id: i32, payload: UpdateBlog) -> anyhow::Result<BlogEntity>;
     async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    async fn bulk(&self, operations: Vec<BulkOperation>, mode: BulkMode) -> anyhow::Result<BulkOutcome>;
+    async fn tags_for(&self, blog_ids: &[i32]) -> anyhow::Result<HashMap<i32, Vec<Tag>>>;
+    async fn related(&self, id: i32, limit: i64) -> anyhow::Result<Vec<BlogEntity>>;
+    async fn tag_counts(&self) -> anyhow::Result<Vec<TagCount>>;
+    async fn recent_published(&self, limit: i64, offset: i64, tag: Option<i32>) -> anyhow::Result<Vec<BlogEntity>>;
+    // id の降順で status を絞り込んだ1ページ分
+    async fn page_with(&self, fields: &BlogFields, status: Option<BlogStatus>, limit: i64, offset: i64) -> anyhow::Result<Vec<BlogEntity>>;
+    // 一覧が最後に変わった時刻。削除でも進む
+    async fn last_modified(&self) -> anyhow::Result<Option<DateTime<Utc>>>;
+
+    async fn find(&self, id: i32) -> anyhow::Result<BlogEntity> {
+        self.find_with(id, &BlogFields::all()).await
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<BlogEntity>> {
+        self.all_with(&BlogFields::all()).await
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlogField {
+    Id,
+    Title,
+    Slug,
+    Body,
+    Tags,
+    Status,
+    Seo,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl BlogField {
+    pub const ALL: [BlogField; 9] = [
+        BlogField::Id,
+        BlogField::Title,
+        BlogField::Slug,
+        BlogField::Body,
+        BlogField::Tags,
+        BlogField::Status,
+        BlogField::Seo,
+        BlogField::CreatedAt,
+        BlogField::UpdatedAt,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlogField::Id => "id",
+            BlogField::Title => "title",
+            BlogField::Slug => "slug",
+            BlogField::Body => "body",
+            BlogField::Tags => "tags",
+            BlogField::Status => "status",
+            BlogField::Seo => "seo",
+            BlogField::CreatedAt => "created_at",
+            BlogField::UpdatedAt => "updated_at",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        BlogField::ALL.iter().copied().find(|field| field.name() == name)
+    }
+}
+
+// レスポンスに含めるフィールド。body と tags は SQL でも読まないようにする
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlogFields(Vec<BlogField>);
+
+impl BlogFields {
+    pub fn all() -> Self {
+        BlogFields(BlogField::ALL.to_vec())
+    }
+
+    pub fn without(field: BlogField) -> Self {
+        BlogFields(BlogField::ALL.iter().copied().filter(|f| *f != field).collect())
+    }
+
+    pub fn new(fields: Vec<BlogField>) -> Self {
+        BlogFields(fields)
+    }
+
+    pub fn include(&mut self, field: BlogField) {
+        if !self.contains(field) {
+            self.0.push(field);
+        }
+    }
+
+    pub fn contains(&self, field: BlogField) -> bool {
+        self.0.contains(&field)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.0.iter().map(BlogField::name)
+    }
+
+    fn select(&self, condition: &str) -> String {
+        let body = if self.contains(BlogField::Body) {
+            "blogs.body"
+        } else {
+            "''::text as body"
+        };
+        let seo = if self.contains(BlogField::Seo) {
+            "blogs.meta_description, blogs.canonical_url, blogs.noindex, blogs.social_image"
+        } else {
+            "null::text as meta_description, null::text as canonical_url, false as noindex, null::text as social_image"
+        };
+        let (tag_columns, joins) = if self.contains(BlogField::Tags) {
+            (
+                "tags.id as label_id, tags.name as tag_name",
+                r#"
+                    left outer join blog_tags tl on blogs.id = tl.blog_id
+                    left outer join tags on tags.id = tl.label_id"#,
+            )
+        } else {
+            ("null::integer as label_id, null::text as tag_name", "")
+        };
+        format!(
+            r#"
+            select blogs.id, blogs.title, blogs.slug, {}, blogs.status, {}, blogs.created_at, blogs.updated_at, {}
+            from blogs{}
+            {}
+            "#,
+            body, seo, tag_columns, joins, condition
+        )
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
 pub struct BlogWithTagFromRow {
     pub id: i32,
     pub title: String,
+    pub slug: Option<String>,
     pub body: String,
+    pub status: BlogStatus,
+    pub meta_description: Option<String>,
+    pub canonical_url: Option<String>,
+    pub noindex: bool,
+    pub social_image: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
     pub label_id: Option<i32>,
     pub tag_name: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+struct BlogTagFromRow {
+    blog_id: i32,
+    id: i32,
+    name: String,
+}
+
+// タグごとの記事数
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct TagCount {
+    pub id: i32,
+    pub name: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow, JsonSchema)]
 pub struct BlogEntity {
     pub id: i32,
     pub title: String,
+    pub slug: Option<String>,
     pub body: String,
     pub tags: Vec<Tag>,
+    pub status: BlogStatus,
+    pub seo: BlogSeo,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// 検索エンジンと SNS のシェア向けの設定。空ならタイトルや本文から補う
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default, Validate, JsonSchema)]
+pub struct BlogSeo {
+    #[validate(length(max=300, message="Over text length"))]
+    pub description: Option<String>,
+    #[validate(url(message="must be an absolute URL"))]
+    pub canonical_url: Option<String>,
+    #[serde(default)]
+    pub noindex: bool,
+    #[validate(url(message="must be an absolute URL"))]
+    pub image: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type, JsonSchema)]
+#[sqlx(type_name = "blog_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum BlogStatus {
+    Draft,
+    Published,
+}
+
+impl Default for BlogStatus {
+    fn default() -> Self {
+        BlogStatus::Published
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, JsonSchema)]
 pub struct CreateBlog {
     #[validate(length(min=1, message="can not be empty"))]
     #[validate(length(max=100, message="Over text length"))]
     pub title: String,
     pub body: String,
     pub tags: Vec<i32>,
+    #[serde(default)]
+    pub status: BlogStatus,
+    #[serde(default)]
+    #[validate]
+    pub seo: BlogSeo,
+    #[serde(default)]
+    #[validate(custom = "validate_slug")]
+    pub slug: Option<String>,
+    // 移行などで過去の日付で作成する場合に指定する。省略時は現在時刻
+    #[serde(default)]
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+// URL に使うので小文字の英数字とハイフンだけにする
+fn validate_slug(slug: &str) -> Result<(), ValidationError> {
+    let valid = !slug.is_empty()
+        && slug.len() <= 100
+        && !slug.starts_with('-')
+        && !slug.ends_with('-')
+        && slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    match valid {
+        true => Result::Ok(()),
+        false => Err(ValidationError::new("slug must be lowercase letters, digits and hyphens")),
+    }
 }
 
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+// JSON Merge Patch (RFC 7386) として扱う。
+// None はフィールド無し (変更しない)、Some(None) は null (クリアする)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, Default, JsonSchema)]
+#[validate(schema(function = "validate_update_blog"))]
 pub struct UpdateBlog {
+    #[serde(default, deserialize_with = "double_option")]
     #[validate(length(min=1, message="can not be empty"))]
     #[validate(length(max=100, message="Over text length"))]
-    pub title: Option<String>,
-    pub body: Option<String>,
+    pub title: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub body: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub tags: Option<Option<Vec<i32>>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub status: Option<Option<BlogStatus>>,
+    // null なら全てクリアし、オブジェクトならフィールドごとにマージする
+    #[serde(default, deserialize_with = "double_option")]
+    pub seo: Option<Option<UpdateBlogSeo>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub slug: Option<Option<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, Default, JsonSchema)]
+pub struct UpdateBlogSeo {
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(length(max=300, message="Over text length"))]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(url(message="must be an absolute URL"))]
+    pub canonical_url: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub noindex: Option<Option<bool>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(url(message="must be an absolute URL"))]
+    pub image: Option<Option<String>>,
+}
+
+impl From<BlogSeo> for UpdateBlogSeo {
+    fn from(seo: BlogSeo) -> Self {
+        UpdateBlogSeo {
+            description: Some(seo.description),
+            canonical_url: Some(seo.canonical_url),
+            noindex: Some(Some(seo.noindex)),
+            image: Some(seo.image),
+        }
+    }
+}
+
+impl UpdateBlogSeo {
+    fn merge(self, current: &BlogSeo) -> BlogSeo {
+        BlogSeo {
+            description: self.description.unwrap_or(current.description.clone()),
+            canonical_url: self.canonical_url.unwrap_or(current.canonical_url.clone()),
+            noindex: match self.noindex {
+                Some(noindex) => noindex.unwrap_or_default(),
+                None => current.noindex,
+            },
+            image: self.image.unwrap_or(current.image.clone()),
+        }
+    }
+}
+
+// null を Some(None) として受け取るためのデシリアライザ
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Deserialize::deserialize(deserializer).map(Some)
+}
+
+fn validate_update_blog(payload: &UpdateBlog) -> Result<(), ValidationError> {
+    if let Some(None) = payload.title {
+        return Err(ValidationError::new("title can not be null"));
+    }
+    if let Some(None) = payload.status {
+        return Err(ValidationError::new("status can not be null"));
+    }
+    if let Some(Some(slug)) = &payload.slug {
+        validate_slug(slug)?;
+    }
+    if let Some(Some(seo)) = &payload.seo {
+        if seo.validate().is_err() {
+            return Err(ValidationError::new("seo is invalid"));
+        }
+    }
+    Result::Ok(())
+}
+
+impl From<CreateBlog> for UpdateBlog {
+    fn from(payload: CreateBlog) -> Self {
+        UpdateBlog {
+            title: Some(Some(payload.title)),
+            body: Some(Some(payload.body)),
+            tags: Some(Some(payload.tags)),
+            status: Some(Some(payload.status)),
+            seo: Some(Some(payload.seo.into())),
+            slug: Some(payload.slug),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergedBlog {
+    pub title: String,
+    pub body: String,
+    pub status: BlogStatus,
+    pub seo: BlogSeo,
+    pub slug: Option<String>,
+    // None の場合はタグを変更しない
     pub tags: Option<Vec<i32>>,
 }
 
+impl UpdateBlog {
+    pub fn merge(self, current: &BlogEntity) -> MergedBlog {
+        MergedBlog {
+            title: self.title.flatten().unwrap_or(current.title.clone()),
+            body: match self.body {
+                Some(body) => body.unwrap_or_default(),
+                None => current.body.clone(),
+            },
+            status: self.status.flatten().unwrap_or(current.status),
+            seo: match self.seo {
+                Some(Some(seo)) => seo.merge(&current.seo),
+                Some(None) => BlogSeo::default(),
+                None => current.seo.clone(),
+            },
+            slug: self.slug.unwrap_or(current.slug.clone()),
+            tags: self.tags.map(Option::unwrap_or_default),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BulkOperation {
+    Delete { id: i32 },
+    AddTags { id: i32, tags: Vec<i32> },
+    RemoveTags { id: i32, tags: Vec<i32> },
+    SetStatus { id: i32, status: BlogStatus },
+}
+
+impl BulkOperation {
+    pub fn id(&self) -> i32 {
+        match self {
+            BulkOperation::Delete { id }
+            | BulkOperation::AddTags { id, .. }
+            | BulkOperation::RemoveTags { id, .. }
+            | BulkOperation::SetStatus { id, .. } => *id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    // 1件でも失敗したら全てロールバックする
+    AllOrNothing,
+    // 失敗した操作だけを飛ばして残りを反映する
+    BestEffort,
+}
+
+impl Default for BulkMode {
+    fn default() -> Self {
+        BulkMode::AllOrNothing
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkItemStatus {
+    Succeeded,
+    Failed,
+    // all_or_nothing で先に失敗があったため実行されなかった
+    Skipped,
+    // 成功したがロールバックされた
+    RolledBack,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+pub struct BulkItemResult {
+    pub index: usize,
+    pub id: i32,
+    pub status: BulkItemStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+pub struct BulkOutcome {
+    pub committed: bool,
+    pub results: Vec<BulkItemResult>,
+}
+
+impl BulkOutcome {
+    fn from_results(mode: BulkMode, results: Vec<anyhow::Result<()>>, ids: Vec<i32>) -> Self {
+        let failed = results.iter().any(|result| result.is_err());
+        let committed = !(failed && mode == BulkMode::AllOrNothing);
+        let mut stopped = false;
+        let results = results
+            .into_iter()
+            .zip(ids)
+            .enumerate()
+            .map(|(index, (result, id))| {
+                let (status, error) = match result {
+                    std::result::Result::Ok(()) if stopped => (BulkItemStatus::Skipped, None),
+                    std::result::Result::Ok(()) if !committed => (BulkItemStatus::RolledBack, None),
+                    std::result::Result::Ok(()) => (BulkItemStatus::Succeeded, None),
+                    Err(e) => {
+                        stopped = mode == BulkMode::AllOrNothing;
+                        (BulkItemStatus::Failed, Some(e.to_string()))
+                    }
+                };
+                BulkItemResult { index, id, status, error }
+            })
+            .collect();
+        BulkOutcome { committed, results }
+    }
+
+    // 実際に反映された操作。ロールバックされたものや失敗したものは含まない
+    pub fn succeeded<'a>(&'a self, operations: &'a [BulkOperation]) -> impl Iterator<Item = &'a BulkOperation> {
+        self.results
+            .iter()
+            .filter(|result| result.status == BulkItemStatus::Succeeded)
+            .filter_map(move |result| operations.get(result.index))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, FromRow)]
 pub struct BlogFromRow {
     id: i32,
@@ -103,7 +531,22 @@ fn fold_entities(rows: Vec<BlogWithTagFromRow>) -> Vec<BlogEntity> {
             vec![]
         };
 
-        accum.push(BlogEntity { id: row.id, title: row.title.clone(), body: row.body.clone(), tags })
+        accum.push(BlogEntity {
+            id: row.id,
+            title: row.title.clone(),
+            slug: row.slug.clone(),
+            body: row.body.clone(),
+            tags,
+            status: row.status,
+            seo: BlogSeo {
+                description: row.meta_description.clone(),
+                canonical_url: row.canonical_url.clone(),
+                noindex: row.noindex,
+                image: row.social_image.clone(),
+            },
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
     }
     accum
 }
@@ -114,13 +557,23 @@ impl BlogRepository for BlogRepositoryForDb {
         let tx = self.pool.begin().await?;
         let row = sqlx::query_as::<_, BlogFromRow>(
             r#"
-            insert into blogs (title, body)
-            values ($1, $2)
+            insert into blogs (
+                title, body, status, meta_description, canonical_url, noindex, social_image,
+                slug, created_at, updated_at
+            )
+            values ($1, $2, $3, $4, $5, $6, $7, $8, coalesce($9, now()), coalesce($9, now()))
             returning *
             "#
         )
         .bind(payload.title.clone())
         .bind(payload.body.clone())
+        .bind(payload.status)
+        .bind(payload.seo.description.clone())
+        .bind(payload.seo.canonical_url.clone())
+        .bind(payload.seo.noindex)
+        .bind(payload.seo.image.clone())
+        .bind(payload.slug.clone())
+        .bind(payload.published_at)
         .fetch_one(&self.pool)
         .await?;
 
@@ -142,17 +595,8 @@ impl BlogRepository for BlogRepositoryForDb {
         Ok(blog)
     }
 
-    async fn find(&self, id: i32) -> anyhow::Result<BlogEntity> {
-        let items = sqlx::query_as::<_, BlogWithTagFromRow>(
-            r#"
-            select blogs.*, tags.id as label_id, tags.name as tag_name
-            from blogs
-                    left outer join blog_tags tl on blogs.id = tl.
-            blog_id
-                    left outer join tags on tags.id = tl.label_id
-            where blogs.id=$1
-            "#
-        )
+    async fn find_with(&self, id: i32, fields: &BlogFields) -> anyhow::Result<BlogEntity> {
+        let items = sqlx::query_as::<_, BlogWithTagFromRow>(&fields.select("where blogs.id=$1"))
         .bind(id)
         .fetch_all(&self.pool)
         .await
@@ -166,16 +610,8 @@ impl BlogRepository for BlogRepositoryForDb {
         Ok(blog.clone())
     }
 
-    async fn all(&self) -> anyhow::Result<Vec<BlogEntity>> {
-        let blogs = sqlx::query_as::<_, BlogWithTagFromRow>(
-            r#"
-            select blogs.*, tags.id as label_id, tags.name as tag_name
-            from blogs
-                    left outer join blog_tags tl on blogs.id = tl.blog_id
-                    left outer join tags on tags.id = tl.label_id
-            order by blogs.id desc;
-            "#
-        )
+    async fn all_with(&self, fields: &BlogFields) -> anyhow::Result<Vec<BlogEntity>> {
+        let blogs = sqlx::query_as::<_, BlogWithTagFromRow>(&fields.select("order by blogs.id desc"))
         .fetch_all(&self.pool)
         .await?;
 
@@ -186,20 +622,28 @@ impl BlogRepository for BlogRepositoryForDb {
         let tx = self.pool.begin().await?;
 
         let old_blog = self.find(id).await?;
+        let merged = payload.merge(&old_blog);
         sqlx::query(
             r#"
-            update blogs set title=$1, body=$2
-            where id=$3
+            update blogs set title=$1, body=$2, status=$3,
+                meta_description=$4, canonical_url=$5, noindex=$6, social_image=$7, slug=$8, updated_at=now()
+            where id=$9
             returning *
             "#
         )
-        .bind(payload.title.unwrap_or(old_blog.title))
-        .bind(payload.body.unwrap_or(old_blog.body))
+        .bind(merged.title)
+        .bind(merged.body)
+        .bind(merged.status)
+        .bind(merged.seo.description)
+        .bind(merged.seo.canonical_url)
+        .bind(merged.seo.noindex)
+        .bind(merged.seo.image)
+        .bind(merged.slug)
         .bind(id)
         .fetch_one(&self.pool)
         .await?;
 
-        if let Some(tags) = payload.tags{
+        if let Some(tags) = merged.tags {
             sqlx::query(
                 r#"
                 delete from blog_tags where blog_id=$1
@@ -261,7 +705,230 @@ impl BlogRepository for BlogRepositoryForDb {
         Ok(())
     }
 
+    async fn bulk(&self, operations: Vec<BulkOperation>, mode: BulkMode) -> anyhow::Result<BulkOutcome> {
+        let mut tx = self.pool.begin().await?;
+        // タグの外部キーは遅延制約なので、失敗した操作をその場で検出できるようにする
+        sqlx::query("set constraints all immediate")
+            .execute(&mut tx)
+            .await?;
+
+        let ids = operations.iter().map(BulkOperation::id).collect();
+        let mut results = vec![];
+        for operation in operations.iter() {
+            if mode == BulkMode::AllOrNothing && results.iter().any(|result: &anyhow::Result<()>| result.is_err()) {
+                results.push(Ok(()));
+                continue;
+            }
+
+            // best_effort では失敗した操作だけを savepoint まで巻き戻す
+            let mut savepoint = Acquire::begin(&mut tx).await?;
+            let result = apply_bulk_operation(&mut savepoint, operation).await;
+            if result.is_ok() {
+                savepoint.commit().await?;
+            } else {
+                savepoint.rollback().await?;
+            }
+            results.push(result);
+        }
+
+        let outcome = BulkOutcome::from_results(mode, results, ids);
+        if outcome.committed {
+            tx.commit().await?;
+        } else {
+            tx.rollback().await?;
+        }
+        Ok(outcome)
+    }
+
+    async fn tags_for(&self, blog_ids: &[i32]) -> anyhow::Result<HashMap<i32, Vec<Tag>>> {
+        let rows = sqlx::query_as::<_, BlogTagFromRow>(
+            r#"
+            select tl.blog_id, tags.id, tags.name
+            from blog_tags tl
+                    inner join tags on tags.id = tl.label_id
+            where tl.blog_id = any($1)
+            order by tags.id asc
+            "#
+        )
+        .bind(blog_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut tags: HashMap<i32, Vec<Tag>> = blog_ids.iter().map(|id| (*id, vec![])).collect();
+        for row in rows {
+            tags.entry(row.blog_id)
+                .or_default()
+                .push(Tag { id: row.id, name: row.name });
+        }
+        Ok(tags)
+    }
+
+    async fn related(&self, id: i32, limit: i64) -> anyhow::Result<Vec<BlogEntity>> {
+        // 共通するタグが多い順
+        let rows = sqlx::query_as::<_, BlogWithTagFromRow>(&BlogFields::without(BlogField::Body).select(
+            r#"
+            inner join (
+                select bt.blog_id, count(*) as shared from blog_tags bt
+                where bt.label_id in (select label_id from blog_tags where blog_id=$1)
+                  and bt.blog_id <> $1
+                group by bt.blog_id
+                order by shared desc, bt.blog_id desc
+                limit $2
+            ) related on related.blog_id = blogs.id
+            order by related.shared desc, blogs.id desc
+            "#,
+        ))
+        .bind(id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
 
+        Ok(fold_entities(rows))
+    }
+
+    async fn tag_counts(&self) -> anyhow::Result<Vec<TagCount>> {
+        let counts = sqlx::query_as::<_, TagCount>(
+            r#"
+            select tags.id, tags.name, count(tl.id) as count
+            from tags
+                    left outer join blog_tags tl on tags.id = tl.label_id
+            group by tags.id
+            order by tags.id asc
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(counts)
+    }
+
+    async fn recent_published(&self, limit: i64, offset: i64, tag: Option<i32>) -> anyhow::Result<Vec<BlogEntity>> {
+        let blogs = sqlx::query_as::<_, BlogWithTagFromRow>(&BlogFields::all().select(
+            r#"
+            where blogs.id in (
+                select b.id from blogs b
+                where b.status = 'published'
+                    and ($2::integer is null or exists (
+                        select 1 from blog_tags bt where bt.blog_id = b.id and bt.label_id = $2
+                    ))
+                order by b.created_at desc, b.id desc
+                limit $1 offset $3
+            )
+            order by blogs.created_at desc, blogs.id desc
+            "#,
+        ))
+        .bind(limit)
+        .bind(tag)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(fold_entities(blogs))
+    }
+
+    async fn page_with(&self, fields: &BlogFields, status: Option<BlogStatus>, limit: i64, offset: i64) -> anyhow::Result<Vec<BlogEntity>> {
+        let blogs = sqlx::query_as::<_, BlogWithTagFromRow>(&fields.select(
+            r#"
+            where blogs.id in (
+                select b.id from blogs b
+                where $1::blog_status is null or b.status = $1
+                order by b.id desc
+                limit $2 offset $3
+            )
+            order by blogs.id desc
+            "#,
+        ))
+        .bind(status)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(fold_entities(blogs))
+    }
+
+    async fn last_modified(&self) -> anyhow::Result<Option<DateTime<Utc>>> {
+        collection_changed_at(&self.pool, "blogs").await
+    }
+}
+
+async fn apply_bulk_operation(conn: &mut PgConnection, operation: &BulkOperation) -> anyhow::Result<()> {
+    let id = operation.id();
+    match operation {
+        BulkOperation::Delete { .. } => {
+            sqlx::query(
+                r#"
+                delete from blog_tags where blog_id=$1
+                "#
+            )
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+        }
+        BulkOperation::AddTags { tags, .. } => {
+            sqlx::query(
+                r#"
+                insert into blog_tags (blog_id, label_id)
+                select $1, t.id
+                from unnest($2) as t(id)
+                where not exists (
+                    select 1 from blog_tags where blog_id=$1 and label_id=t.id
+                )
+                "#
+            )
+            .bind(id)
+            .bind(tags)
+            .execute(&mut *conn)
+            .await?;
+        }
+        BulkOperation::RemoveTags { tags, .. } => {
+            sqlx::query(
+                r#"
+                delete from blog_tags where blog_id=$1 and label_id = any($2)
+                "#
+            )
+            .bind(id)
+            .bind(tags)
+            .execute(&mut *conn)
+            .await?;
+        }
+        BulkOperation::SetStatus { .. } => {}
+    }
+
+    let affected = match operation {
+        BulkOperation::Delete { .. } => sqlx::query(
+            r#"
+            delete from blogs where id=$1
+            "#
+        )
+        .bind(id)
+        .execute(&mut *conn)
+        .await?,
+        BulkOperation::SetStatus { status, .. } => sqlx::query(
+            r#"
+            update blogs set status=$1, updated_at=now()
+            where id=$2
+            "#
+        )
+        .bind(status)
+        .bind(id)
+        .execute(&mut *conn)
+        .await?,
+        _ => sqlx::query(
+            r#"
+            update blogs set updated_at=now()
+            where id=$1
+            "#
+        )
+        .bind(id)
+        .execute(&mut *conn)
+        .await?,
+    };
+
+    if affected.rows_affected() == 0 {
+        return Err(RepositoryError::NotFound(id).into());
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -282,25 +949,50 @@ mod test {
             id: 2,
             name: String::from("tag 2"),
         };
+        let created_at = Utc::now();
         let rows = vec![
             BlogWithTagFromRow {
                 id: 1,
                 title: String::from("Blog 1"),
+                slug: None,
                 body: String::from("Blog 1"),
+                status: BlogStatus::Published,
+                meta_description: None,
+                canonical_url: None,
+                noindex: false,
+                social_image: None,
+                created_at,
+                updated_at: created_at,
                 label_id: Some(tag_1.id),
                 tag_name: Some(tag_1.name.clone()),
             },
             BlogWithTagFromRow {
                 id: 1,
                 title: String::from("Blog 1"),
+                slug: None,
                 body: String::from("Blog 1"),
+                status: BlogStatus::Published,
+                meta_description: None,
+                canonical_url: None,
+                noindex: false,
+                social_image: None,
+                created_at,
+                updated_at: created_at,
                 label_id: Some(tag_2.id),
                 tag_name: Some(tag_2.name.clone()),
             },
             BlogWithTagFromRow {
                 id: 2,
                 title: String::from("Blog 2"),
+                slug: None,
                 body: String::from("Blog 2"),
+                status: BlogStatus::Published,
+                meta_description: None,
+                canonical_url: None,
+                noindex: false,
+                social_image: None,
+                created_at,
+                updated_at: created_at,
                 label_id: Some(tag_1.id),
                 tag_name: Some(tag_1.name.clone()),
             },
@@ -313,14 +1005,24 @@ mod test {
                 BlogEntity {
                     id: 1,
                     title: String::from("Blog 1"),
+                    slug: None,
                     body: String::from("Blog 1"),
                     tags: vec![tag_1.clone(), tag_2.clone()],
+                    status: BlogStatus::Published,
+                    seo: BlogSeo::default(),
+                    created_at,
+                    updated_at: created_at,
                 },
                 BlogEntity {
                     id: 2,
                     title: String::from("Blog 2"),
+                    slug: None,
                     body: String::from("Blog 2"),
                     tags: vec![tag_1.clone()],
+                    status: BlogStatus::Published,
+                    seo: BlogSeo::default(),
+                    created_at,
+                    updated_at: created_at,
                 },
             ]
         )
@@ -398,9 +1100,12 @@ mod test {
             .update(
                 blog.id,
                 UpdateBlog {
-                    title: Some(update_title.to_string()),
-                    body: Some(update_body.to_string()),
-                    tags: Some(vec![])
+                    title: Some(Some(update_title.to_string())),
+                    body: Some(Some(update_body.to_string())),
+                    tags: Some(Some(vec![])),
+                    status: None,
+                    seo: None,
+                    slug: None,
                 }
             )
             .await
@@ -441,6 +1146,91 @@ mod test {
         assert!(rows.len() == 0);
     }
 
+    #[tokio::test]
+    async fn related_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .expect(&format!("fail connect database, url is [{}]", database_url));
+
+        let suffix = Utc::now().timestamp_millis();
+        let mut tags = vec![];
+        for name in ["related a", "related b"] {
+            let tag = sqlx::query_as::<_, Tag>(
+                r#"
+                insert into tags ( name )
+                values ( $1 )
+                returning *
+                "#
+            )
+            .bind(format!("[related_scenario] {} {}", name, suffix))
+            .fetch_one(&pool)
+            .await
+            .expect("Failed to insert label data.");
+            tags.push(tag);
+        }
+
+        let repository = BlogRepositoryForDb::new(pool.clone());
+        let create = |title: &str, tags: Vec<i32>| CreateBlog {
+            title: format!("[related_scenario] {}", title),
+            body: "[related_scenario] body".to_string(),
+            tags,
+            status: BlogStatus::Published,
+            seo: BlogSeo {
+                description: Some("related description".to_string()),
+                canonical_url: None,
+                noindex: true,
+                image: None,
+            },
+            slug: Some(format!("related-scenario-{}-{}", title, suffix)),
+            published_at: None,
+        };
+        let source = repository
+            .create(create("source", vec![tags[0].id, tags[1].id]))
+            .await
+            .expect("[create] returned Err");
+        let one_shared = repository
+            .create(create("one", vec![tags[0].id]))
+            .await
+            .expect("[create] returned Err");
+        let two_shared = repository
+            .create(create("two", vec![tags[0].id, tags[1].id]))
+            .await
+            .expect("[create] returned Err");
+
+        // 共通するタグが多い順に、本文以外の列とタグを読む
+        let related = repository
+            .related(source.id, 5)
+            .await
+            .expect("[related] returned Err");
+        assert_eq!(
+            vec![two_shared.id, one_shared.id],
+            related.iter().map(|blog| blog.id).collect::<Vec<i32>>()
+        );
+        assert_eq!(two_shared.title, related[0].title);
+        assert_eq!(two_shared.slug, related[0].slug);
+        assert_eq!(two_shared.seo, related[0].seo);
+        assert_eq!(2, related[0].tags.len());
+        assert!(related[0].body.is_empty());
+
+        let related = repository
+            .related(source.id, 1)
+            .await
+            .expect("[related] returned Err");
+        assert_eq!(vec![two_shared.id], related.iter().map(|blog| blog.id).collect::<Vec<i32>>());
+
+        for blog in [source, one_shared, two_shared] {
+            repository.delete(blog.id).await.expect("[delete] returned Err");
+        }
+        for tag in tags {
+            sqlx::query("delete from tags where id=$1")
+                .bind(tag.id)
+                .execute(&pool)
+                .await
+                .expect("Failed to delete label data.");
+        }
+    }
 }
 
 #[cfg(test)]
@@ -456,7 +1246,18 @@ pub mod test_utils {
 
     impl BlogEntity {
         pub fn new(id: i32, title: String, body: String, tags: Vec<Tag>) -> Self {
-            Self { id, title, body, tags }
+            let now = Utc::now();
+            Self {
+                id,
+                title,
+                slug: None,
+                body,
+                tags,
+                status: BlogStatus::default(),
+                seo: BlogSeo::default(),
+                created_at: now,
+                updated_at: now,
+            }
         }
     }
 
@@ -468,16 +1269,41 @@ pub mod test_utils {
 
     type BlogDatas = HashMap<i32, BlogEntity>;
 
+    // BlogRepositoryForDb と同じく、読まなかった body, tags, seo は空にする
+    fn project(blog: &BlogEntity, fields: &BlogFields) -> BlogEntity {
+        let mut blog = blog.clone();
+        if !fields.contains(BlogField::Body) {
+            blog.body = String::new();
+        }
+        if !fields.contains(BlogField::Tags) {
+            blog.tags = vec![];
+        }
+        if !fields.contains(BlogField::Seo) {
+            blog.seo = BlogSeo::default();
+        }
+        blog
+    }
+
     #[derive(Debug, Clone)]
     pub struct BlogRepositoryForMemory {
         store: Arc<RwLock<BlogDatas>>,
         tags: Vec<Tag>,
+        last_modified: Arc<RwLock<Option<DateTime<Utc>>>>,
     }
 
     //メソッド定義
     impl BlogRepositoryForMemory {
         pub fn new(tags: Vec<Tag>) -> Self {
-            BlogRepositoryForMemory { store: Arc::default(), tags }
+            BlogRepositoryForMemory { store: Arc::default(), tags, last_modified: Arc::default() }
+        }
+
+        fn touch(&self) {
+            *self.last_modified.write().unwrap() = Some(Utc::now());
+        }
+
+        // Last-Modified は秒単位なので、テストでは待つ代わりに前に変わった時刻をずらしておく
+        pub fn set_last_modified(&self, last_modified: DateTime<Utc>) {
+            *self.last_modified.write().unwrap() = Some(last_modified);
         }
 
         fn write_store_ref(&self) -> RwLockWriteGuard<BlogDatas> {
@@ -495,6 +1321,46 @@ pub mod test_utils {
                 .collect();
             tags
         }
+
+        fn try_resolve_tags(&self, tags: &[i32]) -> anyhow::Result<Vec<Tag>> {
+            tags.iter()
+                .map(|id| {
+                    self.tags
+                        .iter()
+                        .find(|tag| tag.id == *id)
+                        .cloned()
+                        .ok_or(RepositoryError::NotFound(*id).into())
+                })
+                .collect()
+        }
+
+        fn apply_bulk_operation(&self, store: &mut BlogDatas, operation: &BulkOperation) -> anyhow::Result<()> {
+            let id = operation.id();
+            if let BulkOperation::Delete { .. } = operation {
+                store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
+                return Ok(());
+            }
+
+            let blog = store.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+            match operation {
+                BulkOperation::AddTags { tags, .. } => {
+                    for tag in self.try_resolve_tags(tags)? {
+                        if !blog.tags.contains(&tag) {
+                            blog.tags.push(tag);
+                        }
+                    }
+                }
+                BulkOperation::RemoveTags { tags, .. } => {
+                    blog.tags.retain(|tag| !tags.contains(&tag.id));
+                }
+                BulkOperation::SetStatus { status, .. } => {
+                    blog.status = *status;
+                }
+                BulkOperation::Delete { .. } => unreachable!(),
+            }
+            blog.updated_at = Utc::now();
+            Ok(())
+        }
     }
 
 
@@ -504,23 +1370,34 @@ pub mod test_utils {
             let mut store = self.write_store_ref();
             let id = (store.len() + 1) as i32;
             let tags = self.resolve_tags(payload.tags);
-            let blog = BlogEntity::new(id, payload.title.clone(), payload.body.clone(), tags);
+            let created_at = payload.published_at.unwrap_or_else(Utc::now);
+            let blog = BlogEntity {
+                status: payload.status,
+                seo: payload.seo,
+                slug: payload.slug,
+                created_at,
+                updated_at: created_at,
+                ..BlogEntity::new(id, payload.title.clone(), payload.body.clone(), tags)
+            };
             store.insert(id, blog.clone());
+            self.touch();
             Ok(blog)
         }
 
-        async fn find(&self, id: i32) -> anyhow::Result<BlogEntity> {
+        async fn find_with(&self, id: i32, fields: &BlogFields) -> anyhow::Result<BlogEntity> {
             let store = self.read_store_ref();
             let blog = store
                 .get(&id)
-                .map(|blog| blog.clone())
+                .map(|blog| project(blog, fields))
                 .ok_or(RepositoryError::NotFound(id))?;
             Ok(blog)
         }
 
-        async fn all(&self) -> anyhow::Result<Vec<BlogEntity>>{
+        async fn all_with(&self, fields: &BlogFields) -> anyhow::Result<Vec<BlogEntity>>{
             let store = self.read_store_ref();
-            Ok(Vec::from_iter(store.values().map(|blog| blog.clone())))
+            let mut blogs = Vec::from_iter(store.values().map(|blog| project(blog, fields)));
+            blogs.sort_by(|a, b| b.id.cmp(&a.id));
+            Ok(blogs)
         }
 
         async fn update(&self, id: i32, payload: UpdateBlog) -> anyhow::Result<BlogEntity> {
@@ -528,27 +1405,124 @@ pub mod test_utils {
             let blog = store
                 .get(&id)
                 .context(RepositoryError::NotFound(id))?;
-            let title = payload.title.unwrap_or(blog.title.clone());
-            let body = payload.body.unwrap_or(blog.body.clone());
-            let tags = match payload.tags {
+            let merged = payload.merge(blog);
+            let tags = match merged.tags {
                 Some(tag_ids) => self.resolve_tags(tag_ids),
                 None => blog.tags.clone(),
             };
             let blog = BlogEntity {
                 id,
-                title,
-                body,
-                tags
+                title: merged.title,
+                slug: merged.slug,
+                body: merged.body,
+                tags,
+                status: merged.status,
+                seo: merged.seo,
+                created_at: blog.created_at,
+                updated_at: Utc::now(),
             };
             store.insert(id, blog.clone());
+            self.touch();
             Ok(blog)
         }
 
         async fn delete(&self, id: i32) -> anyhow::Result<()> {
             let mut store = self.write_store_ref();
             store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
+            self.touch();
             Ok(())
         }
+
+        async fn bulk(&self, operations: Vec<BulkOperation>, mode: BulkMode) -> anyhow::Result<BulkOutcome> {
+            let mut store = self.write_store_ref();
+            let mut working = store.clone();
+            let ids = operations.iter().map(BulkOperation::id).collect();
+            let mut results = vec![];
+            for operation in operations.iter() {
+                if mode == BulkMode::AllOrNothing && results.iter().any(|result: &anyhow::Result<()>| result.is_err()) {
+                    results.push(Ok(()));
+                    continue;
+                }
+                results.push(self.apply_bulk_operation(&mut working, operation));
+            }
+
+            let outcome = BulkOutcome::from_results(mode, results, ids);
+            if outcome.committed {
+                *store = working;
+                self.touch();
+            }
+            Ok(outcome)
+        }
+
+        async fn tags_for(&self, blog_ids: &[i32]) -> anyhow::Result<HashMap<i32, Vec<Tag>>> {
+            let store = self.read_store_ref();
+            let tags = blog_ids
+                .iter()
+                .map(|id| (*id, store.get(id).map(|blog| blog.tags.clone()).unwrap_or_default()))
+                .collect();
+            Ok(tags)
+        }
+
+        async fn related(&self, id: i32, limit: i64) -> anyhow::Result<Vec<BlogEntity>> {
+            let store = self.read_store_ref();
+            let blog = store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+            let mut related: Vec<(usize, BlogEntity)> = store
+                .values()
+                .filter(|other| other.id != id)
+                .map(|other| {
+                    let shared = other.tags.iter().filter(|tag| blog.tags.contains(tag)).count();
+                    (shared, project(other, &BlogFields::without(BlogField::Body)))
+                })
+                .filter(|(shared, _)| *shared > 0)
+                .collect();
+            related.sort_by(|(a, a_blog), (b, b_blog)| b.cmp(a).then(b_blog.id.cmp(&a_blog.id)));
+            Ok(related
+                .into_iter()
+                .take(limit as usize)
+                .map(|(_, blog)| blog)
+                .collect())
+        }
+
+        async fn tag_counts(&self) -> anyhow::Result<Vec<TagCount>> {
+            let store = self.read_store_ref();
+            let counts = self
+                .tags
+                .iter()
+                .map(|tag| TagCount {
+                    id: tag.id,
+                    name: tag.name.clone(),
+                    count: store.values().filter(|blog| blog.tags.contains(tag)).count() as i64,
+                })
+                .collect();
+            Ok(counts)
+        }
+
+        async fn recent_published(&self, limit: i64, offset: i64, tag: Option<i32>) -> anyhow::Result<Vec<BlogEntity>> {
+            let store = self.read_store_ref();
+            let mut blogs: Vec<BlogEntity> = store
+                .values()
+                .filter(|blog| blog.status == BlogStatus::Published)
+                .filter(|blog| tag.map_or(true, |tag| blog.tags.iter().any(|t| t.id == tag)))
+                .cloned()
+                .collect();
+            blogs.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+            Ok(blogs.into_iter().skip(offset as usize).take(limit as usize).collect())
+        }
+
+        async fn page_with(&self, fields: &BlogFields, status: Option<BlogStatus>, limit: i64, offset: i64) -> anyhow::Result<Vec<BlogEntity>> {
+            let store = self.read_store_ref();
+            let mut blogs: Vec<BlogEntity> = store
+                .values()
+                .filter(|blog| status.map_or(true, |status| blog.status == status))
+                .map(|blog| project(blog, fields))
+                .collect();
+            blogs.sort_by(|a, b| b.id.cmp(&a.id));
+            Ok(blogs.into_iter().skip(offset as usize).take(limit as usize).collect())
+        }
+
+        async fn last_modified(&self) -> anyhow::Result<Option<DateTime<Utc>>> {
+            Ok(*self.last_modified.read().unwrap())
+        }
     }
 
     #[cfg(test)]
@@ -565,12 +1539,7 @@ pub mod test_utils {
                 name: String::from("test tag"),
             };
             let tags = vec![tag_data.clone()];
-            let expected = BlogEntity{
-                id,
-                title: title.clone(),
-                body: body.clone(),
-                tags: tags.clone()
-            };
+            let expected = BlogEntity::new(id, title.clone(), body.clone(), tags.clone());
     
             //create
             let tag_data = Tag {
@@ -579,7 +1548,23 @@ pub mod test_utils {
             };
             let tags = vec![tag_data.clone()];
             let repository = BlogRepositoryForMemory::new(tags.clone());
-            let blog = repository.create(CreateBlog { title, body, tags: vec![tag_data.id] }).await.expect("failed create blog");
+            let blog = repository
+                .create(CreateBlog {
+                    title,
+                    body,
+                    tags: vec![tag_data.id],
+                    status: BlogStatus::Published,
+                    seo: BlogSeo::default(),
+                    slug: None,
+                    published_at: None,
+                })
+                .await
+                .expect("failed create blog");
+            let expected = BlogEntity {
+                created_at: blog.created_at,
+                updated_at: blog.updated_at,
+                ..expected
+            };
             assert_eq!(expected, blog);
     
             //find
@@ -588,7 +1573,7 @@ pub mod test_utils {
     
             //all
             let blog = repository.all().await.expect("failed get all blog");
-            assert_eq!(vec![expected], blog);
+            assert_eq!(vec![expected.clone()], blog);
     
             //update
             let title = "update blog title".to_string();
@@ -596,7 +1581,17 @@ pub mod test_utils {
             let blog = repository
                 .update(
                     1,
-                    UpdateBlog { title: Some(title.clone()), body: Some(body.clone()), tags: Some(vec![]) }
+                    UpdateBlog {
+                        title: Some(Some(title.clone())),
+                        body: Some(Some(body.clone())),
+                        tags: Some(Some(vec![])),
+                        status: Some(Some(BlogStatus::Draft)),
+                        seo: Some(Some(UpdateBlogSeo {
+                            description: Some(Some("description".to_string())),
+                            ..UpdateBlogSeo::default()
+                        })),
+                        slug: Some(Some("updated".to_string())),
+                    }
                 )
                 .await
                 .expect("failed update blog.");
@@ -604,16 +1599,102 @@ pub mod test_utils {
                 BlogEntity {
                     id,
                     title,
+                    slug: Some("updated".to_string()),
                     body,
-                    tags: vec![]
+                    tags: vec![],
+                    status: BlogStatus::Draft,
+                    seo: BlogSeo {
+                        description: Some("description".to_string()),
+                        ..BlogSeo::default()
+                    },
+                    created_at: expected.created_at,
+                    updated_at: blog.updated_at,
                 },
                 blog
             );
+            assert!(blog.updated_at >= blog.created_at);
     
             //delete
             let res = repository.delete(id).await;
             assert!(res.is_ok())
     
         }
+
+        #[tokio::test]
+        async fn bulk_outcome_succeeded_operations() {
+            let tag = Tag {
+                id: 1,
+                name: String::from("test tag"),
+            };
+            let repository = BlogRepositoryForMemory::new(vec![tag.clone()]);
+            for title in ["first", "second"] {
+                repository
+                    .create(CreateBlog {
+                        title: title.to_string(),
+                        body: "body".to_string(),
+                        tags: vec![],
+                        status: BlogStatus::Draft,
+                        seo: BlogSeo::default(),
+                        slug: None,
+                        published_at: None,
+                    })
+                    .await
+                    .unwrap();
+            }
+            let operations = vec![
+                BulkOperation::SetStatus { id: 1, status: BlogStatus::Published },
+                BulkOperation::SetStatus { id: 42, status: BlogStatus::Published },
+                BulkOperation::AddTags { id: 2, tags: vec![tag.id] },
+            ];
+
+            let outcome = repository
+                .bulk(operations.clone(), BulkMode::BestEffort)
+                .await
+                .unwrap();
+            let succeeded: Vec<&BulkOperation> = outcome.succeeded(&operations).collect();
+            assert_eq!(vec![&operations[0], &operations[2]], succeeded);
+
+            // all_or_nothing で失敗すると、成功した操作もロールバックされる
+            let outcome = repository
+                .bulk(operations.clone(), BulkMode::AllOrNothing)
+                .await
+                .unwrap();
+            assert_eq!(0, outcome.succeeded(&operations).count());
+        }
+
+        #[tokio::test]
+        async fn page_with_status() {
+            let repository = BlogRepositoryForMemory::new(vec![]);
+            for (title, status) in [
+                ("first", BlogStatus::Published),
+                ("second", BlogStatus::Draft),
+                ("third", BlogStatus::Published),
+                ("fourth", BlogStatus::Published),
+            ] {
+                repository
+                    .create(CreateBlog {
+                        title: title.to_string(),
+                        body: "body".to_string(),
+                        tags: vec![],
+                        status,
+                        seo: BlogSeo::default(),
+                        slug: None,
+                        published_at: None,
+                    })
+                    .await
+                    .unwrap();
+            }
+            let fields = BlogFields::without(BlogField::Body);
+
+            let page = repository
+                .page_with(&fields, Some(BlogStatus::Published), 2, 1)
+                .await
+                .unwrap();
+            assert_eq!(vec![3, 1], page.iter().map(|blog| blog.id).collect::<Vec<i32>>());
+            assert!(page.iter().all(|blog| blog.body.is_empty()));
+
+            let page = repository.page_with(&fields, None, 10, 0).await.unwrap();
+            assert_eq!(vec![4, 3, 2, 1], page.iter().map(|blog| blog.id).collect::<Vec<i32>>());
+        }
     }
 }
@@ -0,0 +1,121 @@
+use anyhow::Ok;
+use axum::async_trait;
+use sqlx::{FromRow, PgPool};
+
+// ActivityPub でブログをフォローしているアカウント。actor はリモートのアクターの id (URL)
+#[async_trait]
+pub trait FollowerRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn save(&self, follower: Follower) -> anyhow::Result<()>;
+    async fn delete(&self, actor: &str) -> anyhow::Result<()>;
+    async fn all(&self) -> anyhow::Result<Vec<Follower>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct Follower {
+    pub actor: String,
+    pub inbox: String,
+    pub shared_inbox: Option<String>,
+}
+
+impl Follower {
+    // 同じサーバーのフォロワーには共有 inbox へ 1 回だけ配送する
+    pub fn delivery_inbox(&self) -> &str {
+        self.shared_inbox.as_deref().unwrap_or(&self.inbox)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FollowerRepositoryForDb {
+    pool: PgPool,
+}
+
+impl FollowerRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        FollowerRepositoryForDb { pool }
+    }
+}
+
+#[async_trait]
+impl FollowerRepository for FollowerRepositoryForDb {
+    async fn save(&self, follower: Follower) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            insert into followers (actor, inbox, shared_inbox)
+            values ($1, $2, $3)
+            on conflict (actor) do update
+            set inbox=excluded.inbox, shared_inbox=excluded.shared_inbox
+            "#
+        )
+        .bind(follower.actor)
+        .bind(follower.inbox)
+        .bind(follower.shared_inbox)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, actor: &str) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            delete from followers where actor=$1
+            "#
+        )
+        .bind(actor)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<Follower>> {
+        let followers = sqlx::query_as::<_, Follower>(
+            r#"
+            select actor, inbox, shared_inbox from followers order by created_at, actor
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(followers)
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use anyhow::Ok;
+    use axum::async_trait;
+    use std::sync::{Arc, RwLock};
+
+    use super::{Follower, FollowerRepository};
+
+    #[derive(Debug, Clone)]
+    pub struct FollowerRepositoryForMemory {
+        store: Arc<RwLock<Vec<Follower>>>,
+    }
+
+    impl FollowerRepositoryForMemory {
+        pub fn new() -> Self {
+            FollowerRepositoryForMemory { store: Arc::default() }
+        }
+    }
+
+    #[async_trait]
+    impl FollowerRepository for FollowerRepositoryForMemory {
+        async fn save(&self, follower: Follower) -> anyhow::Result<()> {
+            let mut store = self.store.write().unwrap();
+            store.retain(|saved| saved.actor != follower.actor);
+            store.push(follower);
+            Ok(())
+        }
+
+        async fn delete(&self, actor: &str) -> anyhow::Result<()> {
+            self.store.write().unwrap().retain(|saved| saved.actor != actor);
+            Ok(())
+        }
+
+        async fn all(&self) -> anyhow::Result<Vec<Follower>> {
+            Ok(self.store.read().unwrap().clone())
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use anyhow::Ok;
+use axum::async_trait;
+use chrono::{Duration, Utc};
+use sqlx::{FromRow, PgPool};
+
+#[async_trait]
+pub trait IdempotencyRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    // window を過ぎたキーと、lock_timeout を過ぎても完了しなかったキーは消してから予約する
+    async fn reserve(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        window: Duration,
+        lock_timeout: Duration,
+    ) -> anyhow::Result<Reservation>;
+    async fn complete(&self, scope: &str, key: &str, response: StoredResponse) -> anyhow::Result<()>;
+    async fn release(&self, scope: &str, key: &str) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredResponse {
+    pub status_code: i32,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reservation {
+    // 初回のリクエスト。処理して complete を呼ぶ
+    Reserved,
+    // 同じキーのリクエストが処理中
+    InProgress { fingerprint: String },
+    // 処理済み。保存したレスポンスを返す
+    Completed { fingerprint: String, response: StoredResponse },
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct IdempotencyKeyFromRow {
+    fingerprint: String,
+    status_code: Option<i32>,
+    response_body: Option<String>,
+}
+
+impl From<IdempotencyKeyFromRow> for Reservation {
+    fn from(row: IdempotencyKeyFromRow) -> Self {
+        match (row.status_code, row.response_body) {
+            (Some(status_code), Some(body)) => Reservation::Completed {
+                fingerprint: row.fingerprint,
+                response: StoredResponse { status_code, body },
+            },
+            _ => Reservation::InProgress { fingerprint: row.fingerprint },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IdempotencyRepositoryForDb {
+    pool: PgPool,
+}
+
+impl IdempotencyRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        IdempotencyRepositoryForDb { pool }
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for IdempotencyRepositoryForDb {
+    async fn reserve(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        window: Duration,
+        lock_timeout: Duration,
+    ) -> anyhow::Result<Reservation> {
+        let now = Utc::now();
+        // 他のキーも含めて消すので、テーブルが増え続けない
+        sqlx::query(
+            r#"
+            delete from idempotency_keys
+            where created_at < $1 or (status_code is null and created_at < $2)
+            "#
+        )
+        .bind(now - window)
+        .bind(now - lock_timeout)
+        .execute(&self.pool)
+        .await?;
+
+        let inserted = sqlx::query(
+            r#"
+            insert into idempotency_keys (scope, key, fingerprint)
+            values ($1, $2, $3)
+            on conflict do nothing
+            "#
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(fingerprint)
+        .execute(&self.pool)
+        .await?;
+
+        if inserted.rows_affected() == 1 {
+            return Ok(Reservation::Reserved);
+        }
+
+        let row = sqlx::query_as::<_, IdempotencyKeyFromRow>(
+            r#"
+            select fingerprint, status_code, response_body from idempotency_keys
+            where scope=$1 and key=$2
+            "#
+        )
+        .bind(scope)
+        .bind(key)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    async fn complete(&self, scope: &str, key: &str, response: StoredResponse) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            update idempotency_keys set status_code=$1, response_body=$2
+            where scope=$3 and key=$4
+            "#
+        )
+        .bind(response.status_code)
+        .bind(response.body)
+        .bind(scope)
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn release(&self, scope: &str, key: &str) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            delete from idempotency_keys
+            where scope=$1 and key=$2 and status_code is null
+            "#
+        )
+        .bind(scope)
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use anyhow::Ok;
+    use axum::async_trait;
+    use chrono::{DateTime, Duration, Utc};
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock, RwLockWriteGuard};
+
+    use super::{IdempotencyRepository, Reservation, StoredResponse};
+
+    #[derive(Debug, Clone)]
+    struct IdempotencyRecord {
+        fingerprint: String,
+        response: Option<StoredResponse>,
+        created_at: DateTime<Utc>,
+    }
+
+    type IdempotencyData = HashMap<(String, String), IdempotencyRecord>;
+
+    #[derive(Debug, Clone)]
+    pub struct IdempotencyRepositoryForMemory {
+        store: Arc<RwLock<IdempotencyData>>,
+    }
+
+    impl IdempotencyRepositoryForMemory {
+        pub fn new() -> Self {
+            IdempotencyRepositoryForMemory { store: Arc::default() }
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<IdempotencyData> {
+            self.store.write().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl IdempotencyRepository for IdempotencyRepositoryForMemory {
+        async fn reserve(
+            &self,
+            scope: &str,
+            key: &str,
+            fingerprint: &str,
+            window: Duration,
+            lock_timeout: Duration,
+        ) -> anyhow::Result<Reservation> {
+            let mut store = self.write_store_ref();
+            let now = Utc::now();
+            store.retain(|_, record| match record.response {
+                Some(_) => record.created_at >= now - window,
+                None => record.created_at >= now - window && record.created_at >= now - lock_timeout,
+            });
+
+            let id = (scope.to_string(), key.to_string());
+            if let Some(record) = store.get(&id) {
+                let fingerprint = record.fingerprint.clone();
+                return Ok(match record.response.clone() {
+                    Some(response) => Reservation::Completed { fingerprint, response },
+                    None => Reservation::InProgress { fingerprint },
+                });
+            }
+
+            store.insert(
+                id,
+                IdempotencyRecord {
+                    fingerprint: fingerprint.to_string(),
+                    response: None,
+                    created_at: now,
+                },
+            );
+            Ok(Reservation::Reserved)
+        }
+
+        async fn complete(&self, scope: &str, key: &str, response: StoredResponse) -> anyhow::Result<()> {
+            let mut store = self.write_store_ref();
+            if let Some(record) = store.get_mut(&(scope.to_string(), key.to_string())) {
+                record.response = Some(response);
+            }
+            Ok(())
+        }
+
+        // DB と同じく、消すのは処理中のキーだけ。complete 済みの応答は残す
+        async fn release(&self, scope: &str, key: &str) -> anyhow::Result<()> {
+            let mut store = self.write_store_ref();
+            let id = (scope.to_string(), key.to_string());
+            if store.get(&id).map_or(false, |record| record.response.is_none()) {
+                store.remove(&id);
+            }
+            Ok(())
+        }
+    }
+
+    mod test {
+        use super::*;
+
+        #[tokio::test]
+        async fn idempotency_scenario() {
+            let repository = IdempotencyRepositoryForMemory::new();
+            let window = Duration::hours(1);
+            let lock_timeout = Duration::minutes(1);
+
+            let reservation = repository.reserve("POST /blogs", "key", "abc", window, lock_timeout).await.unwrap();
+            assert_eq!(Reservation::Reserved, reservation);
+
+            let reservation = repository.reserve("POST /blogs", "key", "abc", window, lock_timeout).await.unwrap();
+            assert_eq!(Reservation::InProgress { fingerprint: "abc".to_string() }, reservation);
+
+            let response = StoredResponse { status_code: 201, body: "{}".to_string() };
+            repository.complete("POST /blogs", "key", response.clone()).await.unwrap();
+            let reservation = repository.reserve("POST /blogs", "key", "abc", window, lock_timeout).await.unwrap();
+            assert_eq!(
+                Reservation::Completed { fingerprint: "abc".to_string(), response },
+                reservation
+            );
+
+            // 期限切れのキーは新しいリクエストとして扱う
+            let reservation = repository
+                .reserve("POST /blogs", "key", "xyz", Duration::seconds(-1), lock_timeout)
+                .await
+                .unwrap();
+            assert_eq!(Reservation::Reserved, reservation);
+        }
+
+        #[tokio::test]
+        async fn release_stale_reservations() {
+            let repository = IdempotencyRepositoryForMemory::new();
+            let window = Duration::hours(1);
+
+            // 処理中に落ちて complete されなかったキーも、lock_timeout を過ぎればリトライできる
+            let reservation = repository.reserve("POST /blogs", "crashed", "abc", window, Duration::minutes(1)).await.unwrap();
+            assert_eq!(Reservation::Reserved, reservation);
+            let reservation = repository.reserve("POST /blogs", "crashed", "abc", window, Duration::seconds(-1)).await.unwrap();
+            assert_eq!(Reservation::Reserved, reservation);
+
+            // 期限切れのキーは他のキーの予約のときにも消える
+            let response = StoredResponse { status_code: 201, body: "{}".to_string() };
+            repository.complete("POST /blogs", "crashed", response).await.unwrap();
+            repository.reserve("POST /tags", "other", "abc", Duration::seconds(-1), Duration::minutes(1)).await.unwrap();
+            let keys: Vec<String> = repository.store.read().unwrap().keys().map(|(_, key)| key.clone()).collect();
+            assert_eq!(vec!["other".to_string()], keys);
+        }
+
+        #[tokio::test]
+        async fn release_keeps_completed_responses() {
+            let repository = IdempotencyRepositoryForMemory::new();
+            let (window, lock_timeout) = (Duration::hours(1), Duration::minutes(1));
+
+            repository.reserve("POST /blogs", "failed", "abc", window, lock_timeout).await.unwrap();
+            repository.release("POST /blogs", "failed").await.unwrap();
+            let reservation = repository.reserve("POST /blogs", "failed", "abc", window, lock_timeout).await.unwrap();
+            assert_eq!(Reservation::Reserved, reservation);
+
+            let response = StoredResponse { status_code: 201, body: "{}".to_string() };
+            repository.complete("POST /blogs", "failed", response.clone()).await.unwrap();
+            repository.release("POST /blogs", "failed").await.unwrap();
+            let reservation = repository.reserve("POST /blogs", "failed", "abc", window, lock_timeout).await.unwrap();
+            assert_eq!(Reservation::Completed { fingerprint: "abc".to_string(), response }, reservation);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::Ok;
+use axum::async_trait;
+use sqlx::{FromRow, PgPool};
+
+// 取り込み元 (ファイルパスなど) と作成した記事の対応。再実行時に同じ元データを二重に取り込まないために使う
+#[async_trait]
+pub trait ImportRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn find(&self, source: &str) -> anyhow::Result<Option<ImportedSource>>;
+    async fn record(&self, source: &str, blog_id: i32, checksum: &str) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct ImportedSource {
+    pub source: String,
+    pub blog_id: i32,
+    pub checksum: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportRepositoryForDb {
+    pool: PgPool,
+}
+
+impl ImportRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        ImportRepositoryForDb { pool }
+    }
+}
+
+#[async_trait]
+impl ImportRepository for ImportRepositoryForDb {
+    async fn find(&self, source: &str) -> anyhow::Result<Option<ImportedSource>> {
+        let imported = sqlx::query_as::<_, ImportedSource>(
+            r#"
+            select source, blog_id, checksum from import_sources
+            where source=$1
+            "#
+        )
+        .bind(source)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(imported)
+    }
+
+    async fn record(&self, source: &str, blog_id: i32, checksum: &str) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            insert into import_sources (source, blog_id, checksum)
+            values ($1, $2, $3)
+            on conflict (source) do update
+            set blog_id=excluded.blog_id, checksum=excluded.checksum, imported_at=now()
+            "#
+        )
+        .bind(source)
+        .bind(blog_id)
+        .bind(checksum)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use anyhow::Ok;
+    use axum::async_trait;
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock};
+
+    use super::{ImportRepository, ImportedSource};
+
+    #[derive(Debug, Clone)]
+    pub struct ImportRepositoryForMemory {
+        store: Arc<RwLock<HashMap<String, ImportedSource>>>,
+    }
+
+    impl ImportRepositoryForMemory {
+        pub fn new() -> Self {
+            ImportRepositoryForMemory { store: Arc::default() }
+        }
+    }
+
+    #[async_trait]
+    impl ImportRepository for ImportRepositoryForMemory {
+        async fn find(&self, source: &str) -> anyhow::Result<Option<ImportedSource>> {
+            Ok(self.store.read().unwrap().get(source).cloned())
+        }
+
+        async fn record(&self, source: &str, blog_id: i32, checksum: &str) -> anyhow::Result<()> {
+            let imported = ImportedSource {
+                source: source.to_string(),
+                blog_id,
+                checksum: checksum.to_string(),
+            };
+            self.store.write().unwrap().insert(source.to_string(), imported);
+            Ok(())
+        }
+    }
+}
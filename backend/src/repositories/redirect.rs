@@ -0,0 +1,86 @@
+use anyhow::Ok;
+use axum::async_trait;
+use sqlx::PgPool;
+
+// 移行前の URL のパスから記事へのリダイレクト
+#[async_trait]
+pub trait RedirectRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn find(&self, path: &str) -> anyhow::Result<Option<i32>>;
+    async fn save(&self, path: &str, blog_id: i32) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Clone)]
+pub struct RedirectRepositoryForDb {
+    pool: PgPool,
+}
+
+impl RedirectRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        RedirectRepositoryForDb { pool }
+    }
+}
+
+#[async_trait]
+impl RedirectRepository for RedirectRepositoryForDb {
+    async fn find(&self, path: &str) -> anyhow::Result<Option<i32>> {
+        let blog_id = sqlx::query_scalar::<_, i32>(
+            r#"
+            select blog_id from redirects where path=$1
+            "#
+        )
+        .bind(path)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(blog_id)
+    }
+
+    async fn save(&self, path: &str, blog_id: i32) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            insert into redirects (path, blog_id)
+            values ($1, $2)
+            on conflict (path) do update set blog_id=excluded.blog_id
+            "#
+        )
+        .bind(path)
+        .bind(blog_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use anyhow::Ok;
+    use axum::async_trait;
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock};
+
+    use super::RedirectRepository;
+
+    #[derive(Debug, Clone)]
+    pub struct RedirectRepositoryForMemory {
+        store: Arc<RwLock<HashMap<String, i32>>>,
+    }
+
+    impl RedirectRepositoryForMemory {
+        pub fn new() -> Self {
+            RedirectRepositoryForMemory { store: Arc::default() }
+        }
+    }
+
+    #[async_trait]
+    impl RedirectRepository for RedirectRepositoryForMemory {
+        async fn find(&self, path: &str) -> anyhow::Result<Option<i32>> {
+            Ok(self.store.read().unwrap().get(path).copied())
+        }
+
+        async fn save(&self, path: &str, blog_id: i32) -> anyhow::Result<()> {
+            self.store.write().unwrap().insert(path.to_string(), blog_id);
+            Ok(())
+        }
+    }
+}
@@ -1,17 +1,42 @@
 use anyhow::Ok;
 use axum::async_trait;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use super::RepositoryError;
+use super::{collection_changed_at, RepositoryError};
 
 #[async_trait]
 pub trait TagRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
     async fn create(&self, name: String) -> anyhow::Result<Tag>;
+    async fn find(&self, id: i32) -> anyhow::Result<Tag>;
     async fn all(&self) -> anyhow::Result<Vec<Tag>>;
     async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    // 一覧が最後に変わった時刻。削除でも進む
+    async fn last_modified(&self) -> anyhow::Result<Option<DateTime<Utc>>>;
+
+    // 名前からタグの id を引く。無い名前のタグは作る
+    async fn ids_for_names(&self, names: &[String]) -> anyhow::Result<Vec<i32>> {
+        let mut tags = self.all().await?;
+        let mut ids = Vec::new();
+        for name in names {
+            let id = match tags.iter().find(|tag| &tag.name == name) {
+                Some(tag) => tag.id,
+                None => {
+                    let tag = self.create(name.clone()).await?;
+                    tags.push(tag.clone());
+                    tag.id
+                }
+            };
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, sqlx::FromRow)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, JsonSchema)]
 pub struct Tag {
     pub id: i32,
     pub name: String,
@@ -64,6 +89,23 @@ impl TagRepository for TagRepositoryForDb {
     Ok(tag)
     }
 
+    async fn find(&self, id: i32) -> anyhow::Result<Tag> {
+        let tag = sqlx::query_as::<_, Tag>(
+            r#"
+            select * from tags where id=$1
+            "#
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(tag)
+    }
+
     async fn all(&self) -> anyhow::Result<Vec<Tag>>{
         let tags = sqlx::query_as::<_, Tag>(
             r#"
@@ -93,6 +135,10 @@ impl TagRepository for TagRepositoryForDb {
 
         Ok(())
     }
+
+    async fn last_modified(&self) -> anyhow::Result<Option<DateTime<Utc>>> {
+        collection_changed_at(&self.pool, "tags").await
+    }
 }
 
 #[cfg(test)]
@@ -135,6 +181,7 @@ pub mod test_utils {
     use crate::repositories::tag::{TagRepository, RepositoryError};
     use anyhow::Ok;
     use axum::async_trait;
+    use chrono::{DateTime, Utc};
     use std::collections::HashMap;
     use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
@@ -151,11 +198,12 @@ pub mod test_utils {
     #[derive(Debug, Clone)]
     pub struct TagRepositoryForMemory {
         store: Arc<RwLock<TagData>>,
+        last_modified: Arc<RwLock<Option<DateTime<Utc>>>>,
     }
 
     impl TagRepositoryForMemory {
         pub fn new() -> Self {
-            TagRepositoryForMemory { store: Arc::default() }
+            TagRepositoryForMemory { store: Arc::default(), last_modified: Arc::default() }
         }
 
         fn write_store_ref(&self) -> RwLockWriteGuard<TagData> {
@@ -178,6 +226,13 @@ pub mod test_utils {
             let id = (store.len() + 1) as i32;
             let tag = Tag::new(id, name.clone());
             store.insert(id, tag.clone());
+            *self.last_modified.write().unwrap() = Some(Utc::now());
+            Ok(tag)
+        }
+
+        async fn find(&self, id: i32) -> anyhow::Result<Tag> {
+            let store = self.read_store_ref();
+            let tag = store.get(&id).cloned().ok_or(RepositoryError::NotFound(id))?;
             Ok(tag)
         }
 
@@ -190,8 +245,13 @@ pub mod test_utils {
         async fn delete(&self, id: i32) -> anyhow::Result<()> {
             let mut store = self.write_store_ref();
             store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
+            *self.last_modified.write().unwrap() = Some(Utc::now());
             Ok(())
         }
+
+        async fn last_modified(&self) -> anyhow::Result<Option<DateTime<Utc>>> {
+            Ok(*self.last_modified.read().unwrap())
+        }
     }
 
     mod test {
@@ -224,5 +284,16 @@ pub mod test_utils {
 
 
         }
+
+        #[tokio::test]
+        async fn ids_for_names_creates_missing_tags() {
+            let repository = TagRepositoryForMemory::new();
+            repository.create("rust".to_string()).await.unwrap();
+
+            let names = vec!["axum".to_string(), "rust".to_string(), "axum".to_string()];
+            let ids = repository.ids_for_names(&names).await.unwrap();
+            assert_eq!(vec![2, 1], ids);
+            assert_eq!(2, repository.all().await.unwrap().len());
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,192 @@
+use anyhow::Ok;
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool};
+
+use super::RepositoryError;
+
+// ログインできるユーザーと、発行したリフレッシュトークン。
+// password_hash は argon2 の PHC 文字列、token_hash はトークンの SHA-256
+#[async_trait]
+pub trait UserRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, username: &str, password_hash: &str) -> anyhow::Result<UserEntity>;
+    async fn find(&self, id: i32) -> anyhow::Result<Option<UserEntity>>;
+    async fn find_by_username(&self, username: &str) -> anyhow::Result<Option<UserEntity>>;
+    async fn save_refresh_token(&self, user_id: i32, token_hash: &str, expires_at: DateTime<Utc>) -> anyhow::Result<()>;
+    // 有効なトークンなら失効させて持ち主の id を返す。使い回せないよう、リフレッシュにも使う
+    async fn revoke_refresh_token(&self, token_hash: &str) -> anyhow::Result<Option<i32>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct UserEntity {
+    pub id: i32,
+    pub username: String,
+    pub password_hash: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct UserRepositoryForDb {
+    pool: PgPool,
+}
+
+impl UserRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        UserRepositoryForDb { pool }
+    }
+}
+
+#[async_trait]
+impl UserRepository for UserRepositoryForDb {
+    async fn create(&self, username: &str, password_hash: &str) -> anyhow::Result<UserEntity> {
+        if let Some(user) = self.find_by_username(username).await? {
+            return Err(RepositoryError::Duplicate(user.id).into());
+        }
+
+        let user = sqlx::query_as::<_, UserEntity>(
+            r#"
+            insert into users (username, password_hash)
+            values ($1, $2)
+            returning id, username, password_hash
+            "#
+        )
+        .bind(username)
+        .bind(password_hash)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<Option<UserEntity>> {
+        let user = sqlx::query_as::<_, UserEntity>(
+            r#"
+            select id, username, password_hash from users where id=$1
+            "#
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn find_by_username(&self, username: &str) -> anyhow::Result<Option<UserEntity>> {
+        let user = sqlx::query_as::<_, UserEntity>(
+            r#"
+            select id, username, password_hash from users where username=$1
+            "#
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn save_refresh_token(&self, user_id: i32, token_hash: &str, expires_at: DateTime<Utc>) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            insert into refresh_tokens (token_hash, user_id, expires_at)
+            values ($1, $2, $3)
+            "#
+        )
+        .bind(token_hash)
+        .bind(user_id)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke_refresh_token(&self, token_hash: &str) -> anyhow::Result<Option<i32>> {
+        // 同時に同じトークンでリフレッシュされても、更新できるのは 1 回だけ
+        let user_id = sqlx::query_scalar::<_, i32>(
+            r#"
+            update refresh_tokens set revoked_at=now()
+            where token_hash=$1 and revoked_at is null and expires_at > now()
+            returning user_id
+            "#
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user_id)
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use anyhow::Ok;
+    use axum::async_trait;
+    use chrono::{DateTime, Utc};
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock};
+
+    use super::{RepositoryError, UserEntity, UserRepository};
+
+    #[derive(Debug, Clone)]
+    struct RefreshToken {
+        user_id: i32,
+        expires_at: DateTime<Utc>,
+        revoked: bool,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct UserRepositoryForMemory {
+        users: Arc<RwLock<Vec<UserEntity>>>,
+        tokens: Arc<RwLock<HashMap<String, RefreshToken>>>,
+    }
+
+    impl UserRepositoryForMemory {
+        pub fn new() -> Self {
+            UserRepositoryForMemory {
+                users: Arc::default(),
+                tokens: Arc::default(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl UserRepository for UserRepositoryForMemory {
+        async fn create(&self, username: &str, password_hash: &str) -> anyhow::Result<UserEntity> {
+            let mut users = self.users.write().unwrap();
+            if let Some(user) = users.iter().find(|user| user.username == username) {
+                return Err(RepositoryError::Duplicate(user.id).into());
+            }
+            let user = UserEntity {
+                id: users.len() as i32 + 1,
+                username: username.to_string(),
+                password_hash: password_hash.to_string(),
+            };
+            users.push(user.clone());
+            Ok(user)
+        }
+
+        async fn find(&self, id: i32) -> anyhow::Result<Option<UserEntity>> {
+            Ok(self.users.read().unwrap().iter().find(|user| user.id == id).cloned())
+        }
+
+        async fn find_by_username(&self, username: &str) -> anyhow::Result<Option<UserEntity>> {
+            Ok(self.users.read().unwrap().iter().find(|user| user.username == username).cloned())
+        }
+
+        async fn save_refresh_token(&self, user_id: i32, token_hash: &str, expires_at: DateTime<Utc>) -> anyhow::Result<()> {
+            let token = RefreshToken { user_id, expires_at, revoked: false };
+            self.tokens.write().unwrap().insert(token_hash.to_string(), token);
+            Ok(())
+        }
+
+        async fn revoke_refresh_token(&self, token_hash: &str) -> anyhow::Result<Option<i32>> {
+            let mut tokens = self.tokens.write().unwrap();
+            let token = tokens
+                .get_mut(token_hash)
+                .filter(|token| !token.revoked && token.expires_at > Utc::now());
+            Ok(token.map(|token| {
+                token.revoked = true;
+                token.user_id
+            }))
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::Ok;
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+use super::RepositoryError;
+
+// 受け取った Webmention。source が target へリンクしているかはワーカーが後から確かめる
+#[async_trait]
+pub trait WebmentionRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    // 同じ source と target の再送は内容が変わった可能性があるので、確認待ちに戻す
+    async fn save(&self, blog_id: i32, source: &str, target: &str) -> anyhow::Result<WebmentionEntity>;
+    async fn find(&self, id: i32) -> anyhow::Result<WebmentionEntity>;
+    async fn set_status(&self, id: i32, status: WebmentionStatus) -> anyhow::Result<()>;
+    async fn pending(&self) -> anyhow::Result<Vec<WebmentionEntity>>;
+    // 確認が取れたものだけを古い順に返す
+    async fn verified_for(&self, blog_id: i32) -> anyhow::Result<Vec<WebmentionEntity>>;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type, JsonSchema)]
+#[sqlx(type_name = "webmention_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum WebmentionStatus {
+    Pending,
+    Verified,
+    Rejected,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow, JsonSchema)]
+pub struct WebmentionEntity {
+    pub id: i32,
+    pub blog_id: i32,
+    pub source: String,
+    pub target: String,
+    pub status: WebmentionStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebmentionRepositoryForDb {
+    pool: PgPool,
+}
+
+impl WebmentionRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        WebmentionRepositoryForDb { pool }
+    }
+}
+
+#[async_trait]
+impl WebmentionRepository for WebmentionRepositoryForDb {
+    async fn save(&self, blog_id: i32, source: &str, target: &str) -> anyhow::Result<WebmentionEntity> {
+        let webmention = sqlx::query_as::<_, WebmentionEntity>(
+            r#"
+            insert into webmentions (blog_id, source, target)
+            values ($1, $2, $3)
+            on conflict (source, target) do update
+            set blog_id=excluded.blog_id, status='pending', updated_at=now()
+            returning *
+            "#
+        )
+        .bind(blog_id)
+        .bind(source)
+        .bind(target)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(webmention)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<WebmentionEntity> {
+        let webmention = sqlx::query_as::<_, WebmentionEntity>(
+            r#"
+            select * from webmentions where id=$1
+            "#
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(webmention)
+    }
+
+    async fn set_status(&self, id: i32, status: WebmentionStatus) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            update webmentions set status=$2, updated_at=now() where id=$1
+            "#
+        )
+        .bind(id)
+        .bind(status)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn pending(&self) -> anyhow::Result<Vec<WebmentionEntity>> {
+        let webmentions = sqlx::query_as::<_, WebmentionEntity>(
+            r#"
+            select * from webmentions where status='pending' order by id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(webmentions)
+    }
+
+    async fn verified_for(&self, blog_id: i32) -> anyhow::Result<Vec<WebmentionEntity>> {
+        let webmentions = sqlx::query_as::<_, WebmentionEntity>(
+            r#"
+            select * from webmentions where blog_id=$1 and status='verified' order by created_at, id
+            "#
+        )
+        .bind(blog_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(webmentions)
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use anyhow::Ok;
+    use axum::async_trait;
+    use chrono::Utc;
+    use std::sync::{Arc, RwLock};
+
+    use super::{RepositoryError, WebmentionEntity, WebmentionRepository, WebmentionStatus};
+
+    #[derive(Debug, Clone)]
+    pub struct WebmentionRepositoryForMemory {
+        store: Arc<RwLock<Vec<WebmentionEntity>>>,
+    }
+
+    impl WebmentionRepositoryForMemory {
+        pub fn new() -> Self {
+            WebmentionRepositoryForMemory { store: Arc::default() }
+        }
+    }
+
+    #[async_trait]
+    impl WebmentionRepository for WebmentionRepositoryForMemory {
+        async fn save(&self, blog_id: i32, source: &str, target: &str) -> anyhow::Result<WebmentionEntity> {
+            let mut store = self.store.write().unwrap();
+            let now = Utc::now();
+            if let Some(webmention) = store
+                .iter_mut()
+                .find(|webmention| webmention.source == source && webmention.target == target)
+            {
+                webmention.blog_id = blog_id;
+                webmention.status = WebmentionStatus::Pending;
+                webmention.updated_at = now;
+                return Ok(webmention.clone());
+            }
+            let webmention = WebmentionEntity {
+                id: store.len() as i32 + 1,
+                blog_id,
+                source: source.to_string(),
+                target: target.to_string(),
+                status: WebmentionStatus::Pending,
+                created_at: now,
+                updated_at: now,
+            };
+            store.push(webmention.clone());
+            Ok(webmention)
+        }
+
+        async fn find(&self, id: i32) -> anyhow::Result<WebmentionEntity> {
+            let store = self.store.read().unwrap();
+            let webmention = store
+                .iter()
+                .find(|webmention| webmention.id == id)
+                .cloned()
+                .ok_or(RepositoryError::NotFound(id))?;
+            Ok(webmention)
+        }
+
+        async fn set_status(&self, id: i32, status: WebmentionStatus) -> anyhow::Result<()> {
+            let mut store = self.store.write().unwrap();
+            let webmention = store
+                .iter_mut()
+                .find(|webmention| webmention.id == id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            webmention.status = status;
+            webmention.updated_at = Utc::now();
+            Ok(())
+        }
+
+        async fn pending(&self) -> anyhow::Result<Vec<WebmentionEntity>> {
+            let store = self.store.read().unwrap();
+            Ok(store
+                .iter()
+                .filter(|webmention| webmention.status == WebmentionStatus::Pending)
+                .cloned()
+                .collect())
+        }
+
+        async fn verified_for(&self, blog_id: i32) -> anyhow::Result<Vec<WebmentionEntity>> {
+            let store = self.store.read().unwrap();
+            Ok(store
+                .iter()
+                .filter(|webmention| {
+                    webmention.blog_id == blog_id && webmention.status == WebmentionStatus::Verified
+                })
+                .cloned()
+                .collect())
+        }
+    }
+}
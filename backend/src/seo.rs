@@ -0,0 +1,199 @@
+use chrono::SecondsFormat;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::config::SiteConfig;
+use crate::oembed;
+use crate::render::{self, escape_html};
+use crate::repositories::blog::{BlogEntity, BlogStatus};
+
+// description が未設定のときに本文から作る抜粋の文字数
+const DESCRIPTION_LENGTH: usize = 160;
+
+// <meta property="og:..."> と <meta name="twitter:..."> のどちらで出すか
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MetaAttribute {
+    Property,
+    Name,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, JsonSchema)]
+pub struct MetaTag {
+    pub attribute: MetaAttribute,
+    pub key: String,
+    pub content: String,
+}
+
+impl MetaTag {
+    fn property(key: &str, content: impl Into<String>) -> Self {
+        MetaTag { attribute: MetaAttribute::Property, key: key.to_string(), content: content.into() }
+    }
+
+    fn name(key: &str, content: impl Into<String>) -> Self {
+        MetaTag { attribute: MetaAttribute::Name, key: key.to_string(), content: content.into() }
+    }
+
+    fn html(&self) -> String {
+        let attribute = match self.attribute {
+            MetaAttribute::Property => "property",
+            MetaAttribute::Name => "name",
+        };
+        format!(r#"<meta {}="{}" content="{}" />"#, attribute, escape_html(&self.key), escape_html(&self.content))
+    }
+}
+
+// フロントエンドの <head> にそのまま埋め込めるメタデータ
+#[derive(Debug, Serialize, Clone, PartialEq, JsonSchema)]
+pub struct BlogMeta {
+    pub title: String,
+    pub description: String,
+    pub canonical_url: String,
+    pub robots: String,
+    pub open_graph: Vec<MetaTag>,
+    pub twitter: Vec<MetaTag>,
+    // schema.org の BlogPosting
+    pub json_ld: Value,
+    // 上記を全て含む <head> 用の HTML
+    pub html: String,
+}
+
+impl BlogMeta {
+    pub fn new(blog: &BlogEntity, site: &SiteConfig) -> Self {
+        let description = blog
+            .seo
+            .description
+            .clone()
+            .unwrap_or_else(|| render::excerpt(&blog.body, DESCRIPTION_LENGTH));
+        let canonical_url = blog.seo.canonical_url.clone().unwrap_or_else(|| site.blog_url(blog.id));
+        // 下書きは公開前なので常に noindex にする
+        let robots = match blog.seo.noindex || blog.status == BlogStatus::Draft {
+            true => "noindex, nofollow",
+            false => "index, follow",
+        };
+        let published = blog.created_at.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let modified = blog.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true);
+
+        let mut open_graph = vec![
+            MetaTag::property("og:type", "article"),
+            MetaTag::property("og:title", &blog.title),
+            MetaTag::property("og:description", &description),
+            MetaTag::property("og:url", &canonical_url),
+            MetaTag::property("og:site_name", &site.title),
+            MetaTag::property("og:locale", "ja_JP"),
+            MetaTag::property("article:published_time", &published),
+            MetaTag::property("article:modified_time", &modified),
+        ];
+        open_graph.extend(blog.tags.iter().map(|tag| MetaTag::property("article:tag", &tag.name)));
+        let mut twitter = vec![
+            MetaTag::name(
+                "twitter:card",
+                if blog.seo.image.is_some() { "summary_large_image" } else { "summary" },
+            ),
+            MetaTag::name("twitter:title", &blog.title),
+            MetaTag::name("twitter:description", &description),
+        ];
+        if let Some(image) = &blog.seo.image {
+            open_graph.push(MetaTag::property("og:image", image));
+            twitter.push(MetaTag::name("twitter:image", image));
+        }
+
+        let mut json_ld = json!({
+            "@context": "https://schema.org",
+            "@type": "BlogPosting",
+            "headline": blog.title,
+            "description": description,
+            "url": canonical_url,
+            "mainEntityOfPage": { "@type": "WebPage", "@id": canonical_url },
+            "datePublished": published,
+            "dateModified": modified,
+            "author": { "@type": "Person", "name": site.author },
+            "publisher": { "@type": "Organization", "name": site.title, "url": site.url("/") },
+            "keywords": blog.tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>().join(", "),
+        });
+        if let Some(image) = &blog.seo.image {
+            json_ld["image"] = json!([image]);
+        }
+
+        let mut html = head_html(&blog.title, &description, &canonical_url, robots, &open_graph, &twitter, &json_ld);
+        // 下書きは oEmbed でも返さないので公開済みの記事だけ discovery リンクを置く
+        if blog.status == BlogStatus::Published {
+            html.push('\n');
+            html.push_str(&oembed::discovery_links(blog, site));
+        }
+        BlogMeta {
+            title: blog.title.clone(),
+            description,
+            canonical_url,
+            robots: robots.to_string(),
+            open_graph,
+            twitter,
+            json_ld,
+            html,
+        }
+    }
+}
+
+fn head_html(
+    title: &str,
+    description: &str,
+    canonical_url: &str,
+    robots: &str,
+    open_graph: &[MetaTag],
+    twitter: &[MetaTag],
+    json_ld: &Value,
+) -> String {
+    let mut lines = vec![
+        format!("<title>{}</title>", escape_html(title)),
+        MetaTag::name("description", description).html(),
+        MetaTag::name("robots", robots).html(),
+        format!(r#"<link rel="canonical" href="{}" />"#, escape_html(canonical_url)),
+    ];
+    lines.extend(open_graph.iter().chain(twitter).map(MetaTag::html));
+    // </script> で閉じられないように < をエスケープする
+    let json_ld = json_ld.to_string().replace('<', "\\u003c");
+    lines.push(format!(r#"<script type="application/ld+json">{}</script>"#, json_ld));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{blog::BlogSeo, tag::Tag};
+
+    #[test]
+    fn build_meta_from_blog() {
+        let site = SiteConfig::default();
+        let mut blog = BlogEntity::new(
+            1,
+            "Hello </script> & co".to_string(),
+            "**bold** text".to_string(),
+            vec![Tag::new(1, "rust".to_string())],
+        );
+        let meta = BlogMeta::new(&blog, &site);
+        assert_eq!("bold text", meta.description);
+        assert_eq!("http://localhost:3001/blogs/1", meta.canonical_url);
+        assert_eq!("index, follow", meta.robots);
+        assert!(meta.open_graph.contains(&MetaTag::property("article:tag", "rust")));
+        assert!(meta.twitter.contains(&MetaTag::name("twitter:card", "summary")));
+        assert_eq!("BlogPosting", meta.json_ld["@type"]);
+        assert_eq!("Hello </script> & co", meta.json_ld["headline"]);
+        assert!(meta.html.contains(r#"<meta property="og:title" content="Hello &lt;/script&gt; &amp; co" />"#));
+        assert_eq!(1, meta.html.matches("</script>").count());
+
+        blog.seo = BlogSeo {
+            description: Some("custom".to_string()),
+            canonical_url: Some("https://example.com/hello".to_string()),
+            noindex: true,
+            image: Some("https://example.com/hello.png".to_string()),
+        };
+        let meta = BlogMeta::new(&blog, &site);
+        assert_eq!("custom", meta.description);
+        assert_eq!("https://example.com/hello", meta.json_ld["mainEntityOfPage"]["@id"]);
+        assert_eq!("noindex, nofollow", meta.robots);
+        assert!(meta.open_graph.contains(&MetaTag::property("og:image", "https://example.com/hello.png")));
+        assert!(meta.twitter.contains(&MetaTag::name("twitter:card", "summary_large_image")));
+        assert_eq!(serde_json::json!(["https://example.com/hello.png"]), meta.json_ld["image"]);
+    }
+}
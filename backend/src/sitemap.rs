@@ -0,0 +1,233 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::config::SiteConfig;
+use crate::render::escape_html;
+use crate::repositories::{
+    blog::{BlogEntity, BlogStatus},
+    tag::Tag,
+};
+
+pub const CONTENT_TYPE: &str = "application/xml; charset=utf-8";
+// 1つの sitemap に載せられる URL の上限 (sitemaps.org)
+pub const MAX_URLS: usize = 50_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SitemapUrl {
+    pub loc: String,
+    pub lastmod: Option<DateTime<Utc>>,
+}
+
+// 記事とタグの一覧が最後に変わった時刻
+pub type SitemapVersion = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+// 作った URL の一覧を、記事かタグの一覧が変わるまで使い回す。
+// sitemap index の各ページを返すたびに全件を読み直さないためのもの
+#[derive(Debug, Clone, Default)]
+pub struct SitemapCache(Arc<RwLock<Option<(SitemapVersion, Arc<Vec<SitemapUrl>>)>>>);
+
+impl SitemapCache {
+    pub fn get(&self, version: SitemapVersion) -> Option<Arc<Vec<SitemapUrl>>> {
+        match &*self.0.read().unwrap() {
+            Some((cached, urls)) if *cached == version => Some(urls.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn put(&self, version: SitemapVersion, urls: Vec<SitemapUrl>) -> Arc<Vec<SitemapUrl>> {
+        let urls = Arc::new(urls);
+        *self.0.write().unwrap() = Some((version, urls.clone()));
+        urls
+    }
+}
+
+fn timestamp(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// トップページ、公開済みで noindex でないブログ、タグのページ。タグの lastmod はそのタグの最新のブログ
+pub fn urls(blogs: &[BlogEntity], tags: &[Tag], site: &SiteConfig) -> Vec<SitemapUrl> {
+    let published: Vec<&BlogEntity> = blogs
+        .iter()
+        .filter(|blog| blog.status == BlogStatus::Published && !blog.seo.noindex)
+        .collect();
+    let mut tag_lastmod: HashMap<i32, DateTime<Utc>> = HashMap::new();
+    for blog in &published {
+        for tag in &blog.tags {
+            let lastmod = tag_lastmod.entry(tag.id).or_insert(blog.updated_at);
+            *lastmod = (*lastmod).max(blog.updated_at);
+        }
+    }
+
+    let mut urls = vec![SitemapUrl {
+        loc: site.url("/"),
+        lastmod: published.iter().map(|blog| blog.updated_at).max(),
+    }];
+    urls.extend(published.iter().map(|blog| SitemapUrl {
+        loc: site.blog_url(blog.id),
+        lastmod: Some(blog.updated_at),
+    }));
+    urls.extend(tags.iter().map(|tag| SitemapUrl {
+        loc: site.url(&format!("/tags/{}", tag.id)),
+        lastmod: tag_lastmod.get(&tag.id).copied(),
+    }));
+    urls
+}
+
+fn lastmod_element(lastmod: Option<DateTime<Utc>>) -> String {
+    lastmod
+        .map(|lastmod| format!("<lastmod>{}</lastmod>", timestamp(lastmod)))
+        .unwrap_or_default()
+}
+
+pub fn lastmod(urls: &[SitemapUrl]) -> Option<DateTime<Utc>> {
+    urls.iter().filter_map(|url| url.lastmod).max()
+}
+
+pub fn urlset(urls: &[SitemapUrl]) -> String {
+    let entries: String = urls
+        .iter()
+        .map(|url| format!("\n  <url><loc>{}</loc>{}</url>", escape_html(&url.loc), lastmod_element(url.lastmod)))
+        .collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">{}\n</urlset>\n",
+        entries
+    )
+}
+
+pub fn page_path(page: usize) -> String {
+    format!("/sitemaps/{}.xml", page)
+}
+
+fn index(pages: &[&[SitemapUrl]], site: &SiteConfig) -> String {
+    let entries: String = pages
+        .iter()
+        .enumerate()
+        .map(|(i, urls)| {
+            format!(
+                "\n  <sitemap><loc>{}</loc>{}</sitemap>",
+                escape_html(&site.url(&page_path(i + 1))),
+                lastmod_element(lastmod(urls))
+            )
+        })
+        .collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">{}\n</sitemapindex>\n",
+        entries
+    )
+}
+
+// per_page 件を超えたら sitemap index にして、各ページを /sitemaps/{n}.xml で返す
+pub fn sitemap(urls: &[SitemapUrl], site: &SiteConfig, per_page: usize) -> String {
+    if urls.len() <= per_page {
+        return urlset(urls);
+    }
+    let pages: Vec<&[SitemapUrl]> = urls.chunks(per_page).collect();
+    index(&pages, site)
+}
+
+// page は 1 始まり。ページの XML は urlset で作る
+pub fn sitemap_page(urls: &[SitemapUrl], page: usize, per_page: usize) -> Option<&[SitemapUrl]> {
+    if urls.len() <= per_page || page == 0 {
+        return None;
+    }
+    urls.chunks(per_page).nth(page - 1)
+}
+
+pub fn robots(site: &SiteConfig) -> String {
+    let rules: String = match site.robots_disallow.is_empty() {
+        true => "Disallow:\n".to_string(),
+        false => site
+            .robots_disallow
+            .iter()
+            .map(|path| format!("Disallow: {}\n", path))
+            .collect(),
+    };
+    format!("User-agent: *\n{}\nSitemap: {}\n", rules, site.url("/sitemap.xml"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fixture() -> (Vec<BlogEntity>, Vec<Tag>) {
+        let rust = Tag::new(1, "rust".to_string());
+        let mut draft = BlogEntity::new(3, "draft".to_string(), String::new(), vec![rust.clone()]);
+        draft.status = BlogStatus::Draft;
+        let blogs = vec![
+            draft,
+            BlogEntity::new(2, "second".to_string(), String::new(), vec![rust.clone()]),
+            BlogEntity::new(1, "first".to_string(), String::new(), vec![]),
+        ];
+        (blogs, vec![rust, Tag::new(2, "empty".to_string())])
+    }
+
+    #[test]
+    fn list_published_blogs_and_tags() {
+        let (blogs, tags) = fixture();
+        let site = SiteConfig::default();
+        let urls = urls(&blogs, &tags, &site);
+        let locs: Vec<&str> = urls.iter().map(|url| url.loc.as_str()).collect();
+        assert_eq!(
+            vec![
+                "http://localhost:3001/",
+                "http://localhost:3001/blogs/2",
+                "http://localhost:3001/blogs/1",
+                "http://localhost:3001/tags/1",
+                "http://localhost:3001/tags/2",
+            ],
+            locs
+        );
+        assert_eq!(Some(blogs[1].updated_at), urls[3].lastmod);
+        assert_eq!(None, urls[4].lastmod);
+
+        let xml = sitemap(&urls, &site, MAX_URLS);
+        assert!(xml.contains("<urlset"));
+        assert_eq!(5, xml.matches("<url>").count());
+        assert_eq!(None, sitemap_page(&urls, 1, MAX_URLS));
+    }
+
+    #[test]
+    fn split_into_sitemap_index() {
+        let (blogs, tags) = fixture();
+        let site = SiteConfig::default();
+        let urls = urls(&blogs, &tags, &site);
+
+        let xml = sitemap(&urls, &site, 2);
+        assert!(xml.contains("<sitemapindex"));
+        assert_eq!(3, xml.matches("<sitemap>").count());
+        assert!(xml.contains("<loc>http://localhost:3001/sitemaps/3.xml</loc>"));
+
+        let page = sitemap_page(&urls, 1, 2).unwrap();
+        assert_eq!(2, urlset(page).matches("<url>").count());
+        assert_eq!(urls[0].lastmod, lastmod(page));
+        let page = sitemap_page(&urls, 3, 2).unwrap();
+        assert_eq!(1, urlset(page).matches("<url>").count());
+        assert_eq!(None, lastmod(page));
+        assert_eq!(None, sitemap_page(&urls, 4, 2));
+    }
+
+    #[test]
+    fn reuse_urls_until_collections_change() {
+        let (blogs, tags) = fixture();
+        let cache = SitemapCache::default();
+        let version = (Some(blogs[0].updated_at), None);
+        assert_eq!(None, cache.get(version));
+
+        let urls = cache.put(version, urls(&blogs, &tags, &SiteConfig::default()));
+        assert_eq!(Some(urls), cache.get(version));
+        assert_eq!(None, cache.get((Some(Utc::now()), None)));
+    }
+
+    #[test]
+    fn robots_references_sitemap() {
+        let mut site = SiteConfig::default();
+        assert_eq!(
+            "User-agent: *\nDisallow: /api/\nDisallow: /graphql\n\nSitemap: http://localhost:3001/sitemap.xml\n",
+            robots(&site)
+        );
+        site.robots_disallow = vec![];
+        assert!(robots(&site).starts_with("User-agent: *\nDisallow:\n"));
+    }
+}
@@ -0,0 +1,399 @@
+use chrono::SecondsFormat;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use tokio::fs;
+
+use crate::config::SiteConfig;
+use crate::feed::{self, atom, json, rss};
+use crate::render::{escape_html, excerpt, markdown_to_html};
+use crate::repositories::{
+    blog::{BlogEntity, BlogRepository},
+    tag::{Tag, TagRepository},
+};
+use crate::seo::BlogMeta;
+use crate::sitemap::{self, MAX_URLS};
+
+pub const PER_PAGE: usize = 10;
+// 公開済みのブログを何件ずつ読み出すか
+const FETCH_SIZE: i64 = 100;
+const EXCERPT_LENGTH: usize = 200;
+// 前回書き出したファイルの一覧。今回書き出さなかったものを消すのに使う
+const MANIFEST: &str = ".static-manifest";
+
+// {{ name }} を値で置き換えるだけのテンプレート。値は呼び出し側でエスケープしておく
+#[derive(Debug, Clone)]
+pub struct Templates {
+    layout: String,
+    post: String,
+    list: String,
+    item: String,
+}
+
+impl Templates {
+    pub fn builtin() -> Self {
+        Templates {
+            layout: include_str!("../templates/static/layout.html").to_string(),
+            post: include_str!("../templates/static/post.html").to_string(),
+            list: include_str!("../templates/static/list.html").to_string(),
+            item: include_str!("../templates/static/item.html").to_string(),
+        }
+    }
+
+    // dir にある同じ名前のファイルで組み込みのテンプレートを上書きする
+    pub fn load(dir: Option<&Path>) -> anyhow::Result<Self> {
+        let mut templates = Self::builtin();
+        if let Some(dir) = dir {
+            for (name, template) in [
+                ("layout.html", &mut templates.layout),
+                ("post.html", &mut templates.post),
+                ("list.html", &mut templates.list),
+                ("item.html", &mut templates.item),
+            ] {
+                let path = dir.join(name);
+                if path.exists() {
+                    *template = std::fs::read_to_string(path)?;
+                }
+            }
+        }
+        Ok(templates)
+    }
+}
+
+// 値の中に {{ }} があっても展開しないように、テンプレートを先頭から1回だけ走査する
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        let key = rest[start + 2..end].trim();
+        match values.iter().find(|(name, _)| *name == key) {
+            Some((_, value)) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BuildReport {
+    pub written: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+struct Renderer<'a> {
+    site: &'a SiteConfig,
+    templates: &'a Templates,
+    per_page: usize,
+}
+
+impl Renderer<'_> {
+    fn layout(&self, head: &str, content: &str) -> String {
+        fill(
+            &self.templates.layout,
+            &[("head", head), ("site_title", &escape_html(&self.site.title)), ("content", content)],
+        )
+    }
+
+    fn post(&self, blog: &BlogEntity) -> String {
+        let tags: String = blog
+            .tags
+            .iter()
+            .map(|tag| format!(r#"<li><a href="{}">{}</a></li>"#, tag_path(tag.id, 1), escape_html(&tag.name)))
+            .collect();
+        let content = fill(
+            &self.templates.post,
+            &[
+                ("title", &escape_html(&blog.title)),
+                ("datetime", &blog.created_at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+                ("date", &blog.created_at.format("%Y-%m-%d").to_string()),
+                ("tags", &tags),
+                ("body", &markdown_to_html(&blog.body)),
+            ],
+        );
+        self.layout(&BlogMeta::new(blog, self.site).html, &content)
+    }
+
+    // 1ページ目は base、2ページ目以降は base/page/{n}/ に置く
+    fn list_pages(&self, heading: &str, blogs: &[&BlogEntity], page_path: impl Fn(usize) -> String) -> Vec<(String, String)> {
+        let chunks: Vec<&[&BlogEntity]> = match blogs.is_empty() {
+            true => vec![&[]],
+            false => blogs.chunks(self.per_page).collect(),
+        };
+        let last = chunks.len();
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(i, blogs)| {
+                let page = i + 1;
+                let items: String = blogs.iter().map(|blog| self.item(blog)).collect();
+                let mut pagination = Vec::new();
+                if page > 1 {
+                    pagination.push(format!(r#"<a rel="prev" href="{}">前へ</a>"#, page_path(page - 1)));
+                }
+                if page < last {
+                    pagination.push(format!(r#"<a rel="next" href="{}">次へ</a>"#, page_path(page + 1)));
+                }
+                let content = fill(
+                    &self.templates.list,
+                    &[
+                        ("heading", &escape_html(heading)),
+                        ("items", &items),
+                        ("pagination", &pagination.join(" ")),
+                    ],
+                );
+                let head = format!(
+                    r#"<title>{}</title>
+    <link rel="canonical" href="{}" />"#,
+                    escape_html(heading),
+                    escape_html(&self.site.url(&page_path(page))),
+                );
+                (file_path(&page_path(page)), self.layout(&head, &content))
+            })
+            .collect()
+    }
+
+    fn item(&self, blog: &BlogEntity) -> String {
+        fill(
+            &self.templates.item,
+            &[
+                ("url", &format!("/blogs/{}/", blog.id)),
+                ("title", &escape_html(&blog.title)),
+                ("datetime", &blog.created_at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+                ("date", &blog.created_at.format("%Y-%m-%d").to_string()),
+                ("excerpt", &escape_html(&excerpt(&blog.body, EXCERPT_LENGTH))),
+            ],
+        )
+    }
+}
+
+fn index_path(page: usize) -> String {
+    match page {
+        1 => "/".to_string(),
+        page => format!("/page/{}/", page),
+    }
+}
+
+fn tag_path(id: i32, page: usize) -> String {
+    match page {
+        1 => format!("/tags/{}/", id),
+        page => format!("/tags/{}/page/{}/", id, page),
+    }
+}
+
+// /tags/1/ のようなディレクトリの URL は index.html に書き出す
+fn file_path(url_path: &str) -> String {
+    let path = url_path.trim_start_matches('/');
+    match path.is_empty() || path.ends_with('/') {
+        true => format!("{}index.html", path),
+        false => path.to_string(),
+    }
+}
+
+// blogs は公開済みの新しい順、tags は id 順に並んでいること。同じ入力からは常に同じファイルを作る
+pub fn render_site(
+    blogs: &[BlogEntity],
+    tags: &[Tag],
+    site: &SiteConfig,
+    templates: &Templates,
+    per_page: usize,
+) -> BTreeMap<String, String> {
+    let renderer = Renderer { site, templates, per_page: per_page.max(1) };
+    let mut files = BTreeMap::new();
+
+    for blog in blogs {
+        files.insert(file_path(&format!("/blogs/{}/", blog.id)), renderer.post(blog));
+    }
+    let all: Vec<&BlogEntity> = blogs.iter().collect();
+    files.extend(renderer.list_pages(&site.title, &all, index_path));
+
+    let feed_limit = (site.feed_limit.max(0) as usize).min(blogs.len());
+    for tag in tags {
+        let tagged: Vec<&BlogEntity> = blogs
+            .iter()
+            .filter(|blog| blog.tags.iter().any(|t| t.id == tag.id))
+            .collect();
+        files.extend(renderer.list_pages(&tag.name, &tagged, |page| tag_path(tag.id, page)));
+        let feed: Vec<BlogEntity> = tagged.iter().take(feed_limit).map(|blog| (*blog).clone()).collect();
+        files.insert(format!("tags/{}/feed.atom", tag.id), atom::atom(&feed, site, Some(tag), feed::last_modified(&feed)));
+    }
+
+    let recent = &blogs[..feed_limit];
+    files.insert("feed.rss".to_string(), rss::rss(recent, site, feed::last_modified(recent)));
+    files.insert("feed.atom".to_string(), atom::atom(recent, site, None, feed::last_modified(recent)));
+    let json_feed = json::json_feed(recent, site, 1, false);
+    files.insert(
+        file_path(json::FEED_PATH),
+        serde_json::to_string_pretty(&json_feed).unwrap_or_default(),
+    );
+
+    let urls = sitemap::urls(blogs, tags, site);
+    files.insert("sitemap.xml".to_string(), sitemap::sitemap(&urls, site, MAX_URLS));
+    let mut page = 1;
+    while let Some(page_urls) = sitemap::sitemap_page(&urls, page, MAX_URLS) {
+        files.insert(file_path(&sitemap::page_path(page)), sitemap::urlset(page_urls));
+        page += 1;
+    }
+    files.insert("robots.txt".to_string(), sitemap::robots(site));
+    files
+}
+
+// 内容が変わったファイルだけを書き、前回あって今回無いファイルは消す
+pub async fn write_site(out: &Path, files: &BTreeMap<String, String>) -> anyhow::Result<BuildReport> {
+    let mut report = BuildReport::default();
+    for (path, content) in files {
+        let target = out.join(path);
+        if fs::read(&target).await.ok().as_deref() == Some(content.as_bytes()) {
+            report.unchanged += 1;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&target, content).await?;
+        report.written += 1;
+    }
+
+    let manifest = out.join(MANIFEST);
+    let previous = fs::read_to_string(&manifest).await.unwrap_or_default();
+    let stale: BTreeSet<&str> = previous
+        .lines()
+        .filter(|path| !path.is_empty() && !files.contains_key(*path))
+        .collect();
+    for path in stale {
+        let target = out.join(path);
+        if fs::remove_file(&target).await.is_ok() {
+            report.removed += 1;
+        }
+        // 空になったディレクトリも消す。中身が残っていれば失敗するので無視する
+        let mut dir = target.parent();
+        while let Some(parent) = dir.filter(|parent| *parent != out) {
+            if fs::remove_dir(parent).await.is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+    }
+    let paths: Vec<&str> = files.keys().map(String::as_str).collect();
+    fs::write(&manifest, format!("{}\n", paths.join("\n"))).await?;
+    Ok(report)
+}
+
+async fn published_blogs<B: BlogRepository>(repository: &B) -> anyhow::Result<Vec<BlogEntity>> {
+    let mut blogs = Vec::new();
+    loop {
+        let page = repository.recent_published(FETCH_SIZE, blogs.len() as i64, None).await?;
+        let done = (page.len() as i64) < FETCH_SIZE;
+        blogs.extend(page);
+        if done {
+            return Ok(blogs);
+        }
+    }
+}
+
+pub async fn build<B: BlogRepository, T: TagRepository>(
+    blog_repository: &B,
+    tag_repository: &T,
+    site: &SiteConfig,
+    templates: &Templates,
+    out: &Path,
+) -> anyhow::Result<BuildReport> {
+    let blogs = published_blogs(blog_repository).await?;
+    let mut tags = tag_repository.all().await?;
+    tags.sort_by_key(|tag| tag.id);
+    let files = render_site(&blogs, &tags, site, templates, PER_PAGE);
+    write_site(out, &files).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn fixture() -> (Vec<BlogEntity>, Vec<Tag>) {
+        let rust = Tag::new(1, "Rust & axum".to_string());
+        let empty = Tag::new(2, "empty".to_string());
+        let base = Utc.ymd(2023, 11, 1).and_hms(0, 0, 0);
+        let blogs = (1..=3)
+            .rev()
+            .map(|id| BlogEntity {
+                created_at: base + Duration::days(id as i64),
+                updated_at: base + Duration::days(id as i64),
+                ..BlogEntity::new(id, format!("post <{}>", id), format!("body {{{{ title }}}} {}", id), vec![rust.clone()])
+            })
+            .collect();
+        (blogs, vec![rust, empty])
+    }
+
+    #[test]
+    fn render_every_page() {
+        let (blogs, tags) = fixture();
+        let site = SiteConfig::default();
+        let files = render_site(&blogs, &tags, &site, &Templates::builtin(), 2);
+
+        let paths: Vec<&str> = files.keys().map(String::as_str).collect();
+        assert_eq!(
+            vec![
+                "blogs/1/index.html",
+                "blogs/2/index.html",
+                "blogs/3/index.html",
+                "feed.atom",
+                "feed.json",
+                "feed.rss",
+                "index.html",
+                "page/2/index.html",
+                "robots.txt",
+                "sitemap.xml",
+                "tags/1/feed.atom",
+                "tags/1/index.html",
+                "tags/1/page/2/index.html",
+                "tags/2/feed.atom",
+                "tags/2/index.html",
+            ],
+            paths
+        );
+
+        let post = &files["blogs/3/index.html"];
+        assert!(post.contains("<h1>post &lt;3&gt;</h1>"));
+        assert!(post.contains(r#"<link rel="canonical" href="http://localhost:3001/blogs/3" />"#));
+        // 本文に {{ }} があってもテンプレートとして展開しない
+        assert!(post.contains("body {{ title }} 3"));
+        assert!(post.contains(r#"<a href="/tags/1/">Rust &amp; axum</a>"#));
+
+        let index = &files["index.html"];
+        assert!(index.contains(r#"<a href="/blogs/3/">post &lt;3&gt;</a>"#));
+        assert!(index.contains(r#"<a rel="next" href="/page/2/">"#));
+        assert!(files["page/2/index.html"].contains(r#"<a rel="prev" href="/">"#));
+
+        // 同じ入力からは同じ出力になる
+        assert_eq!(files, render_site(&blogs, &tags, &site, &Templates::builtin(), 2));
+    }
+
+    #[tokio::test]
+    async fn write_only_changed_files_and_remove_stale() {
+        let out = std::env::temp_dir().join(format!("next_blog-static-{}", std::process::id()));
+        let files = |entries: &[(&str, &str)]| -> BTreeMap<String, String> {
+            entries.iter().map(|(path, content)| (path.to_string(), content.to_string())).collect()
+        };
+
+        let report = write_site(&out, &files(&[("index.html", "index"), ("blogs/1/index.html", "one")]))
+            .await
+            .unwrap();
+        assert_eq!(BuildReport { written: 2, unchanged: 0, removed: 0 }, report);
+
+        let report = write_site(&out, &files(&[("index.html", "index"), ("blogs/2/index.html", "two")]))
+            .await
+            .unwrap();
+        assert_eq!(BuildReport { written: 1, unchanged: 1, removed: 1 }, report);
+        assert!(!out.join("blogs/1").exists());
+        assert_eq!("two", std::fs::read_to_string(out.join("blogs/2/index.html")).unwrap());
+
+        std::fs::remove_dir_all(&out).unwrap();
+    }
+}
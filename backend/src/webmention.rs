@@ -0,0 +1,376 @@
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use reqwest::{
+    header::{ACCEPT, LINK},
+    StatusCode, Url,
+};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::config::{OutboundConfig, SiteConfig};
+use crate::import::html::{attribute, start_tags};
+use crate::oembed;
+use crate::outbound::{read_body, OutboundClient};
+use crate::render::escape_html;
+use crate::repositories::{
+    blog::{BlogRepository, BlogStatus},
+    webmention::{WebmentionEntity, WebmentionRepository, WebmentionStatus},
+};
+
+pub const WEBMENTION_PATH: &str = "/webmention";
+// 巨大なページで止まらないよう、取得する本文はこの長さまでにする
+const MAX_BODY: usize = 1024 * 1024;
+
+// 記事ページの head に置くエンドポイントの告知
+pub fn discovery_link(site: &SiteConfig) -> String {
+    format!(
+        r#"<link rel="webmention" href="{}" />"#,
+        escape_html(&site.url(WEBMENTION_PATH))
+    )
+}
+
+fn http_url(value: &str) -> Option<Url> {
+    Url::parse(value)
+        .ok()
+        .filter(|url| url.scheme() == "http" || url.scheme() == "https")
+}
+
+// 受け取った source と target を確かめて、target の記事の id を返す
+pub fn validate(source: &str, target: &str, site: &SiteConfig) -> Result<i32, String> {
+    let source_url = http_url(source).ok_or_else(|| "source must be an http(s) URL".to_string())?;
+    let target_url = http_url(target).ok_or_else(|| "target must be an http(s) URL".to_string())?;
+    if source_url == target_url {
+        return Err("source and target must be different".to_string());
+    }
+    oembed::resolve(target, site).ok_or_else(|| "target is not a post on this site".to_string())
+}
+
+// 本文の Markdown にあるほかのサイトへのリンク。インラインの HTML の <a> も含める
+pub fn outbound_links(markdown: &str, site: &SiteConfig) -> Vec<String> {
+    let site_host = Url::parse(&site.base_url)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from));
+    let mut destinations: Vec<String> = Vec::new();
+    for event in Parser::new_ext(markdown, Options::all()) {
+        match event {
+            Event::Start(Tag::Link(_, destination, _)) => destinations.push(destination.to_string()),
+            Event::Html(html) => destinations.extend(
+                start_tags(&html)
+                    .into_iter()
+                    .filter(|(name, _)| name == "a")
+                    .filter_map(|(_, attributes)| attribute(attributes, "href")),
+            ),
+            _ => {}
+        }
+    }
+
+    let mut links: Vec<String> = Vec::new();
+    for destination in destinations {
+        let outbound = http_url(&destination)
+            .map(|url| url.host_str().map(String::from) != site_host)
+            .unwrap_or(false);
+        if outbound && !links.contains(&destination) {
+            links.push(destination);
+        }
+    }
+    links
+}
+
+// source のページに target へのリンク (href / src) があるか。相対 URL は source の URL で解決する
+pub fn links_to(html: &str, source: &Url, target: &str) -> bool {
+    let target = match Url::parse(target) {
+        Ok(target) => target,
+        Err(_) => return false,
+    };
+    start_tags(html).into_iter().any(|(_, attributes)| {
+        ["href", "src"]
+            .iter()
+            .filter_map(|key| attribute(attributes, key))
+            .any(|value| source.join(&value).map(|url| url == target).unwrap_or(false))
+    })
+}
+
+fn is_webmention_rel(rel: &str) -> bool {
+    rel.split_whitespace().any(|rel| rel.eq_ignore_ascii_case("webmention"))
+}
+
+// Link: <https://example.com/webmention>; rel="webmention"
+fn link_header_endpoint(value: &str) -> Option<&str> {
+    value.split(',').find_map(|link| {
+        let mut parts = link.split(';');
+        let url = parts.next()?.trim().strip_prefix('<')?.strip_suffix('>')?;
+        let is_webmention = parts.any(|param| match param.split_once('=') {
+            Some((key, value)) => key.trim().eq_ignore_ascii_case("rel") && is_webmention_rel(value.trim().trim_matches('"')),
+            None => false,
+        });
+        match is_webmention {
+            true => Some(url),
+            false => None,
+        }
+    })
+}
+
+// Link ヘッダ、HTML の <link> / <a> の順にエンドポイントを探す (Webmention 3.1.2)
+pub fn discover_endpoint(target: &Url, link_headers: &[String], html: &str) -> Option<Url> {
+    let endpoint = link_headers
+        .iter()
+        .find_map(|value| link_header_endpoint(value).map(String::from))
+        .or_else(|| {
+            start_tags(html)
+                .into_iter()
+                .filter(|(name, _)| name == "link" || name == "a")
+                .find(|(_, attributes)| {
+                    attribute(attributes, "rel").map(|rel| is_webmention_rel(&rel)).unwrap_or(false)
+                })
+                .and_then(|(_, attributes)| attribute(attributes, "href"))
+        })?;
+    target.join(&endpoint).ok()
+}
+
+struct Page {
+    url: Url,
+    status: StatusCode,
+    links: Vec<String>,
+    body: String,
+}
+
+// source も target も外から渡された URL なので、内部のネットワークには繋がない (OutboundClient)
+async fn fetch(client: &OutboundClient, url: &str) -> anyhow::Result<Page> {
+    let mut response = client.get(url)?.header(ACCEPT, "text/html").send().await?;
+    let url = response.url().clone();
+    let status = response.status();
+    let links = response
+        .headers()
+        .get_all(LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(String::from)
+        .collect();
+    let body = read_body(&mut response, MAX_BODY).await?;
+    Ok(Page { url, status, links, body: String::from_utf8_lossy(&body).to_string() })
+}
+
+// source を取得して target へのリンクがあれば確認済みにする。削除されたページやリンクが消えたページは拒否する
+pub async fn verify(client: &OutboundClient, webmention: &WebmentionEntity) -> anyhow::Result<WebmentionStatus> {
+    let page = fetch(client, &webmention.source).await?;
+    match page.status.is_success() && links_to(&page.body, &page.url, &webmention.target) {
+        true => Ok(WebmentionStatus::Verified),
+        false => Ok(WebmentionStatus::Rejected),
+    }
+}
+
+// target のエンドポイントに通知する。エンドポイントが無いサイトなら false
+pub async fn send(client: &OutboundClient, source: &str, target: &str) -> anyhow::Result<bool> {
+    let page = fetch(client, target).await?;
+    let endpoint = match discover_endpoint(&page.url, &page.links, &page.body) {
+        Some(endpoint) => endpoint,
+        None => return Ok(false),
+    };
+    client
+        .post(endpoint.as_str())?
+        .form(&[("source", source), ("target", target)])
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(true)
+}
+
+// 公開中の記事の本文にあるリンク先へ通知し、送れた数を返す
+pub async fn send_for_blog<B: BlogRepository>(
+    client: &OutboundClient,
+    repository: &B,
+    blog_id: i32,
+    site: &SiteConfig,
+) -> anyhow::Result<usize> {
+    let blog = repository.find(blog_id).await?;
+    if blog.status != BlogStatus::Published {
+        return Ok(0);
+    }
+    let source = site.blog_url(blog.id);
+    let mut sent = 0;
+    for target in outbound_links(&blog.body, site) {
+        match send(client, &source, &target).await {
+            Ok(true) => sent += 1,
+            Ok(false) => {}
+            Err(e) => tracing::warn!("webmention to {} failed: {}", target, e),
+        }
+    }
+    Ok(sent)
+}
+
+#[derive(Debug)]
+enum Job {
+    Verify(i32),
+    Send(i32),
+}
+
+// ハンドラからバックグラウンドのワーカーへ仕事を渡す。外部サイトへのアクセスはレスポンスを待たせない
+#[derive(Debug, Clone)]
+pub struct WebmentionQueue {
+    sender: mpsc::UnboundedSender<Job>,
+}
+
+impl WebmentionQueue {
+    pub fn verify(&self, webmention_id: i32) {
+        // ワーカーが止まっていても書き込み自体は失敗させない
+        let _ = self.sender.send(Job::Verify(webmention_id));
+    }
+
+    pub fn send(&self, blog_id: i32) {
+        let _ = self.sender.send(Job::Send(blog_id));
+    }
+}
+
+pub fn spawn<B: BlogRepository, W: WebmentionRepository>(
+    blog_repository: Arc<B>,
+    webmention_repository: Arc<W>,
+    site: SiteConfig,
+    outbound: OutboundConfig,
+) -> WebmentionQueue {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let client = OutboundClient::new(&outbound);
+        // 再起動前に確認できなかったものからやり直す
+        match webmention_repository.pending().await {
+            Ok(pending) => {
+                for webmention in pending {
+                    verify_and_save(&client, &*webmention_repository, &webmention).await;
+                }
+            }
+            Err(e) => tracing::warn!("fail load pending webmentions: {}", e),
+        }
+        while let Some(job) = receiver.recv().await {
+            match job {
+                Job::Verify(id) => match webmention_repository.find(id).await {
+                    Ok(webmention) => verify_and_save(&client, &*webmention_repository, &webmention).await,
+                    Err(e) => tracing::warn!("fail load webmention {}: {}", id, e),
+                },
+                Job::Send(blog_id) => {
+                    if let Err(e) = send_for_blog(&client, &*blog_repository, blog_id, &site).await {
+                        tracing::warn!("fail send webmentions for blog {}: {}", blog_id, e);
+                    }
+                }
+            }
+        }
+    });
+    WebmentionQueue { sender }
+}
+
+async fn verify_and_save<W: WebmentionRepository>(client: &OutboundClient, repository: &W, webmention: &WebmentionEntity) {
+    let status = verify(client, webmention).await.unwrap_or_else(|e| {
+        tracing::warn!("fail fetch webmention source {}: {}", webmention.source, e);
+        WebmentionStatus::Rejected
+    });
+    if let Err(e) = repository.set_status(webmention.id, status).await {
+        tracing::warn!("fail save webmention {}: {}", webmention.id, e);
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use axum::Router;
+    use std::net::{SocketAddr, TcpListener};
+
+    // 外部サイトの代わりに 127.0.0.1 の空いているポートで router を動かす
+    pub fn serve(router: Router) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(router.into_make_service());
+        tokio::spawn(server);
+        addr
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{
+        extract::{Extension, Form},
+        http::{header, HeaderMap, HeaderValue},
+        response::Html,
+        routing::{get, post},
+        Router,
+    };
+    use chrono::Utc;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[test]
+    fn find_links_and_endpoints() {
+        let site = SiteConfig::default();
+        assert_eq!(Ok(1), validate("https://example.com/post", "http://localhost:3001/blogs/1", &site));
+        assert!(validate("ftp://example.com/post", "http://localhost:3001/blogs/1", &site).is_err());
+        assert!(validate("https://example.com/post", "https://example.com/blogs/1", &site).is_err());
+
+        let body = "[a](https://example.com/a) [self](http://localhost:3001/blogs/2) <https://example.com/a>\n\n\
+            <p><a href=\"https://example.org/?a=1&amp;b=2\">b</a></p>\n\n[c](/blogs/3)";
+        assert_eq!(
+            vec!["https://example.com/a".to_string(), "https://example.org/?a=1&b=2".to_string()],
+            outbound_links(body, &site)
+        );
+
+        let source = Url::parse("https://example.com/posts/1").unwrap();
+        assert!(links_to(r#"<p><a href="http://localhost:3001/blogs/1">post</a></p>"#, &source, "http://localhost:3001/blogs/1"));
+        assert!(!links_to(r#"<p>http://localhost:3001/blogs/1</p>"#, &source, "http://localhost:3001/blogs/1"));
+
+        let target = Url::parse("https://example.com/posts/1").unwrap();
+        let headers = vec![r#"<https://example.com/feed>; rel="alternate", </webmention?x=1>; rel="webmention""#.to_string()];
+        assert_eq!(
+            "https://example.com/webmention?x=1",
+            discover_endpoint(&target, &headers, "").unwrap().as_str()
+        );
+        let html = r#"<a href="/other" rel="me">me</a><link rel="WebMention" href="">"#;
+        assert_eq!("https://example.com/posts/1", discover_endpoint(&target, &[], html).unwrap().as_str());
+        assert_eq!(None, discover_endpoint(&target, &[], "<p>no endpoint</p>"));
+    }
+
+    #[tokio::test]
+    async fn verify_and_send_against_local_server() {
+        let received: Arc<Mutex<Vec<HashMap<String, String>>>> = Arc::default();
+        async fn endpoint(
+            Extension(received): Extension<Arc<Mutex<Vec<HashMap<String, String>>>>>,
+            Form(form): Form<HashMap<String, String>>,
+        ) -> StatusCode {
+            received.lock().unwrap().push(form);
+            StatusCode::ACCEPTED
+        }
+        async fn target() -> (HeaderMap, Html<&'static str>) {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::LINK, HeaderValue::from_static(r#"</endpoint>; rel="webmention""#));
+            (headers, Html("<p>target</p>"))
+        }
+        let router = Router::new()
+            .route("/linking", get(|| async { Html(r#"<a href="http://localhost:3001/blogs/1">post</a>"#) }))
+            .route("/unrelated", get(|| async { Html("<p>nothing</p>") }))
+            .route("/target", get(target))
+            .route("/endpoint", post(endpoint))
+            .layer(Extension(received.clone()));
+        let addr = test_utils::serve(router);
+        let client = OutboundClient::new(&OutboundConfig { allow_local: true });
+
+        let webmention = |source: &str| WebmentionEntity {
+            id: 1,
+            blog_id: 1,
+            source: format!("http://{}{}", addr, source),
+            target: "http://localhost:3001/blogs/1".to_string(),
+            status: WebmentionStatus::Pending,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        assert_eq!(WebmentionStatus::Verified, verify(&client, &webmention("/linking")).await.unwrap());
+        assert_eq!(WebmentionStatus::Rejected, verify(&client, &webmention("/unrelated")).await.unwrap());
+        assert_eq!(WebmentionStatus::Rejected, verify(&client, &webmention("/missing")).await.unwrap());
+        // 既定では手元のサーバーのような内部のアドレスは取得しない
+        let public_only = OutboundClient::new(&OutboundConfig::default());
+        assert!(verify(&public_only, &webmention("/linking")).await.is_err());
+
+        let target = format!("http://{}/target", addr);
+        assert!(send(&client, "http://localhost:3001/blogs/1", &target).await.unwrap());
+        assert!(!send(&client, "http://localhost:3001/blogs/1", &format!("http://{}/unrelated", addr)).await.unwrap());
+        let received = received.lock().unwrap();
+        assert_eq!(1, received.len());
+        assert_eq!("http://localhost:3001/blogs/1", received[0]["source"]);
+        assert_eq!(target, received[0]["target"]);
+    }
+}
@@ -0,0 +1,470 @@
+pub mod metaweblog;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use quick_xml::{events::Event, Reader};
+use std::collections::BTreeMap;
+use validator::ValidationErrors;
+
+use crate::render::escape_html;
+use crate::repositories::RepositoryError;
+
+pub const XMLRPC_PATH: &str = "/xmlrpc";
+pub const CONTENT_TYPE: &str = "text/xml; charset=utf-8";
+
+// fault code。WordPress の XML-RPC に合わせ、仕様外のものは xmlrpc-epi の値を使う
+pub const PARSE_ERROR: i32 = -32700;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+pub const BAD_REQUEST: i32 = 400;
+pub const FORBIDDEN: i32 = 403;
+pub const NOT_FOUND: i32 = 404;
+pub const CONFLICT: i32 = 409;
+pub const SERVER_ERROR: i32 = 500;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Boolean(bool),
+    String(String),
+    Double(f64),
+    DateTime(DateTime<Utc>),
+    // メディアのアップロードには対応しないので中身はそのまま持つ
+    Base64(String),
+    Struct(BTreeMap<String, Value>),
+    Array(Vec<Value>),
+    Nil,
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // postid などは文字列で送るクライアントと整数で送るクライアントがある
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(value) => Some(*value),
+            Value::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(value) => Some(*value),
+            Value::Int(value) => Some(*value != 0),
+            _ => None,
+        }
+    }
+
+    pub fn as_struct(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Value::Struct(members) => Some(members),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn to_xml(&self) -> String {
+        let inner = match self {
+            Value::Int(value) => format!("<int>{}</int>", value),
+            Value::Boolean(value) => format!("<boolean>{}</boolean>", *value as u8),
+            Value::String(value) => format!("<string>{}</string>", escape_html(value)),
+            Value::Double(value) => format!("<double>{}</double>", value),
+            Value::DateTime(value) => {
+                format!("<dateTime.iso8601>{}</dateTime.iso8601>", value.format("%Y%m%dT%H:%M:%S"))
+            }
+            Value::Base64(value) => format!("<base64>{}</base64>", value),
+            Value::Struct(members) => {
+                let members: String = members
+                    .iter()
+                    .map(|(name, value)| format!("<member><name>{}</name>{}</member>", escape_html(name), value.to_xml()))
+                    .collect();
+                format!("<struct>{}</struct>", members)
+            }
+            Value::Array(values) => {
+                let values: String = values.iter().map(Value::to_xml).collect();
+                format!("<array><data>{}</data></array>", values)
+            }
+            Value::Nil => "<nil/>".to_string(),
+        };
+        format!("<value>{}</value>", inner)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<DateTime<Utc>> for Value {
+    fn from(value: DateTime<Utc>) -> Self {
+        Value::DateTime(value)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(values: Vec<Value>) -> Self {
+        Value::Array(values)
+    }
+}
+
+impl From<BTreeMap<String, Value>> for Value {
+    fn from(members: BTreeMap<String, Value>) -> Self {
+        Value::Struct(members)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    pub code: i32,
+    pub message: String,
+}
+
+impl Fault {
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        Fault { code, message: message.into() }
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut members = BTreeMap::new();
+        members.insert("faultCode".to_string(), Value::Int(self.code as i64));
+        members.insert("faultString".to_string(), Value::String(self.message.clone()));
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<methodResponse><fault>{}</fault></methodResponse>\n",
+            Value::Struct(members).to_xml()
+        )
+    }
+}
+
+impl From<anyhow::Error> for Fault {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast_ref::<RepositoryError>() {
+            Some(RepositoryError::NotFound(id)) => Fault::new(NOT_FOUND, format!("post {} not found", id)),
+            Some(RepositoryError::Duplicate(id)) => Fault::new(CONFLICT, format!("duplicate data, id is {}", id)),
+            _ => Fault::new(SERVER_ERROR, e.to_string()),
+        }
+    }
+}
+
+impl From<ValidationErrors> for Fault {
+    fn from(e: ValidationErrors) -> Self {
+        Fault::new(BAD_REQUEST, e.to_string().replace('\n', ", "))
+    }
+}
+
+pub fn response(value: &Value) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<methodResponse><params><param>{}</param></params></methodResponse>\n",
+        value.to_xml()
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodCall {
+    pub name: String,
+    pub params: Vec<Value>,
+}
+
+impl MethodCall {
+    pub fn param(&self, index: usize) -> Result<&Value, Fault> {
+        self.params
+            .get(index)
+            .ok_or_else(|| Fault::new(INVALID_PARAMS, format!("{} requires parameter {}", self.name, index + 1)))
+    }
+
+    pub fn string(&self, index: usize) -> Result<&str, Fault> {
+        self.param(index)?.as_str().ok_or_else(|| self.invalid(index, "a string"))
+    }
+
+    pub fn int(&self, index: usize) -> Result<i64, Fault> {
+        self.param(index)?.as_i64().ok_or_else(|| self.invalid(index, "an integer"))
+    }
+
+    pub fn boolean(&self, index: usize) -> Result<bool, Fault> {
+        self.param(index)?.as_bool().ok_or_else(|| self.invalid(index, "a boolean"))
+    }
+
+    pub fn members(&self, index: usize) -> Result<&BTreeMap<String, Value>, Fault> {
+        self.param(index)?.as_struct().ok_or_else(|| self.invalid(index, "a struct"))
+    }
+
+    fn invalid(&self, index: usize, expected: &str) -> Fault {
+        Fault::new(INVALID_PARAMS, format!("parameter {} of {} must be {}", index + 1, self.name, expected))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Start(String),
+    End(String),
+    Text(String),
+}
+
+fn tokenize(xml: &str) -> Result<Vec<Token>, quick_xml::Error> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut tokens = Vec::new();
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(e) => tokens.push(Token::Start(String::from_utf8_lossy(e.name()).to_string())),
+            Event::Empty(e) => {
+                let name = String::from_utf8_lossy(e.name()).to_string();
+                tokens.push(Token::Start(name.clone()));
+                tokens.push(Token::End(name));
+            }
+            Event::End(e) => tokens.push(Token::End(String::from_utf8_lossy(e.name()).to_string())),
+            Event::Text(e) | Event::CData(e) => tokens.push(Token::Text(e.unescape_and_decode(&reader)?)),
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    // 要素の間の改行やインデント
+    fn skip_whitespace(&mut self) {
+        while let Some(Token::Text(text)) = self.peek() {
+            if !text.trim().is_empty() {
+                break;
+            }
+            self.position += 1;
+        }
+    }
+
+    fn text(&mut self) -> String {
+        let mut text = String::new();
+        while let Some(Token::Text(value)) = self.peek() {
+            text.push_str(value);
+            self.position += 1;
+        }
+        text
+    }
+
+    fn start(&mut self, name: &str) -> Result<(), Fault> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(Token::Start(found)) if found == name => {
+                self.position += 1;
+                Ok(())
+            }
+            found => Err(Fault::new(PARSE_ERROR, format!("expected <{}> but found {:?}", name, found))),
+        }
+    }
+
+    fn end(&mut self, name: &str) -> Result<(), Fault> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(Token::End(found)) if found == name => {
+                self.position += 1;
+                Ok(())
+            }
+            found => Err(Fault::new(PARSE_ERROR, format!("expected </{}> but found {:?}", name, found))),
+        }
+    }
+
+    fn is_start(&mut self, name: &str) -> bool {
+        self.skip_whitespace();
+        matches!(self.peek(), Some(Token::Start(found)) if found == name)
+    }
+
+    fn element_text(&mut self, name: &str) -> Result<String, Fault> {
+        self.start(name)?;
+        let text = self.text();
+        self.end(name)?;
+        Ok(text)
+    }
+
+    fn value(&mut self) -> Result<Value, Fault> {
+        self.start("value")?;
+        let text = self.text();
+        // 型の無い <value>text</value> は string
+        let kind = match self.peek() {
+            Some(Token::Start(kind)) if text.trim().is_empty() => kind.clone(),
+            _ => {
+                self.end("value")?;
+                return Ok(Value::String(text));
+            }
+        };
+        let invalid = |text: &str| Fault::new(PARSE_ERROR, format!("invalid {}: {}", kind, text));
+        let value = match kind.as_str() {
+            "int" | "i4" | "i8" => {
+                let text = self.element_text(&kind)?;
+                Value::Int(text.trim().parse().map_err(|_| invalid(&text))?)
+            }
+            "boolean" => match self.element_text(&kind)?.trim() {
+                "1" | "true" => Value::Boolean(true),
+                "0" | "false" => Value::Boolean(false),
+                text => return Err(invalid(text)),
+            },
+            "string" => Value::String(self.element_text(&kind)?),
+            "double" => {
+                let text = self.element_text(&kind)?;
+                Value::Double(text.trim().parse().map_err(|_| invalid(&text))?)
+            }
+            "dateTime.iso8601" => {
+                let text = self.element_text(&kind)?;
+                Value::DateTime(parse_datetime(text.trim()).ok_or_else(|| invalid(&text))?)
+            }
+            "base64" => Value::Base64(self.element_text(&kind)?.trim().to_string()),
+            "nil" => {
+                self.element_text(&kind)?;
+                Value::Nil
+            }
+            "struct" => {
+                self.start("struct")?;
+                let mut members = BTreeMap::new();
+                while self.is_start("member") {
+                    self.start("member")?;
+                    let name = self.element_text("name")?;
+                    let value = self.value()?;
+                    self.end("member")?;
+                    members.insert(name, value);
+                }
+                self.end("struct")?;
+                Value::Struct(members)
+            }
+            "array" => {
+                self.start("array")?;
+                self.start("data")?;
+                let mut values = Vec::new();
+                while self.is_start("value") {
+                    values.push(self.value()?);
+                }
+                self.end("data")?;
+                self.end("array")?;
+                Value::Array(values)
+            }
+            kind => return Err(Fault::new(PARSE_ERROR, format!("unknown type: {}", kind))),
+        };
+        self.end("value")?;
+        Ok(value)
+    }
+}
+
+// タイムゾーンの無いものは UTC として扱う
+fn parse_datetime(text: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+        return Some(date.with_timezone(&Utc));
+    }
+    let text = text.trim_end_matches('Z');
+    ["%Y%m%dT%H:%M:%S", "%Y%m%dT%H%M%S", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .map(|date| DateTime::from_utc(date, Utc))
+}
+
+pub fn parse_call(xml: &str) -> Result<MethodCall, Fault> {
+    let tokens = tokenize(xml).map_err(|e| Fault::new(PARSE_ERROR, e.to_string()))?;
+    let mut parser = Parser { tokens, position: 0 };
+    parser.start("methodCall")?;
+    let name = parser.element_text("methodName")?.trim().to_string();
+    let mut params = Vec::new();
+    if parser.is_start("params") {
+        parser.start("params")?;
+        while parser.is_start("param") {
+            parser.start("param")?;
+            params.push(parser.value()?);
+            parser.end("param")?;
+        }
+        parser.end("params")?;
+    }
+    parser.end("methodCall")?;
+    Ok(MethodCall { name, params })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parse_method_call() {
+        let xml = r#"<?xml version="1.0"?>
+<methodCall>
+  <methodName>metaWeblog.newPost</methodName>
+  <params>
+    <param><value><string>1</string></value></param>
+    <param><value>admin</value></param>
+    <param><value><i4>42</i4></value></param>
+    <param>
+      <value>
+        <struct>
+          <member><name>title</name><value><string>Hello &amp; &lt;world&gt;</string></value></member>
+          <member><name>description</name><value><![CDATA[<p>body</p>]]></value></member>
+          <member><name>dateCreated</name><value><dateTime.iso8601>20231113T09:00:00</dateTime.iso8601></value></member>
+          <member><name>categories</name><value><array><data><value>rust</value><value><string>axum</string></value></data></array></value></member>
+          <member><name>empty</name><value><string/></value></member>
+        </struct>
+      </value>
+    </param>
+    <param><value><boolean>1</boolean></value></param>
+  </params>
+</methodCall>"#;
+        let call = parse_call(xml).unwrap();
+        assert_eq!("metaWeblog.newPost", call.name);
+        assert_eq!(5, call.params.len());
+        assert_eq!("admin", call.string(1).unwrap());
+        assert_eq!(42, call.int(2).unwrap());
+        assert_eq!(1, call.int(0).unwrap());
+        assert!(call.boolean(4).unwrap());
+        let post = call.members(3).unwrap();
+        assert_eq!(Some("Hello & <world>"), post["title"].as_str());
+        assert_eq!(Some("<p>body</p>"), post["description"].as_str());
+        assert_eq!(Value::DateTime(Utc.ymd(2023, 11, 13).and_hms(9, 0, 0)), post["dateCreated"]);
+        assert_eq!(Value::Array(vec!["rust".into(), "axum".into()]), post["categories"]);
+        assert_eq!(Some(""), post["empty"].as_str());
+
+        assert_eq!(INVALID_PARAMS, call.members(0).unwrap_err().code);
+        assert_eq!(INVALID_PARAMS, call.param(5).unwrap_err().code);
+        assert_eq!(PARSE_ERROR, parse_call("<methodCall><params/></methodCall>").unwrap_err().code);
+    }
+
+    #[test]
+    fn serialize_response_and_fault() {
+        let mut members = BTreeMap::new();
+        members.insert("title".to_string(), Value::from("a < b"));
+        members.insert("dateCreated".to_string(), Value::from(Utc.ymd(2023, 11, 13).and_hms(9, 0, 0)));
+        let xml = response(&Value::Array(vec![Value::Struct(members), Value::Boolean(true)]));
+        assert!(xml.contains(
+            "<params><param><value><array><data><value><struct><member><name>dateCreated</name><value><dateTime.iso8601>20231113T09:00:00</dateTime.iso8601></value></member><member><name>title</name><value><string>a &lt; b</string></value></member></struct></value><value><boolean>1</boolean></value></data></array></value></param></params>"
+        ));
+
+        let xml = Fault::new(NOT_FOUND, "post 1 not found").to_xml();
+        assert!(xml.contains("<name>faultCode</name><value><int>404</int></value>"));
+        assert!(xml.contains("<name>faultString</name><value><string>post 1 not found</string></value>"));
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use super::{Fault, Value, INVALID_PARAMS};
+use crate::config::SiteConfig;
+use crate::import::html::html_to_markdown;
+use crate::render;
+use crate::repositories::{
+    blog::{BlogEntity, BlogSeo, BlogStatus, CreateBlog, UpdateBlog, UpdateBlogSeo},
+    tag::Tag,
+};
+
+// MetaWeblog の post の struct。description は本文を HTML にしたもの
+pub fn post_struct(blog: &BlogEntity, site: &SiteConfig) -> Value {
+    let url = site.blog_url(blog.id);
+    let tags: Vec<&str> = blog.tags.iter().map(|tag| tag.name.as_str()).collect();
+    let status = match blog.status {
+        BlogStatus::Published => "publish",
+        BlogStatus::Draft => "draft",
+    };
+    let mut members = BTreeMap::new();
+    members.insert("postid".to_string(), Value::from(blog.id.to_string()));
+    members.insert("userid".to_string(), Value::from("1"));
+    members.insert("title".to_string(), Value::from(blog.title.clone()));
+    members.insert("description".to_string(), Value::from(render::markdown_to_html(&blog.body)));
+    members.insert("link".to_string(), Value::from(url.clone()));
+    members.insert("permaLink".to_string(), Value::from(url));
+    members.insert(
+        "categories".to_string(),
+        Value::from(tags.iter().map(|tag| Value::from(*tag)).collect::<Vec<_>>()),
+    );
+    members.insert("mt_keywords".to_string(), Value::from(tags.join(",")));
+    members.insert(
+        "mt_excerpt".to_string(),
+        Value::from(blog.seo.description.clone().unwrap_or_default()),
+    );
+    members.insert("wp_slug".to_string(), Value::from(render::slug(blog)));
+    members.insert("post_status".to_string(), Value::from(status));
+    members.insert("dateCreated".to_string(), Value::from(blog.created_at));
+    members.insert("date_modified".to_string(), Value::from(blog.updated_at));
+    Value::from(members)
+}
+
+// getCategories の struct。カテゴリはタグとして扱う
+pub fn category_struct(tag: &Tag, site: &SiteConfig) -> Value {
+    let mut members = BTreeMap::new();
+    members.insert("categoryId".to_string(), Value::from(tag.id.to_string()));
+    members.insert("title".to_string(), Value::from(tag.name.clone()));
+    members.insert("description".to_string(), Value::from(tag.name.clone()));
+    members.insert("htmlUrl".to_string(), Value::from(site.url(&format!("/tags/{}/", tag.id))));
+    members.insert("rssUrl".to_string(), Value::from(site.url(&format!("/tags/{}/feed.atom", tag.id))));
+    Value::from(members)
+}
+
+// newPost / editPost で送られた struct。送られなかった項目は None
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PostFields {
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub categories: Option<Vec<String>>,
+    pub slug: Option<String>,
+    pub excerpt: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub status: BlogStatus,
+}
+
+impl PostFields {
+    pub fn from_struct(members: &BTreeMap<String, Value>, publish: bool) -> Result<Self, Fault> {
+        let text = |key: &str| -> Result<Option<String>, Fault> {
+            match members.get(key) {
+                None => Ok(None),
+                Some(value) => value
+                    .as_str()
+                    .map(|value| Some(value.to_string()))
+                    .ok_or_else(|| Fault::new(INVALID_PARAMS, format!("{} must be a string", key))),
+            }
+        };
+
+        // 続きを読む (mt_text_more) は本文の後ろに続ける
+        let body = match (text("description")?, text("mt_text_more")?) {
+            (None, None) => None,
+            (description, more) => {
+                let html = [description, more].iter().flatten().cloned().collect::<Vec<_>>().join("\n");
+                Some(html_to_markdown(&html))
+            }
+        };
+        // categories と mt_keywords (カンマ区切り) のどちらもタグにする
+        let mut names: Option<Vec<String>> = None;
+        if let Some(categories) = members.get("categories") {
+            let categories = categories
+                .as_array()
+                .ok_or_else(|| Fault::new(INVALID_PARAMS, "categories must be an array"))?;
+            names.get_or_insert_with(Vec::new).extend(
+                categories.iter().filter_map(Value::as_str).map(String::from),
+            );
+        }
+        if let Some(keywords) = text("mt_keywords")? {
+            names.get_or_insert_with(Vec::new).extend(keywords.split(',').map(String::from));
+        }
+        let categories = names.map(|names| {
+            let mut categories: Vec<String> = Vec::new();
+            for name in names.iter().map(|name| name.trim()).filter(|name| !name.is_empty()) {
+                if !categories.iter().any(|category| category == name) {
+                    categories.push(name.to_string());
+                }
+            }
+            categories
+        });
+        // post_status があれば publish フラグより優先する
+        let status = match text("post_status")?.as_deref() {
+            Some("publish") => BlogStatus::Published,
+            Some(_) => BlogStatus::Draft,
+            None if publish => BlogStatus::Published,
+            None => BlogStatus::Draft,
+        };
+        let published_at = match members.get("dateCreated") {
+            None => None,
+            Some(Value::DateTime(date)) => Some(*date),
+            Some(_) => return Err(Fault::new(INVALID_PARAMS, "dateCreated must be a dateTime.iso8601")),
+        };
+
+        Ok(PostFields {
+            title: text("title")?,
+            body,
+            categories,
+            slug: text("wp_slug")?.filter(|slug| !slug.is_empty()),
+            excerpt: text("mt_excerpt")?,
+            published_at,
+            status,
+        })
+    }
+
+    pub fn into_create_blog(self, tags: Vec<i32>) -> CreateBlog {
+        CreateBlog {
+            title: self.title.unwrap_or_default(),
+            body: self.body.unwrap_or_default(),
+            tags,
+            status: self.status,
+            seo: BlogSeo {
+                description: self.excerpt.filter(|excerpt| !excerpt.is_empty()),
+                ..BlogSeo::default()
+            },
+            slug: self.slug,
+            published_at: self.published_at,
+        }
+    }
+
+    // 送られた項目だけを変更する。公開日時は変更しない
+    pub fn into_update_blog(self, tags: Option<Vec<i32>>) -> UpdateBlog {
+        UpdateBlog {
+            title: self.title.map(Some),
+            body: self.body.map(Some),
+            tags: tags.map(Some),
+            status: Some(Some(self.status)),
+            seo: self.excerpt.map(|excerpt| {
+                Some(UpdateBlogSeo {
+                    description: Some(Some(excerpt).filter(|excerpt| !excerpt.is_empty())),
+                    ..UpdateBlogSeo::default()
+                })
+            }),
+            slug: self.slug.map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::tag::Tag;
+
+    #[test]
+    fn map_post_struct_to_blog() {
+        let mut members = BTreeMap::new();
+        members.insert("title".to_string(), Value::from("Hello"));
+        members.insert("description".to_string(), Value::from("<p>a <strong>b</strong></p>"));
+        members.insert("mt_text_more".to_string(), Value::from("<p>more</p>"));
+        members.insert("categories".to_string(), Value::from(vec![Value::from("rust")]));
+        members.insert("mt_keywords".to_string(), Value::from("axum, rust"));
+        members.insert("mt_excerpt".to_string(), Value::from(""));
+
+        let fields = PostFields::from_struct(&members, false).unwrap();
+        assert_eq!(Some("a **b**\n\nmore".to_string()), fields.body);
+        assert_eq!(Some(vec!["rust".to_string(), "axum".to_string()]), fields.categories);
+        assert_eq!(BlogStatus::Draft, fields.status);
+
+        let update = fields.clone().into_update_blog(None);
+        assert_eq!(Some(Some("Hello".to_string())), update.title);
+        assert_eq!(None, update.tags);
+        assert_eq!(None, update.slug);
+        assert_eq!(Some(None), update.seo.unwrap().unwrap().description);
+        let create = fields.into_create_blog(vec![1, 2]);
+        assert_eq!(None, create.seo.description);
+
+        members.insert("post_status".to_string(), Value::from("publish"));
+        members.insert("categories".to_string(), Value::from("rust"));
+        assert_eq!(INVALID_PARAMS, PostFields::from_struct(&members, false).unwrap_err().code);
+        members.remove("categories");
+        assert_eq!(BlogStatus::Published, PostFields::from_struct(&members, false).unwrap().status);
+    }
+
+    #[test]
+    fn build_post_struct_from_blog() {
+        let site = SiteConfig::default();
+        let blog = BlogEntity::new(
+            3,
+            "Hello".to_string(),
+            "# heading".to_string(),
+            vec![Tag::new(1, "rust".to_string()), Tag::new(2, "axum".to_string())],
+        );
+        let post = post_struct(&blog, &site);
+        let members = post.as_struct().unwrap();
+        assert_eq!(Some("3"), members["postid"].as_str());
+        assert_eq!(Some("<h1>heading</h1>\n"), members["description"].as_str());
+        assert_eq!(Some("rust,axum"), members["mt_keywords"].as_str());
+        assert_eq!(Some("http://localhost:3001/blogs/3"), members["permaLink"].as_str());
+        assert_eq!(Some("publish"), members["post_status"].as_str());
+
+        let category = category_struct(&Tag::new(1, "rust".to_string()), &site);
+        assert_eq!(
+            Some("http://localhost:3001/tags/1/feed.atom"),
+            category.as_struct().unwrap()["rssUrl"].as_str()
+        );
+    }
+}